@@ -29,11 +29,12 @@ fn list_device_map(
     println!("{1:0$}  Path:         {2}", padding, " ", device.path.display());
     println!("{1:0$}  Sector Size:  {2}", padding, " ", device.logical_sector_size);
     println!("{1:0$}  Sectors:      {2}", padding, " ", device.sectors);
+    println!("{1:0$}  Size:         {2}", padding, " ", device.size());
 
     if let Some((pv, vg)) = manager.pv(entity) {
         println!("{1:0$}  PV:           {2}", padding, " ", pv.path.display());
         println!("{1:0$}  PV UUID:      {2}", padding, " ", pv.uuid);
-        println!("{1:0$}  PV SizeBytes: {2}", padding, " ", pv.size_bytes);
+        println!("{1:0$}  PV Size:      {2}", padding, " ", pv.size());
         if let Some(vg) = vg {
             println!("{1:0$}  VG:          {2}", padding, " ", vg.name);
         }
@@ -55,6 +56,7 @@ fn list_disk(manager: &DiskManager, entity: DeviceEntity, disk_device: &Device)
     println!("  Path:        {}", disk_device.path.display());
     println!("  Sector Size: {}", disk_device.logical_sector_size);
     println!("  Sectors:     {}", disk_device.sectors);
+    println!("  Size:        {}", disk_device.size());
     match manager.partition(entity) {
         Some(partition) => list_partition(manager, entity, partition, 1, false),
         None => {
@@ -81,6 +83,8 @@ fn list_by_vg(manager: &DiskManager) {
         println!("  Extent Size:  {}", vg.extent_size);
         println!("  Extents:      {}", vg.extents);
         println!("  Extents Free: {}", vg.extents_free);
+        println!("  Size:         {}", vg.size());
+        println!("  Size Free:    {}", vg.size_free());
         for (lv_entity, lv) in manager.lvm_lvs_of_vg(entity) {
             let partition = manager.partition(lv_entity).expect("LV that isn't a partition");
             println!("  Child: {}", lv.name);
@@ -105,6 +109,7 @@ fn list_partition(
     println!("{1:0$}Sector Size: {2}", padding, " ", device.logical_sector_size);
     println!("{1:0$}Offset:      {2}", padding, " ", partition.offset);
     println!("{1:0$}Length:      {2}", padding, " ", device.sectors);
+    println!("{1:0$}Size:        {2}", padding, " ", device.size());
     println!("{1:0$}Number:      {2}", padding, " ", partition.number);
 
     if let Some(fs) = partition.filesystem {
@@ -115,6 +120,10 @@ fn list_partition(
         println!("{1:0$}UUID:        {2}", padding, " ", uuid);
     }
 
+    if let Some(label) = &partition.label {
+        println!("{1:0$}Label:       {2}", padding, " ", label);
+    }
+
     if let Some(partuuid) = &partition.partuuid {
         println!("{1:0$}PartUUID:    {2}", padding, " ", partuuid);
     }
@@ -1,6 +1,6 @@
 extern crate disk_ops;
-use disk_ops::table::{PartitionResult, Partitioner};
-use disk_types::{FileSystem, PartitionTable};
+use disk_ops::table::{NewPartition, PartitionResult, Partitioner};
+use disk_types::{FileSystem, FormatOptions, PartitionKind, PartitionTable};
 use std::{error::Error, io, path::Path};
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -11,23 +11,54 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     let root_size = 16 * 1024 * 1024 * 1024;
 
-    let efi = table.add(1024, 1024000, "EFI".into())?;
-    let root = table.add(1024000, root_size / 512, "Root".into())?;
+    let efi = table.add(NewPartition {
+        start: 1024,
+        end: 1023999,
+        name: "EFI".into(),
+        kind: PartitionKind::EfiSystem,
+        ..Default::default()
+    })?;
+
+    let root = table.add(NewPartition {
+        start: 1024000,
+        end: root_size / 512,
+        name: "Root".into(),
+        ..Default::default()
+    })?;
 
     table.write()?;
 
-    disk_ops::partition::create(Path::new(&format!("/dev/sdb{}", efi)), FileSystem::Vfat)?;
+    let options = FormatOptions::default();
+    disk_ops::partition::format(
+        Path::new(&format!("/dev/sdb{}", efi)),
+        FileSystem::Vfat,
+        &options,
+    )?;
 
-    disk_ops::partition::create(Path::new(&format!("/dev/sdb{}", root)), FileSystem::Btrfs)?;
+    disk_ops::partition::format(
+        Path::new(&format!("/dev/sdb{}", root)),
+        FileSystem::Btrfs,
+        &options,
+    )?;
 
     table = disk_ops::table::Gpt::open(path)?;
     table.remove(1024001)?;
 
-    let home = table.add(root_size / 512, table.last_sector(), "Home".into())?;
+    let home = table.add(NewPartition {
+        start: root_size / 512 + 1,
+        end: table.last_sector(),
+        name: "Home".into(),
+        kind: PartitionKind::LinuxHome,
+        ..Default::default()
+    })?;
 
     table.write()?;
 
-    disk_ops::partition::create(Path::new(&format!("/dev/sdb{}", home)), FileSystem::Btrfs)?;
+    disk_ops::partition::format(
+        Path::new(&format!("/dev/sdb{}", home)),
+        FileSystem::Btrfs,
+        &options,
+    )?;
 
     Ok(())
 }
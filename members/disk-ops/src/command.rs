@@ -0,0 +1,141 @@
+//! Execution of the external programs which disk operations are carried out with.
+
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    process::{Command, ExitStatus, Stdio},
+    sync::mpsc,
+    thread,
+};
+
+/// An error from running an external program, which names the command that was run.
+#[derive(Debug, Error)]
+pub enum CommandError {
+    #[error(display = "{} exited with failure status: {}: {}", _0, _1, _2)]
+    ExitStatus(Box<str>, ExitStatus, Box<str>),
+    #[error(display = "{} was not found; is it installed?", _0)]
+    NotFound(Box<str>),
+    #[error(display = "failed to spawn {}", _0)]
+    Spawn(Box<str>, #[error(cause)] io::Error),
+    #[error(display = "failed to write to the stdin of {}", _0)]
+    StdinWrite(Box<str>, #[error(cause)] io::Error),
+    #[error(display = "failed to wait on {}", _0)]
+    Wait(Box<str>, #[error(cause)] io::Error),
+}
+
+impl From<CommandError> for io::Error {
+    fn from(error: CommandError) -> Self {
+        let kind = match error {
+            CommandError::NotFound(_) => io::ErrorKind::NotFound,
+            _ => io::ErrorKind::Other,
+        };
+
+        io::Error::new(kind, error)
+    }
+}
+
+/// Runs a command, and errors if it did not exit successfully.
+///
+/// Whatever the command writes to stderr is passed through, and kept in the error if it fails.
+pub fn run(cmd: &mut Command) -> Result<(), CommandError> { run_with_input(cmd, None) }
+
+/// Runs a command with the given input written to its stdin, and errors if it did not exit
+/// successfully.
+pub fn run_with_input(cmd: &mut Command, input: Option<&[u8]>) -> Result<(), CommandError> {
+    eprintln!("running command: {:?}", cmd);
+
+    let mut child = cmd
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|why| spawn_error(cmd, why))?;
+
+    if let Some(input) = input {
+        // The pipe is closed when stdin is dropped, so that the command is not left waiting.
+        let mut stdin = child.stdin.take().expect("stdin was not piped");
+        stdin.write_all(input).map_err(|why| CommandError::StdinWrite(describe(cmd), why))?;
+    }
+
+    let output = child.wait_with_output().map_err(|why| CommandError::Wait(describe(cmd), why))?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    eprint!("{}", stderr);
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(CommandError::ExitStatus(describe(cmd), output.status, stderr.trim().into()))
+    }
+}
+
+/// Runs a command, passing each line that it writes to stdout and stderr to `func`, and
+/// returns its exit status, which it is up to the caller to interpret.
+pub fn run_lines(
+    cmd: &mut Command,
+    mut func: impl FnMut(&str),
+) -> Result<ExitStatus, CommandError> {
+    eprintln!("running command: {:?}", cmd);
+
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|why| spawn_error(cmd, why))?;
+
+    let (tx, rx) = mpsc::channel();
+    let readers = vec![
+        forward_lines(child.stdout.take().expect("stdout was not piped"), tx.clone()),
+        forward_lines(child.stderr.take().expect("stderr was not piped"), tx),
+    ];
+
+    for line in rx {
+        eprintln!("{}", line);
+        func(&line);
+    }
+
+    for reader in readers {
+        let _ = reader.join();
+    }
+
+    child.wait().map_err(|why| CommandError::Wait(describe(cmd), why))
+}
+
+/// Sends each line read from a stream to the receiver, from a background thread.
+fn forward_lines<R: Read + Send + 'static>(
+    stream: R,
+    tx: mpsc::Sender<String>,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        for line in BufReader::new(stream).lines() {
+            match line {
+                Ok(line) => {
+                    if tx.send(line).is_err() {
+                        break;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+    })
+}
+
+fn spawn_error(cmd: &Command, why: io::Error) -> CommandError {
+    if why.kind() == io::ErrorKind::NotFound {
+        CommandError::NotFound(program(cmd))
+    } else {
+        CommandError::Spawn(describe(cmd), why)
+    }
+}
+
+/// The command line, as it is shown by the `Debug` implementation of `Command`.
+fn describe(cmd: &Command) -> Box<str> { format!("{:?}", cmd).into() }
+
+/// The name of the program that a command runs.
+fn program(cmd: &Command) -> Box<str> {
+    let described = format!("{:?}", cmd);
+    let program = match described.find("\" ") {
+        Some(end) => &described[..end],
+        None => &described,
+    };
+
+    program.trim_matches('"').into()
+}
@@ -3,17 +3,27 @@ extern crate err_derive;
 #[macro_use]
 extern crate cascade;
 #[macro_use]
+extern crate nix;
+#[macro_use]
 extern crate shrinkwraprs;
 
 use std::{io, path::Path};
 
+pub mod command;
+pub mod relocate;
 pub mod table;
+pub mod tools;
 
 pub mod partition {
-    use disk_types::FileSystem;
-    use std::{io, path::Path, process::Command};
+    use crate::command::{self, run_lines};
+    use disk_types::{CheckMode, CheckVerdict, FileSystem, FormatOptions};
+    use std::{fs, io, path::Path, process::Command};
 
-    pub fn format(device: &Path, fs: FileSystem) -> io::Result<()> {
+    /// Creates a file system on a device, with the given options.
+    ///
+    /// Options should be validated with `FormatOptions::validate` beforehand, as options which
+    /// the file system does not support are ignored.
+    pub fn format(device: &Path, fs: FileSystem, options: &FormatOptions) -> io::Result<()> {
         let (cmd, args): (&'static str, &'static [&'static str]) = match fs {
             FileSystem::Btrfs => ("mkfs.btrfs", &["-f"]),
             FileSystem::Exfat => ("mkfs.exfat", &[]),
@@ -24,8 +34,12 @@ pub mod partition {
             FileSystem::Vfat => ("mkfs.fat", &["-F", "32"]),
             FileSystem::Ntfs => ("mkfs.ntfs", &["-FQ", "-q"]),
             FileSystem::Swap => {
+                // Existing swap spaces are kept, but are still given the requested label.
                 if swap_exists(device) {
-                    return Ok(());
+                    return match options.label {
+                        Some(ref name) => label(device, fs, name),
+                        None => Ok(()),
+                    };
                 }
 
                 ("mkswap", &["-f"])
@@ -35,17 +49,391 @@ pub mod partition {
             _ => unimplemented!("creating unsupported file system"),
         };
 
-        let mut cmd = Command::new(cmd);
-        cmd.args(args).arg(device);
+        run(Command::new(cmd).args(args).args(option_args(device, fs, options)?).arg(device))
+    }
+
+    /// Translates format options into the arguments of the file system's mkfs tool.
+    fn option_args(
+        device: &Path,
+        fs: FileSystem,
+        options: &FormatOptions,
+    ) -> io::Result<Vec<String>> {
+        let mut args = Vec::new();
+        let mut push = |flag: &str, value: String| {
+            args.push(flag.to_owned());
+            args.push(value);
+        };
+
+        let is_ext = match fs {
+            FileSystem::Ext2 | FileSystem::Ext3 | FileSystem::Ext4 => true,
+            _ => false,
+        };
+
+        if let Some(ref label) = options.label {
+            match fs {
+                FileSystem::F2fs => push("-l", label.to_string()),
+                FileSystem::Vfat => push("-n", label.to_string()),
+                _ => push("-L", label.to_string()),
+            }
+        }
+
+        match (options.block_size, fs) {
+            (Some(size), _) if is_ext => push("-b", size.to_string()),
+            (Some(size), FileSystem::Xfs) => push("-b", format!("size={}", size)),
+            _ => (),
+        }
+
+        match options.inode_ratio {
+            Some(ratio) if is_ext => push("-i", ratio.to_string()),
+            _ => (),
+        }
+
+        match (options.cluster_size, fs) {
+            // mkfs.fat measures clusters in logical sectors.
+            (Some(size), FileSystem::Vfat) => {
+                let sector_size = crate::table::logical_sector_size(&fs::File::open(device)?);
+                if u64::from(size) < sector_size {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "FAT clusters may not be smaller than a logical sector",
+                    ));
+                }
+
+                push("-s", (u64::from(size) / sector_size).to_string());
+            }
+            (Some(size), FileSystem::Exfat) | (Some(size), FileSystem::Ntfs) => {
+                push("-c", size.to_string())
+            }
+            _ => (),
+        }
 
-        eprintln!("creating file system: {:?}", cmd);
+        if fs == FileSystem::Btrfs {
+            if let Some(profile) = options.data_profile {
+                push("-d", <&'static str>::from(profile).to_owned());
+            }
 
-        cmd.status()?;
+            if let Some(profile) = options.metadata_profile {
+                push("-m", <&'static str>::from(profile).to_owned());
+            }
+        }
 
-        Ok(())
+        Ok(args)
     }
 
-    pub fn resize(device: &Path, fs: FileSystem) -> io::Result<()> { unimplemented!() }
+    /// Checks the file system on a device, which must not be mounted, and repairs it as the
+    /// mode permits.
+    ///
+    /// Progress is reported as the number of steps completed out of the total, by the tools
+    /// which report it: e2fsck, `btrfs check`, and xfs_repair.
+    pub fn check(
+        device: &Path,
+        fs: FileSystem,
+        mode: CheckMode,
+        mut progress: impl FnMut(u64, u64),
+    ) -> io::Result<CheckVerdict> {
+        let (cmd, args): (&'static str, &'static [&'static str]) = match (fs, mode) {
+            // btrfs has no repair which is safe to run unattended.
+            (FileSystem::Btrfs, CheckMode::ForceUnsafe) => ("btrfs", &["check", "--repair"]),
+            (FileSystem::Btrfs, _) => ("btrfs", &["check", "--readonly"]),
+            (FileSystem::Ext2, _) | (FileSystem::Ext3, _) | (FileSystem::Ext4, _) => match mode {
+                CheckMode::DryRun => ("e2fsck", &["-n", "-C", "1"]),
+                CheckMode::Repair => ("e2fsck", &["-p", "-C", "1"]),
+                CheckMode::ForceRepair => ("e2fsck", &["-f", "-p", "-C", "1"]),
+                CheckMode::Force | CheckMode::ForceUnsafe => ("e2fsck", &["-f", "-y", "-C", "1"]),
+            },
+            (FileSystem::F2fs, CheckMode::DryRun) => ("fsck.f2fs", &["--dry-run"]),
+            (FileSystem::F2fs, CheckMode::Repair) => ("fsck.f2fs", &["-a"]),
+            (FileSystem::F2fs, CheckMode::ForceRepair) => ("fsck.f2fs", &["-a", "-f"]),
+            (FileSystem::F2fs, _) => ("fsck.f2fs", &["-f", "-y"]),
+            // ntfsfix, fsck.fat, and xfs_repair check file systems whether or not they are clean.
+            (FileSystem::Ntfs, CheckMode::DryRun) => ("ntfsfix", &["-n"]),
+            (FileSystem::Ntfs, CheckMode::Repair) | (FileSystem::Ntfs, CheckMode::ForceRepair) => {
+                ("ntfsfix", &[])
+            }
+            (FileSystem::Ntfs, _) => ("ntfsfix", &["-d"]),
+            (FileSystem::Vfat, CheckMode::DryRun) => ("fsck.fat", &["-n"]),
+            (FileSystem::Vfat, CheckMode::Repair) | (FileSystem::Vfat, CheckMode::ForceRepair) => {
+                ("fsck.fat", &["-a"])
+            }
+            (FileSystem::Vfat, _) => ("fsck.fat", &["-a", "-V"]),
+            (FileSystem::Xfs, CheckMode::DryRun) => ("xfs_repair", &["-n"]),
+            (FileSystem::Xfs, CheckMode::Repair) | (FileSystem::Xfs, CheckMode::ForceRepair) => {
+                ("xfs_repair", &[])
+            }
+            // Zeroes a log which cannot be replayed, discarding the changes within it.
+            (FileSystem::Xfs, _) => ("xfs_repair", &["-L"]),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{} file systems cannot be checked", fs),
+                ))
+            }
+        };
+
+        let mut total = None;
+        let status = run_lines(Command::new(cmd).args(args).arg(device), |line| {
+            if let Some((done, steps)) = check_progress(fs, line) {
+                total = Some(steps);
+                progress(done, steps);
+            }
+        })?;
+
+        let code = status.code().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::Other, format!("{} was terminated: {}", cmd, status))
+        })?;
+
+        let verdict = check_verdict(fs, mode, code).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                format!("{} failed with exit status {}", cmd, code),
+            )
+        })?;
+
+        if let Some(total) = total {
+            progress(total, total);
+        }
+
+        Ok(verdict)
+    }
+
+    /// The outcome of a check, from the exit status of the check tool for a file system, or
+    /// `None` if the check itself failed.
+    pub fn check_verdict(fs: FileSystem, mode: CheckMode, code: i32) -> Option<CheckVerdict> {
+        let repairing = mode != CheckMode::DryRun;
+        let verdict = match (fs, code) {
+            // These exit successfully whether or not they repaired anything.
+            (FileSystem::Ntfs, 0) | (FileSystem::Xfs, 0) if repairing => {
+                CheckVerdict::CleanOrRepaired
+            }
+            (FileSystem::Btrfs, 0) if mode == CheckMode::ForceUnsafe => {
+                CheckVerdict::CleanOrRepaired
+            }
+            (_, 0) => CheckVerdict::Clean,
+            // e2fsck and fsck.f2fs exit with the bit flags of fsck.
+            (FileSystem::Ext2, code)
+            | (FileSystem::Ext3, code)
+            | (FileSystem::Ext4, code)
+            | (FileSystem::F2fs, code)
+                if code < 8 =>
+            {
+                if code & 4 != 0 {
+                    CheckVerdict::ErrorsRemaining
+                } else {
+                    CheckVerdict::Repaired
+                }
+            }
+            (FileSystem::Vfat, 1) if repairing => CheckVerdict::Repaired,
+            (FileSystem::Vfat, 1) => CheckVerdict::ErrorsRemaining,
+            // Either corruption was found without modifying, or the log must first be replayed.
+            (FileSystem::Xfs, 1) | (FileSystem::Xfs, 2) => CheckVerdict::ErrorsRemaining,
+            (FileSystem::Btrfs, 1) | (FileSystem::Ntfs, 1) => CheckVerdict::ErrorsRemaining,
+            _ => return None,
+        };
+
+        Some(verdict)
+    }
+
+    /// Parses the steps completed, and the total steps, from a line of a check tool's output.
+    pub fn check_progress(fs: FileSystem, line: &str) -> Option<(u64, u64)> {
+        match fs {
+            // Written as `pass current max device` to the file descriptor given to `-C`.
+            FileSystem::Ext2 | FileSystem::Ext3 | FileSystem::Ext4 => {
+                let mut fields = line.split_whitespace().map(|field| field.parse::<u64>());
+                match (fields.next(), fields.next(), fields.next()) {
+                    (Some(Ok(pass)), Some(Ok(current)), Some(Ok(max)))
+                        if pass >= 1 && pass <= 5 && current <= max && max != 0 =>
+                    {
+                        Some(((pass - 1) * 1000 + current * 1000 / max, 5000))
+                    }
+                    _ => None,
+                }
+            }
+            // Written as `[1/7] checking root items`.
+            FileSystem::Btrfs => {
+                let line = line.trim_start();
+                if !line.starts_with('[') {
+                    return None;
+                }
+
+                let end = line.find(']')?;
+                let mut fields = line[1..end].splitn(2, '/').map(|field| field.parse::<u64>());
+                match (fields.next(), fields.next()) {
+                    (Some(Ok(step)), Some(Ok(steps))) if step >= 1 && step <= steps => {
+                        Some((step - 1, steps))
+                    }
+                    _ => None,
+                }
+            }
+            // Written as `Phase 1 - find and verify superblock...`, for each of seven phases.
+            FileSystem::Xfs => {
+                let phase = line.trim_start().split_whitespace();
+                let mut phase = phase.skip_while(|&word| word != "Phase").skip(1);
+                match phase.next().map(str::parse::<u64>) {
+                    Some(Ok(phase)) if phase >= 1 && phase <= 7 => Some((phase - 1, 7)),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Resizes the file system on a device to the given size, in bytes.
+    ///
+    /// File systems must be shrunk before their partition is, and grown after it. Ext
+    /// file systems must first be checked in the `ForceRepair` mode, as resize2fs requires.
+    pub fn resize(device: &Path, fs: FileSystem, size: u64) -> io::Result<()> {
+        match fs {
+            FileSystem::Ext2 | FileSystem::Ext3 | FileSystem::Ext4 => {
+                run(Command::new("resize2fs").arg(device).arg(format!("{}K", size / 1024)))
+            }
+            FileSystem::Btrfs => with_mount(device, |mount| {
+                run(Command::new("btrfs")
+                    .args(&["filesystem", "resize"])
+                    .arg(size.to_string())
+                    .arg(mount))
+            }),
+            // XFS can only be grown, and always fills the partition.
+            FileSystem::Xfs => {
+                with_mount(device, |mount| run(Command::new("xfs_growfs").arg(mount)))
+            }
+            FileSystem::Ntfs => run(Command::new("ntfsresize")
+                .args(&["--force", "--force", "--size"])
+                .arg(size.to_string())
+                .arg(device)),
+            // F2FS can only be grown, and measures its size in 512-byte sectors.
+            FileSystem::F2fs => {
+                run(Command::new("resize.f2fs").arg("-t").arg((size / 512).to_string()).arg(device))
+            }
+            FileSystem::Vfat => {
+                run(Command::new("fatresize").arg("--size").arg(size.to_string()).arg(device))
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} file systems cannot be resized", fs),
+            )),
+        }
+    }
+
+    /// Sets the label of the file system on a device, which must not be mounted.
+    pub fn label(device: &Path, fs: FileSystem, label: &str) -> io::Result<()> {
+        let (cmd, args): (&'static str, &'static [&'static str]) = match fs {
+            FileSystem::Btrfs => ("btrfs", &["filesystem", "label"]),
+            FileSystem::Exfat => ("exfatlabel", &[]),
+            FileSystem::Ext2 | FileSystem::Ext3 | FileSystem::Ext4 => ("e2label", &[]),
+            FileSystem::Ntfs => ("ntfslabel", &[]),
+            FileSystem::Vfat => ("fatlabel", &[]),
+            // These take the label as an option, rather than following the device.
+            FileSystem::Swap => {
+                return run(Command::new("swaplabel").arg("-L").arg(label).arg(device))
+            }
+            FileSystem::Xfs => {
+                return run(Command::new("xfs_admin").arg("-L").arg(label).arg(device))
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{} file systems cannot be labeled", fs),
+                ))
+            }
+        };
+
+        run(Command::new(cmd).args(args).arg(device).arg(label))
+    }
+
+    /// Gives the file system on a device a new, random UUID, which must not be mounted.
+    ///
+    /// The new UUID is returned as blkid reports it, unless it was chosen by the tool itself.
+    pub fn regenerate_uuid(device: &Path, fs: FileSystem) -> io::Result<Option<Box<str>>> {
+        let bytes = crate::table::generate_random_uuid();
+        let hex =
+            |bytes: &[u8]| bytes.iter().map(|byte| format!("{:02X}", byte)).collect::<String>();
+
+        // FAT and exFAT file systems have a 32-bit serial, and NTFS file systems a 64-bit serial.
+        let serial = hex(&bytes[..4]);
+        let serial = [&serial[..4], "-", &serial[4..]].concat();
+        let uuid = {
+            let mut bytes = bytes;
+            bytes[6] = (bytes[6] & 0x0F) | 0x40;
+            bytes[8] = (bytes[8] & 0x3F) | 0x80;
+            [&bytes[..4], &bytes[4..6], &bytes[6..8], &bytes[8..10], &bytes[10..]]
+                .iter()
+                .map(|bytes| hex(bytes).to_lowercase())
+                .collect::<Vec<_>>()
+                .join("-")
+        };
+
+        let new = match fs {
+            FileSystem::Btrfs => {
+                run(Command::new("btrfstune").args(&["-f", "-U"]).arg(&uuid).arg(device))?;
+                uuid
+            }
+            FileSystem::Exfat => {
+                let id = ["0x", &serial.replace("-", "")].concat();
+                run(Command::new("tune.exfat").arg("-I").arg(id).arg(device))?;
+                serial
+            }
+            FileSystem::Ext2 | FileSystem::Ext3 | FileSystem::Ext4 => {
+                run(Command::new("tune2fs").arg("-U").arg(&uuid).arg(device))?;
+                uuid
+            }
+            FileSystem::Luks => {
+                run(Command::new("cryptsetup")
+                    .args(&["-q", "luksUUID", "--uuid"])
+                    .arg(&uuid)
+                    .arg(device))?;
+                uuid
+            }
+            FileSystem::Lvm => {
+                run(Command::new("pvchange").arg("--uuid").arg(device))?;
+                return Ok(None);
+            }
+            FileSystem::Ntfs => {
+                let serial = hex(&bytes[..8]);
+                run(Command::new("ntfslabel")
+                    .arg(["--new-serial=", &serial].concat())
+                    .arg(device))?;
+                serial
+            }
+            // The label is left untouched, which is not the case when recreating the swap.
+            FileSystem::Swap => {
+                run(Command::new("swaplabel").arg("-U").arg(&uuid).arg(device))?;
+                uuid
+            }
+            FileSystem::Vfat => {
+                run(Command::new("fatlabel").arg("-i").arg(device).arg(serial.replace("-", "")))?;
+                serial
+            }
+            FileSystem::Xfs => {
+                run(Command::new("xfs_admin").arg("-U").arg(&uuid).arg(device))?;
+                uuid
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("the UUID of {} file systems cannot be changed", fs),
+                ))
+            }
+        };
+
+        Ok(Some(new.into()))
+    }
+
+    /// Runs a command, and errors if it did not exit successfully.
+    fn run(cmd: &mut Command) -> io::Result<()> { command::run(cmd).map_err(io::Error::from) }
+
+    /// Temporarily mounts a device, for file systems which can only be resized while mounted.
+    fn with_mount(device: &Path, func: impl FnOnce(&Path) -> io::Result<()>) -> io::Result<()> {
+        let mount = std::env::temp_dir().join(format!("disk-ops-{}", std::process::id()));
+        fs::create_dir_all(&mount)?;
+
+        let result = run(Command::new("mount").arg(device).arg(&mount)).and_then(|_| {
+            let result = func(&mount);
+            run(Command::new("umount").arg(&mount)).and(result)
+        });
+
+        let _ = fs::remove_dir(&mount);
+        result
+    }
 
     fn swap_exists(path: &Path) -> bool {
         Command::new("swaplabel").arg(path).status().ok().map_or(false, |stat| stat.success())
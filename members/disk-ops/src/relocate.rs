@@ -0,0 +1,84 @@
+//! Relocation of partition data within a block device.
+
+use std::{
+    cmp,
+    fs::OpenOptions,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// Data is copied in chunks of this many bytes.
+const CHUNK_SIZE: u64 = 4 * 1024 * 1024;
+
+#[derive(Debug, Error)]
+pub enum MoveError {
+    #[error(display = "the move was cancelled before any data was overwritten")]
+    Cancelled,
+    #[error(display = "unable to open device")]
+    DeviceOpen(#[error(cause)] io::Error),
+    #[error(display = "unable to read from sector {}", _0)]
+    Read(u64, #[error(cause)] io::Error),
+    #[error(display = "unable to sync changes to the device")]
+    Sync(#[error(cause)] io::Error),
+    #[error(display = "unable to write to sector {}", _0)]
+    Write(u64, #[error(cause)] io::Error),
+}
+
+/// Copies `sectors` sectors on a device from the `from` sector to the `to` sector.
+///
+/// The source and destination may overlap. Data is copied backwards when moving right, and
+/// forwards when moving left, so that each chunk is read before it is overwritten.
+///
+/// The `progress` callback receives the number of sectors copied, and the total. Cancellation
+/// is honoured between chunks, until the first chunk that overwrites the source is written.
+/// After that point, stopping would leave neither copy of the data intact.
+pub fn move_sectors(
+    device: &Path,
+    from: u64,
+    to: u64,
+    sectors: u64,
+    sector_size: u64,
+    cancel: &AtomicBool,
+    mut progress: impl FnMut(u64, u64),
+) -> Result<(), MoveError> {
+    if from == to || sectors == 0 {
+        return Ok(());
+    }
+
+    let mut device =
+        OpenOptions::new().read(true).write(true).open(device).map_err(MoveError::DeviceOpen)?;
+
+    let chunk = cmp::max(1, CHUNK_SIZE / sector_size);
+    let mut buffer = vec![0u8; (chunk * sector_size) as usize];
+    let mut overwritten = false;
+    let mut copied = 0;
+
+    while copied < sectors {
+        let length = cmp::min(chunk, sectors - copied);
+        let offset = if to > from { sectors - copied - length } else { copied };
+        let (source, target) = (from + offset, to + offset);
+
+        overwritten = overwritten || (target < from + sectors && target + length > from);
+        if !overwritten && cancel.load(Ordering::SeqCst) {
+            return Err(MoveError::Cancelled);
+        }
+
+        let buffer = &mut buffer[..(length * sector_size) as usize];
+
+        device
+            .seek(SeekFrom::Start(source * sector_size))
+            .and_then(|_| device.read_exact(buffer))
+            .map_err(|why| MoveError::Read(source, why))?;
+
+        device
+            .seek(SeekFrom::Start(target * sector_size))
+            .and_then(|_| device.write_all(buffer))
+            .map_err(|why| MoveError::Write(target, why))?;
+
+        copied += length;
+        progress(copied, sectors);
+    }
+
+    device.sync_all().map_err(MoveError::Sync)
+}
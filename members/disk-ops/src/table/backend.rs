@@ -0,0 +1,125 @@
+//! Storage which partition tables are read from and written to.
+//!
+//! Partition tables are usually written to block devices, but they may also be written to disk
+//! image files and in-memory buffers, which require no privileges to partition.
+
+use super::{SyncError, TablePartition};
+use std::{
+    fs::File,
+    io::{self, Cursor, Read, Seek, SeekFrom, Write},
+};
+
+/// A seekable store of sectors which a partition table can be kept on.
+///
+/// The size of the backend is found by seeking to its end.
+pub trait BlockBackend: Read + Write + Seek {
+    /// The logical sector size of the backend, which is 512 bytes unless it says otherwise.
+    fn logical_sector_size(&self) -> u64 { 512 }
+
+    /// Ensures that everything written so far has reached the storage.
+    fn sync(&mut self) -> io::Result<()> { self.flush() }
+
+    /// Informs the kernel of the partitions now in the table, if the backend is a block device.
+    fn reload(
+        &mut self,
+        _partitions: &[TablePartition],
+        _sector_size: u64,
+    ) -> Result<(), SyncError> {
+        Ok(())
+    }
+}
+
+/// Block devices, and disk image files.
+impl BlockBackend for File {
+    fn logical_sector_size(&self) -> u64 { super::logical_sector_size(self) }
+
+    fn sync(&mut self) -> io::Result<()> { self.sync_all() }
+
+    fn reload(&mut self, partitions: &[TablePartition], sector_size: u64) -> Result<(), SyncError> {
+        super::sync_kernel(self, partitions, sector_size)
+    }
+}
+
+/// An in-memory disk image, which is extended when written beyond its end.
+impl BlockBackend for Cursor<Vec<u8>> {}
+
+/// A borrowed in-memory disk image, whose size is fixed.
+impl<'a> BlockBackend for Cursor<&'a mut [u8]> {}
+
+/// An image of a given size which keeps none of its data, so that a table can be laid out for
+/// a device without allocating, or touching, its sectors.
+///
+/// Reads give zeroes, and writes are discarded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NullImage {
+    size:        u64,
+    sector_size: u64,
+    position:    u64,
+}
+
+impl NullImage {
+    /// An image with the given number of sectors, of the given size in bytes.
+    pub fn new(sectors: u64, sector_size: u64) -> Self {
+        NullImage { size: sectors * sector_size, sector_size, position: 0 }
+    }
+}
+
+impl Read for NullImage {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.size.saturating_sub(self.position);
+        let read = (buf.len() as u64).min(remaining) as usize;
+        for byte in &mut buf[..read] {
+            *byte = 0;
+        }
+
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl Write for NullImage {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.position += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> { Ok(()) }
+}
+
+impl Seek for NullImage {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => checked_offset(self.size, offset),
+            SeekFrom::Current(offset) => checked_offset(self.position, offset),
+        };
+
+        self.position = position.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "seek to a negative position")
+        })?;
+
+        Ok(self.position)
+    }
+}
+
+impl BlockBackend for NullImage {
+    fn logical_sector_size(&self) -> u64 { self.sector_size }
+}
+
+impl<'a, B: BlockBackend + ?Sized> BlockBackend for &'a mut B {
+    fn logical_sector_size(&self) -> u64 { (**self).logical_sector_size() }
+
+    fn sync(&mut self) -> io::Result<()> { (**self).sync() }
+
+    fn reload(&mut self, partitions: &[TablePartition], sector_size: u64) -> Result<(), SyncError> {
+        (**self).reload(partitions, sector_size)
+    }
+}
+
+fn checked_offset(base: u64, offset: i64) -> Option<u64> {
+    if offset < 0 {
+        base.checked_sub(offset.wrapping_neg() as u64)
+    } else {
+        base.checked_add(offset as u64)
+    }
+}
@@ -0,0 +1,341 @@
+use disk_types::{PartitionAttributes, PartitionKind, PartitionTable, PartitionType};
+use std::{
+    fs::OpenOptions,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use super::{
+    convert_str_to_array, Gpt, Mbr, NewPartition, PartitionError, Partitioner, TablePartition,
+};
+
+/// Size of the partition entry array that is written by GPT tables.
+const GPT_ENTRIES_SIZE: u64 = 128 * 128;
+/// Size of the boot code at the start of the master boot record.
+const BOOT_CODE_SIZE: usize = 440;
+
+/// Reasons why partitions cannot be converted into another partition table format.
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+pub enum PlanError {
+    #[error(display = "disk has {} sectors, which exceeds what a MBR table can address", _0)]
+    DiskTooLarge(u64),
+    #[error(display = "partition {} has a partition type which is not known", _0)]
+    KindUnknown(u32),
+    #[error(display = "partition {} is a {:?} partition, which MBR tables do not support", _0, _1)]
+    KindUnsupported(u32, PartitionKind),
+    #[error(display = "partition {} does not have a free sector for an extended boot record", _0)]
+    NoRoomForEbr(u32),
+    #[error(display = "partitions occupy the sectors required by the GPT headers")]
+    NoRoomForGpt,
+}
+
+#[derive(Debug, Error)]
+pub enum ConvertError {
+    #[error(display = "device could not be opened")]
+    DeviceOpen(#[error(cause)] io::Error),
+    #[error(display = "failed to wipe the GPT headers from the disk")]
+    GptWipe(#[error(cause)] io::Error),
+    #[error(display = "partitions cannot be converted")]
+    Plan(#[error(cause)] PlanError),
+    #[error(display = "failed to add the partition at sector {} to the new table", _0)]
+    PartitionAdd(u64, #[error(cause)] PartitionError),
+    #[error(display = "failed to create the new partition table")]
+    TableCreate(#[error(cause)] PartitionError),
+    #[error(display = "failed to read the existing partition table")]
+    TableRead(#[error(cause)] PartitionError),
+    #[error(display = "failed to write the new partition table")]
+    TableWrite(#[error(cause)] PartitionError),
+}
+
+/// Plans the conversion of partitions into a partition table of another format, without
+/// moving any of their sectors.
+///
+/// Extended partitions are dropped when converting to GPT, and one is created when converting
+/// more than four partitions to MBR. Partition names, and attributes other than the legacy
+/// bootable flag, are not supported by MBR tables, and will therefore be dropped.
+///
+/// Partitions keep their numbers, except on MBR tables, where logical partitions are numbered
+/// by their order, and primary partitions numbered above four are given the numbers not in use.
+pub fn plan_conversion<'a>(
+    partitions: &'a [TablePartition],
+    table: PartitionTable,
+    sectors: u64,
+    sector_size: u64,
+) -> Result<Vec<NewPartition<'a>>, PlanError> {
+    let mut partitions = partitions
+        .iter()
+        .filter(|partition| partition.variant != PartitionType::Extended)
+        .collect::<Vec<_>>();
+
+    partitions.sort_by_key(|partition| partition.start);
+
+    let new_partition = |partition: &'a TablePartition| {
+        partition.kind.ok_or(PlanError::KindUnknown(partition.number)).map(|kind| NewPartition {
+            start: partition.start,
+            end: partition.end,
+            name: partition.name.as_ref().map(AsRef::as_ref),
+            variant: PartitionType::Primary,
+            kind,
+            attributes: partition.attributes,
+            uuid: None,
+            number: Some(partition.number),
+        })
+    };
+
+    match table {
+        PartitionTable::Guid => {
+            let (first_usable, last_usable) = usable_sectors(table, sectors, sector_size);
+
+            let fits = partitions
+                .iter()
+                .all(|partition| partition.start >= first_usable && partition.end <= last_usable);
+
+            if !fits {
+                return Err(PlanError::NoRoomForGpt);
+            }
+
+            partitions.into_iter().map(new_partition).collect()
+        }
+        PartitionTable::Mbr => {
+            if sectors > u64::from(u32::max_value()) {
+                return Err(PlanError::DiskTooLarge(sectors));
+            }
+
+            let mut planned = Vec::with_capacity(partitions.len() + 1);
+            for &partition in &partitions {
+                let mut new = new_partition(partition)?;
+                if new.kind.mbr_id().is_none() {
+                    return Err(PlanError::KindUnsupported(partition.number, new.kind));
+                }
+
+                new.name = None;
+                new.attributes &= PartitionAttributes::LEGACY_BOOTABLE;
+                planned.push(new);
+            }
+
+            if planned.len() > 4 {
+                let numbers =
+                    partitions.iter().map(|partition| partition.number).collect::<Vec<_>>();
+                let (first, last) = logical_range(&planned, &numbers)?;
+
+                let extended = NewPartition {
+                    start: planned[first].start - 1,
+                    end: planned[last].end,
+                    variant: PartitionType::Extended,
+                    ..Default::default()
+                };
+
+                for partition in &mut planned[first..=last] {
+                    partition.variant = PartitionType::Logical;
+                }
+
+                // Extended partitions must exist before their logical partitions are added.
+                planned.insert(first, extended);
+            }
+
+            let kept = planned
+                .iter()
+                .filter(|new| new.variant == PartitionType::Primary)
+                .filter_map(|new| new.number)
+                .filter(|&number| number <= 4)
+                .collect::<Vec<_>>();
+
+            let mut free = (1..=4).filter(|number| !kept.contains(number));
+            for new in &mut planned {
+                new.number = match (new.variant, new.number) {
+                    (PartitionType::Logical, _) => None,
+                    (PartitionType::Primary, Some(number)) if number <= 4 => Some(number),
+                    _ => free.next(),
+                };
+            }
+
+            Ok(planned)
+        }
+    }
+}
+
+/// Rewrites the partition table of the device in another format, without moving any of the
+/// partitions on the device.
+///
+/// The boot code of the master boot record is kept, and so is the disk signature, which GPT
+/// keeps in its protective MBR. As MBR PARTUUIDs cannot be kept as they are, MBR partitions are
+/// given the GUIDs of `mbr_guid`, from which their PARTUUIDs are recovered if the table is
+/// converted back to MBR. The GUIDs of other GPT partitions cannot be represented on MBR.
+///
+/// The new table is built in memory, and nothing is written to the device until every
+/// partition has been added to it. The old table is only erased once the new one is written.
+///
+/// Returns the partitions of the new partition table.
+pub fn convert(
+    path: &Path,
+    from: PartitionTable,
+    to: PartitionTable,
+) -> Result<Vec<TablePartition>, ConvertError> {
+    let (sectors, sector_size, boot_record) = {
+        let mut device =
+            OpenOptions::new().read(true).open(path).map_err(ConvertError::DeviceOpen)?;
+
+        let sector_size = super::logical_sector_size(&device);
+        let size = device.seek(SeekFrom::End(0)).map_err(ConvertError::DeviceOpen)?;
+
+        let mut boot_record = [0; BOOT_CODE_SIZE + 4];
+        device.seek(SeekFrom::Start(0)).map_err(ConvertError::DeviceOpen)?;
+        device.read_exact(&mut boot_record).map_err(ConvertError::DeviceOpen)?;
+
+        (size / sector_size, sector_size, boot_record)
+    };
+
+    let (boot_code, signature) = boot_record.split_at(BOOT_CODE_SIZE);
+    let signature = u32::from_le_bytes([signature[0], signature[1], signature[2], signature[3]]);
+
+    let partitions = match from {
+        PartitionTable::Guid => Gpt::open(path).map(|table| table.partitions()),
+        PartitionTable::Mbr => Mbr::open(path).map(|table| table.partitions()),
+    };
+
+    let partitions = partitions.map_err(ConvertError::TableRead)?;
+    let mut planned =
+        plan_conversion(&partitions, to, sectors, sector_size).map_err(ConvertError::Plan)?;
+
+    match to {
+        PartitionTable::Guid => {
+            // The table is built in memory, and the MBR is only replaced by the protective MBR
+            // once the GPT headers are written. Its boot code and disk signature are kept.
+            let mut table = Gpt::create(path, sector_size).map_err(ConvertError::TableCreate)?;
+
+            if from == PartitionTable::Mbr {
+                table.set_disk_guid(mbr_guid(signature, 0));
+                for new in &mut planned {
+                    new.uuid = new.number.map(|number| mbr_guid(signature, number));
+                }
+            }
+
+            add_partitions(&mut table, &planned)?;
+            table.write().map_err(ConvertError::TableWrite)?;
+            Ok(table.partitions())
+        }
+        PartitionTable::Mbr => {
+            let mut table = Mbr::create(path, sector_size).map_err(ConvertError::TableCreate)?;
+            table.set_boot_code(boot_code);
+
+            // A new signature is only generated if the disk never had one.
+            match carried_signature(&partitions) {
+                Some(signature) => table.set_signature(signature),
+                None if signature != 0 => table.set_signature(signature),
+                None => (),
+            }
+
+            add_partitions(&mut table, &planned)?;
+            table.write().map_err(ConvertError::TableWrite)?;
+
+            // Otherwise, the stale GPT headers would continue to be detected. They are only
+            // wiped once the MBR is written, so that the disk always has one of the tables.
+            if from == PartitionTable::Guid {
+                wipe_gpt_headers(path, sectors, sector_size).map_err(ConvertError::GptWipe)?;
+            }
+
+            Ok(table.partitions())
+        }
+    }
+}
+
+/// The first and last sectors, inclusive, which a new table of the given kind leaves for its
+/// partitions on a device with the given number of sectors.
+///
+/// GPT tables have a header and 16 KiB of partition entries at either end of the device. MBR
+/// tables only occupy the first sector.
+pub fn usable_sectors(table: PartitionTable, sectors: u64, sector_size: u64) -> (u64, u64) {
+    match table {
+        PartitionTable::Guid => {
+            let reserved = 1 + (GPT_ENTRIES_SIZE + sector_size - 1) / sector_size;
+            (1 + reserved, sectors.saturating_sub(1 + reserved))
+        }
+        PartitionTable::Mbr => (1, sectors.saturating_sub(1)),
+    }
+}
+
+/// The GUID which a MBR disk signature and partition number are carried over to GPT with.
+///
+/// The GUID reads as the signature followed by the number, such as
+/// `1234ABCD-0002-0000-0000-000000000000` for the MBR PARTUUID `1234abcd-02`. The disk GUID is
+/// given the number 0.
+pub fn mbr_guid(signature: u32, number: u32) -> [u8; 16] {
+    let guid = format!("{:08x}-{:04x}-0000-0000-000000000000", signature, number);
+    convert_str_to_array(&guid).expect("formatted GUID is not valid")
+}
+
+/// Recovers the disk signature and partition number from a GUID created by `mbr_guid`.
+pub fn parse_mbr_guid(guid: &str) -> Option<(u32, u32)> {
+    match guid.split('-').collect::<Vec<_>>().as_slice() {
+        [signature, number, "0000", "0000", "000000000000"]
+            if signature.len() == 8 && number.len() == 4 =>
+        {
+            let signature = u32::from_str_radix(signature, 16).ok()?;
+            let number = u32::from_str_radix(number, 16).ok()?;
+            Some((signature, number))
+        }
+        _ => None,
+    }
+}
+
+/// The disk signature that the GUIDs of every partition were created from by `mbr_guid`.
+fn carried_signature(partitions: &[TablePartition]) -> Option<u32> {
+    let mut signatures = partitions
+        .iter()
+        .filter(|partition| partition.variant != PartitionType::Extended)
+        .map(|partition| parse_mbr_guid(&partition.uuid).map(|(signature, _)| signature));
+
+    let first = signatures.next()??;
+    if signatures.all(|signature| signature == Some(first)) {
+        Some(first)
+    } else {
+        None
+    }
+}
+
+fn add_partitions(
+    table: &mut dyn Partitioner,
+    planned: &[NewPartition],
+) -> Result<(), ConvertError> {
+    for &partition in planned {
+        table.add(partition).map_err(|why| ConvertError::PartitionAdd(partition.start, why))?;
+    }
+
+    Ok(())
+}
+
+/// Finds partitions at the end of the disk which can be converted into logical partitions.
+///
+/// Every logical partition requires a free sector before it to store its extended boot record,
+/// and there may be no more than three primary partitions alongside the extended partition.
+fn logical_range(planned: &[NewPartition], numbers: &[u32]) -> Result<(usize, usize), PlanError> {
+    let logicals = planned.len() - 3;
+    let has_room = |id: usize| {
+        let previous_end = if id == 0 { 0 } else { planned[id - 1].end };
+        planned[id].start > previous_end + 1
+    };
+
+    let mut blocked = None;
+    for first in (0..=planned.len() - logicals).rev() {
+        let last = first + logicals - 1;
+        match (first..=last).find(|&id| !has_room(id)) {
+            Some(id) => blocked = blocked.or(Some(id)),
+            None => return Ok((first, last)),
+        }
+    }
+
+    Err(PlanError::NoRoomForEbr(blocked.map_or(0, |id| numbers[id])))
+}
+
+/// Zeroes the primary and backup GPT headers of a device.
+fn wipe_gpt_headers(path: &Path, sectors: u64, sector_size: u64) -> io::Result<()> {
+    let mut device = OpenOptions::new().write(true).open(path)?;
+    let zeroes = vec![0; sector_size as usize];
+
+    for &sector in &[1, sectors - 1] {
+        device.seek(SeekFrom::Start(sector * sector_size))?;
+        device.write_all(&zeroes)?;
+    }
+
+    device.sync_all()
+}
@@ -0,0 +1,456 @@
+use disk_types::PartitionTable;
+use std::{fmt, fs::File, path::Path, str::FromStr};
+
+use super::{Gpt, Mbr, NullImage, PartitionError, PartitionResult, Partitioner, TablePartition};
+
+/// Names which sfdisk assigns to the attribute bits that are defined by the UEFI specification.
+const ATTRIBUTE_NAMES: &[(u64, &str)] =
+    &[(0, "RequiredPartition"), (1, "NoBlockIOProtocol"), (2, "LegacyBIOSBootable")];
+
+/// Attribute bits from this bit onwards are specific to the partition type.
+const TYPE_ATTRIBUTES: u64 = 48;
+
+#[derive(Debug, Error)]
+pub enum DumpError {
+    #[error(display = "line {}: invalid value for the `{}` field", _0, _1)]
+    FieldInvalid(usize, Box<str>),
+    #[error(display = "line {}: the `{}` field is required", _0, _1)]
+    FieldMissing(usize, &'static str),
+    #[error(display = "the dump does not specify a partition table label")]
+    LabelMissing,
+    #[error(display = "line {}: `{}` is not a supported partition table label", _0, _1)]
+    LabelUnsupported(usize, Box<str>),
+    #[error(display = "line {}: the partition device does not end with a partition number", _0)]
+    NodeInvalid(usize),
+    #[error(display = "line {}: only sector units are supported", _0)]
+    UnitUnsupported(usize),
+}
+
+/// Reasons why a dump cannot be restored to a device, which are found without writing to it.
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+pub enum RestoreError {
+    #[error(display = "an extended partition already exists in the dump")]
+    ExtendedExists,
+    #[error(display = "logical partitions require an extended partition")]
+    ExtendedNotFound,
+    #[error(display = "the dump has more partitions than the partition table can hold")]
+    LimitExceeded,
+    #[error(display = "partition number {} is invalid or already in use", _0)]
+    NumberInvalid(u32),
+    #[error(display = "a partition lies outside of the usable sectors of the device")]
+    OutOfBounds,
+    #[error(display = "a logical partition does not fit within the extended partition")]
+    OutsideExtended,
+    #[error(display = "partitions of the dump overlap each other")]
+    Overlap,
+    #[error(display = "MBR tables are limited to four primary partitions")]
+    PrimaryLimitExceeded,
+    #[error(display = "expected a sector size of {}, but the device has {}", _0, _1)]
+    SectorSizeMismatch(u64, u64),
+    #[error(display = "the partition table cannot be laid out on the device")]
+    TableInvalid,
+    #[error(display = "a partition type of the dump is invalid")]
+    TypeInvalid,
+    #[error(display = "a GUID or disk signature of the dump is invalid")]
+    UuidInvalid,
+}
+
+impl From<PartitionError> for RestoreError {
+    fn from(error: PartitionError) -> Self {
+        match error {
+            PartitionError::ExtendedExists => RestoreError::ExtendedExists,
+            PartitionError::ExtendedNotFound => RestoreError::ExtendedNotFound,
+            PartitionError::LimitExceeded => RestoreError::LimitExceeded,
+            PartitionError::NumberInvalid(number) => RestoreError::NumberInvalid(number),
+            PartitionError::OutOfBounds => RestoreError::OutOfBounds,
+            PartitionError::OutsideExtended => RestoreError::OutsideExtended,
+            PartitionError::Overlap => RestoreError::Overlap,
+            PartitionError::PrimaryLimitExceeded => RestoreError::PrimaryLimitExceeded,
+            PartitionError::SectorSizeMismatch(expected, actual) => {
+                RestoreError::SectorSizeMismatch(expected, actual)
+            }
+            PartitionError::TypeInvalid(_) => RestoreError::TypeInvalid,
+            PartitionError::UuidInvalid(_) => RestoreError::UuidInvalid,
+            _ => RestoreError::TableInvalid,
+        }
+    }
+}
+
+/// The layout of a partition table, which is written and read in the format of `sfdisk --dump`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableDump {
+    pub table:       PartitionTable,
+    /// The disk GUID of GPT tables, or the disk signature of MBR tables.
+    pub label_id:    Option<Box<str>>,
+    /// The device that the dump was taken from.
+    pub device:      Option<Box<str>>,
+    /// The first usable sector of GPT tables.
+    pub first_lba:   Option<u64>,
+    /// The last usable sector of GPT tables.
+    pub last_lba:    Option<u64>,
+    pub sector_size: Option<u64>,
+    pub partitions:  Vec<DumpPartition>,
+}
+
+/// A partition within a `TableDump`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DumpPartition {
+    pub number:     u32,
+    pub start:      u64,
+    /// The length of the partition, in sectors.
+    pub size:       u64,
+    /// The type GUID of GPT partitions, or the hexadecimal system ID of MBR partitions.
+    pub type_id:    Box<str>,
+    /// The PARTUUID of GPT partitions.
+    pub uuid:       Option<Box<str>>,
+    pub name:       Option<Box<str>>,
+    /// Attribute bits of GPT partitions. The boot indicator of MBR partitions is bit 2.
+    pub attributes: u64,
+}
+
+impl DumpPartition {
+    /// The last sector of the partition, inclusive.
+    ///
+    /// Parsing a dump rejects partitions whose last sector cannot be addressed.
+    pub fn end(&self) -> u64 { self.start + self.size - 1 }
+
+    fn is_bootable(&self) -> bool { self.attributes & (1 << 2) != 0 }
+}
+
+impl fmt::Display for TableDump {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let label = match self.table {
+            PartitionTable::Guid => "gpt",
+            PartitionTable::Mbr => "dos",
+        };
+
+        writeln!(fmt, "label: {}", label)?;
+
+        if let Some(ref label_id) = self.label_id {
+            writeln!(fmt, "label-id: {}", label_id)?;
+        }
+
+        if let Some(ref device) = self.device {
+            writeln!(fmt, "device: {}", device)?;
+        }
+
+        writeln!(fmt, "unit: sectors")?;
+
+        if let Some(first_lba) = self.first_lba {
+            writeln!(fmt, "first-lba: {}", first_lba)?;
+        }
+
+        if let Some(last_lba) = self.last_lba {
+            writeln!(fmt, "last-lba: {}", last_lba)?;
+        }
+
+        if let Some(sector_size) = self.sector_size {
+            writeln!(fmt, "sector-size: {}", sector_size)?;
+        }
+
+        writeln!(fmt)?;
+
+        let device = self.device.as_ref().map_or("", AsRef::as_ref);
+        let separator =
+            if device.chars().last().map_or(false, char::is_numeric) { "p" } else { "" };
+
+        for partition in &self.partitions {
+            write!(
+                fmt,
+                "{}{}{} : start={:>12}, size={:>12}, type={}",
+                device,
+                separator,
+                partition.number,
+                partition.start,
+                partition.size,
+                partition.type_id
+            )?;
+
+            match self.table {
+                PartitionTable::Guid => {
+                    if let Some(ref uuid) = partition.uuid {
+                        write!(fmt, ", uuid={}", uuid)?;
+                    }
+
+                    if let Some(ref name) = partition.name {
+                        write!(fmt, ", name=\"{}\"", escape(name))?;
+                    }
+
+                    if partition.attributes != 0 {
+                        write!(fmt, ", attrs=\"{}\"", attributes_to_string(partition.attributes))?;
+                    }
+                }
+                PartitionTable::Mbr => {
+                    if partition.is_bootable() {
+                        write!(fmt, ", bootable")?;
+                    }
+                }
+            }
+
+            writeln!(fmt)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for TableDump {
+    type Err = DumpError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut table = None;
+        let mut dump = TableDump {
+            table:       PartitionTable::Guid,
+            label_id:    None,
+            device:      None,
+            first_lba:   None,
+            last_lba:    None,
+            sector_size: None,
+            partitions:  Vec::new(),
+        };
+
+        for (line_no, line) in input.lines().enumerate() {
+            let line_no = line_no + 1;
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            // sfdisk separates the device of a partition line with " : ", as device paths such
+            // as those in `/dev/disk/by-path` may contain colons of their own.
+            let separator = line
+                .find(" : ")
+                .map(|pos| (pos, pos + 3))
+                .or_else(|| line.find(':').map(|pos| (pos, pos + 1)));
+
+            let (key, value) = match separator {
+                Some((end, start)) => (line[..end].trim(), line[start..].trim()),
+                None => return Err(DumpError::FieldInvalid(line_no, line.into())),
+            };
+
+            // Partition lines consist of the partition's device, followed by its fields.
+            if value.contains('=') {
+                dump.partitions.push(parse_partition(line_no, key, value)?);
+                continue;
+            }
+
+            let number =
+                || value.parse::<u64>().map_err(|_| DumpError::FieldInvalid(line_no, key.into()));
+
+            match key {
+                "label" => {
+                    table = Some(match value {
+                        "gpt" => PartitionTable::Guid,
+                        "dos" => PartitionTable::Mbr,
+                        _ => return Err(DumpError::LabelUnsupported(line_no, value.into())),
+                    });
+                }
+                "label-id" => dump.label_id = Some(value.into()),
+                "device" => dump.device = Some(value.into()),
+                "unit" if value != "sectors" => return Err(DumpError::UnitUnsupported(line_no)),
+                "first-lba" => dump.first_lba = Some(number()?),
+                "last-lba" => dump.last_lba = Some(number()?),
+                "sector-size" => dump.sector_size = Some(number()?),
+                _ => (),
+            }
+        }
+
+        dump.table = table.ok_or(DumpError::LabelMissing)?;
+        Ok(dump)
+    }
+}
+
+/// Reads the partition table of a device into a dump.
+pub fn dump(path: &Path, table: PartitionTable) -> PartitionResult<TableDump> {
+    let mut dump = match table {
+        PartitionTable::Guid => Gpt::open(path)?.export(),
+        PartitionTable::Mbr => Mbr::open(path)?.export(),
+    };
+
+    dump.device = Some(path.to_string_lossy().into());
+    Ok(dump)
+}
+
+/// Checks that a dump can be restored to a device with the given number of sectors, without
+/// touching the device.
+///
+/// The dump is imported into a `NullImage` of the same size, which fails in the same way that
+/// `restore` would, before anything is written.
+pub fn check_restore(dump: &TableDump, sectors: u64, sector_size: u64) -> Result<(), RestoreError> {
+    check_sector_size(dump, sector_size)?;
+
+    let image = NullImage::new(sectors, sector_size);
+    match dump.table {
+        PartitionTable::Guid => Gpt::import_from(image, sector_size, dump).map(|_| ()),
+        PartitionTable::Mbr => Mbr::import_from(image, sector_size, dump).map(|_| ()),
+    }
+    .map_err(RestoreError::from)
+}
+
+/// Replaces the partition table of a device with the layout of a dump.
+///
+/// Returns the partitions of the restored table.
+pub fn restore(path: &Path, dump: &TableDump) -> PartitionResult<Vec<TablePartition>> {
+    let sector_size = File::open(path)
+        .map(|device| super::logical_sector_size(&device))
+        .map_err(PartitionError::DeviceOpen)?;
+
+    check_sector_size(dump, sector_size)?;
+
+    let mut table: Box<dyn Partitioner> = match dump.table {
+        PartitionTable::Guid => Box::new(Gpt::import(path, sector_size, dump)?),
+        PartitionTable::Mbr => Box::new(Mbr::import(path, sector_size, dump)?),
+    };
+
+    table.write()?;
+    Ok(table.partitions())
+}
+
+fn check_sector_size(dump: &TableDump, sector_size: u64) -> PartitionResult<()> {
+    match dump.sector_size {
+        Some(expected) if expected != sector_size => {
+            Err(PartitionError::SectorSizeMismatch(expected, sector_size))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn parse_partition(line_no: usize, node: &str, fields: &str) -> Result<DumpPartition, DumpError> {
+    let number = node
+        .rfind(|c: char| !c.is_ascii_digit())
+        .map_or(node, |pos| &node[pos + 1..])
+        .parse::<u32>()
+        .map_err(|_| DumpError::NodeInvalid(line_no))?;
+
+    let mut start = None;
+    let mut size = None;
+    let mut partition = DumpPartition {
+        number,
+        start: 0,
+        size: 0,
+        type_id: Box::from(""),
+        uuid: None,
+        name: None,
+        attributes: 0,
+    };
+
+    for field in split_fields(fields) {
+        let (key, value) = match field.find('=') {
+            Some(pos) => (field[..pos].trim(), unquote(field[pos + 1..].trim())),
+            None => (field.trim(), String::new()),
+        };
+
+        let invalid = || DumpError::FieldInvalid(line_no, key.into());
+
+        match key {
+            "start" => start = Some(value.parse::<u64>().map_err(|_| invalid())?),
+            "size" => size = Some(value.parse::<u64>().map_err(|_| invalid())?),
+            "type" | "Id" => partition.type_id = value.into(),
+            "uuid" => partition.uuid = Some(value.into()),
+            "name" => partition.name = Some(value.into()),
+            "attrs" => partition.attributes = attributes_from_str(&value).ok_or_else(invalid)?,
+            "bootable" => partition.attributes |= 1 << 2,
+            _ => (),
+        }
+    }
+
+    partition.start = start.ok_or(DumpError::FieldMissing(line_no, "start"))?;
+    partition.size =
+        size.filter(|&size| size != 0).ok_or(DumpError::FieldMissing(line_no, "size"))?;
+
+    // The last sector of the partition must be addressable.
+    if partition.start.checked_add(partition.size).is_none() {
+        return Err(DumpError::FieldInvalid(line_no, "size".into()));
+    }
+
+    if partition.type_id.is_empty() {
+        return Err(DumpError::FieldMissing(line_no, "type"));
+    }
+
+    Ok(partition)
+}
+
+/// Splits the fields of a partition line on each comma that is not within a quoted value.
+fn split_fields(fields: &str) -> Vec<&str> {
+    let mut split = Vec::new();
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut start = 0;
+
+    for (pos, character) in fields.char_indices() {
+        match character {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                split.push(&fields[start..pos]);
+                start = pos + 1;
+            }
+            _ => (),
+        }
+    }
+
+    split.push(&fields[start..]);
+    split.into_iter().filter(|field| !field.trim().is_empty()).collect()
+}
+
+fn escape(value: &str) -> String { value.replace('\\', "\\\\").replace('"', "\\\"") }
+
+fn unquote(value: &str) -> String {
+    if value.len() < 2 || !value.starts_with('"') || !value.ends_with('"') {
+        return value.into();
+    }
+
+    let mut unquoted = String::with_capacity(value.len());
+    let mut escaped = false;
+    for character in value[1..value.len() - 1].chars() {
+        if !escaped && character == '\\' {
+            escaped = true;
+        } else {
+            escaped = false;
+            unquoted.push(character);
+        }
+    }
+
+    unquoted
+}
+
+fn attributes_to_string(bits: u64) -> String {
+    let mut words = ATTRIBUTE_NAMES
+        .iter()
+        .filter(|&&(bit, _)| bits & (1 << bit) != 0)
+        .map(|&(_, name)| name.to_owned())
+        .collect::<Vec<_>>();
+
+    let type_bits = (TYPE_ATTRIBUTES..64)
+        .filter(|&bit| bits & (1 << bit) != 0)
+        .map(|bit| bit.to_string())
+        .collect::<Vec<_>>();
+
+    if !type_bits.is_empty() {
+        words.push(["GUID:", &type_bits.join(",")].concat());
+    }
+
+    words.join(" ")
+}
+
+fn attributes_from_str(input: &str) -> Option<u64> {
+    let mut bits = 0;
+
+    for word in input.split_whitespace() {
+        if word.starts_with("GUID:") {
+            for bit in word[5..].split(',') {
+                match bit.parse::<u64>() {
+                    Ok(bit) if bit >= TYPE_ATTRIBUTES && bit < 64 => bits |= 1 << bit,
+                    _ => return None,
+                }
+            }
+        } else {
+            let bit = ATTRIBUTE_NAMES.iter().find(|&&(_, name)| name == word)?.0;
+            bits |= 1 << bit;
+        }
+    }
+
+    Some(bits)
+}
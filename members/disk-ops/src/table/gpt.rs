@@ -1,6 +1,5 @@
-use disk_types::PartitionTable;
+use disk_types::{PartitionAttributes, PartitionKind, PartitionTable, PartitionType};
 use gptman::{GPTPartitionEntry, PartitionName, GPT};
-use rand::Rng;
 use std::{
     fs::{self, File, OpenOptions},
     io::{self, Seek, SeekFrom},
@@ -8,7 +7,13 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use super::{PartitionError, PartitionResult, Partitioner, TableError};
+use super::{
+    uuids::generate_random_uuid, BlockBackend, DumpPartition, NewPartition, PartitionError,
+    PartitionResult, Partitioner, TableDump, TableError, TablePartition,
+};
+
+/// The number of partition entries in the tables that `Gpt::create_from` writes.
+pub const GPT_ENTRIES: u32 = 128;
 
 pub fn convert_str_to_array(uuid: &str) -> Result<[u8; 16], ParseIntError> {
     let mut arr = [0; 16];
@@ -39,37 +44,259 @@ pub fn convert_str_to_array(uuid: &str) -> Result<[u8; 16], ParseIntError> {
     Ok(arr)
 }
 
-pub struct Gpt {
-    device: File,
-    table:  GPT,
+/// The inverse of `convert_str_to_array`.
+pub fn convert_array_to_str(uuid: &[u8; 16]) -> String {
+    let hex = |bytes: &[u8]| bytes.iter().map(|byte| format!("{:02X}", byte)).collect::<String>();
+
+    let mut reordered = uuid.to_vec();
+    reordered[..4].reverse();
+    reordered[4..6].reverse();
+    reordered[6..8].reverse();
+
+    [
+        hex(&reordered[..4]),
+        hex(&reordered[4..6]),
+        hex(&reordered[6..8]),
+        hex(&reordered[8..10]),
+        hex(&reordered[10..]),
+    ]
+    .join("-")
+}
+
+/// Parses a GUID string, which must consist of 32 hexadecimal digits.
+fn parse_guid(guid: &str) -> Option<[u8; 16]> {
+    let digits = guid.chars().filter(|&x| x != '-');
+    if digits.clone().count() != 32 || !digits.clone().all(|x| x.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    convert_str_to_array(guid).ok()
+}
+
+/// A GPT partition table, which is kept on a block device by default.
+pub struct Gpt<D: BlockBackend = File> {
+    device:     D,
+    table:      GPT,
+    /// Whether the protective MBR is written on `write()`, which is only for new tables.
+    protective: bool,
 }
 
 impl Gpt {
     pub fn create(device: &Path, sector_size: u64) -> PartitionResult<Self> {
-        let mut device =
+        let device =
             OpenOptions::new().write(true).open(device).map_err(PartitionError::DeviceOpen)?;
 
-        let table = GPT::new_from(&mut device, sector_size, generate_random_uuid())
-            .map_err(TableError::from)
-            .map_err(PartitionError::TableRead)?;
-
-        write_protective_mbr_into(&mut device, sector_size).unwrap();
-
-        Ok(Gpt { device, table })
+        Self::create_from(device, sector_size)
     }
 
     pub fn open(device: &Path) -> PartitionResult<Self> {
-        let mut device = OpenOptions::new()
+        let device = OpenOptions::new()
             .read(true)
             .write(true)
             .open(device)
             .map_err(PartitionError::DeviceOpen)?;
 
+        Self::open_from(device)
+    }
+
+    /// Creates a new partition table from the layout of a dump, which is written on `write()`.
+    ///
+    /// Partitions keep their numbers, and GUIDs which are missing will be generated.
+    pub fn import(device: &Path, sector_size: u64, dump: &TableDump) -> PartitionResult<Self> {
+        let device =
+            OpenOptions::new().write(true).open(device).map_err(PartitionError::DeviceOpen)?;
+
+        Self::import_from(device, sector_size, dump)
+    }
+
+    /// The number of sectors that a device has grown by, beyond the backup header of its table.
+    ///
+    /// The size of the device is read from `/sys/class/block`.
+    pub fn growth(device: &Path) -> PartitionResult<u64> {
+        let gpt = Self::open(device)?;
+        let sectors =
+            kernel_sectors(device, gpt.table.sector_size).map_err(PartitionError::DeviceRead)?;
+
+        Ok(sectors.saturating_sub(gpt.table.header.backup_lba + 1))
+    }
+}
+
+impl<D: BlockBackend> Gpt<D> {
+    /// Creates a new, empty table spanning the whole backend, which is written on `write()`.
+    ///
+    /// Nothing is written to the backend before then. The protective MBR is written after the
+    /// headers, so that the first sector is only replaced once the rest of the table is intact.
+    pub fn create_from(mut device: D, sector_size: u64) -> PartitionResult<Self> {
+        let table = GPT::new_from(&mut device, sector_size, generate_random_uuid())
+            .map_err(TableError::from)
+            .map_err(PartitionError::TableRead)?;
+
+        Ok(Gpt { device, table, protective: true })
+    }
+
+    /// Reads the table from a backend, trying each of the sector sizes that GPT supports.
+    pub fn open_from(mut device: D) -> PartitionResult<Self> {
         let table = GPT::find_from(&mut device)
             .map_err(TableError::from)
             .map_err(PartitionError::TableRead)?;
 
-        Ok(Gpt { device, table })
+        Ok(Gpt { device, table, protective: false })
+    }
+
+    /// Gives back the backend that the table is kept on.
+    pub fn into_inner(self) -> D { self.device }
+
+    /// Assigns the GUID of the disk, which is written on `write()`.
+    pub fn set_disk_guid(&mut self, guid: [u8; 16]) { self.table.header.disk_guid = guid; }
+
+    /// Describes the partition table in the layout of a dump.
+    pub fn export(&self) -> TableDump {
+        let header = &self.table.header;
+        let mut partitions = self
+            .table
+            .iter()
+            .filter(|(_, entry)| entry.is_used())
+            .map(|(number, entry)| DumpPartition {
+                number,
+                start: entry.starting_lba,
+                size: entry.ending_lba - entry.starting_lba + 1,
+                type_id: convert_array_to_str(&entry.partition_type_guid).into(),
+                uuid: Some(convert_array_to_str(&entry.unique_parition_guid).into()),
+                name: Some(entry.partition_name.as_str())
+                    .filter(|name| !name.is_empty())
+                    .map(Box::from),
+                attributes: entry.attribute_bits,
+            })
+            .collect::<Vec<_>>();
+
+        partitions.sort_by_key(|partition| partition.number);
+
+        TableDump {
+            table:       PartitionTable::Guid,
+            label_id:    Some(convert_array_to_str(&header.disk_guid).into()),
+            device:      None,
+            first_lba:   Some(header.first_usable_lba),
+            last_lba:    Some(header.last_usable_lba),
+            sector_size: Some(self.table.sector_size),
+            partitions,
+        }
+    }
+
+    /// Creates a new partition table on a backend from the layout of a dump.
+    pub fn import_from(device: D, sector_size: u64, dump: &TableDump) -> PartitionResult<Self> {
+        let mut gpt = Self::create_from(device, sector_size)?;
+
+        if let Some(ref label_id) = dump.label_id {
+            gpt.table.header.disk_guid = parse_guid(label_id)
+                .ok_or_else(|| PartitionError::UuidInvalid(label_id.clone()))?;
+        }
+
+        // The usable area may only be shrunk, as the headers occupy the sectors around it.
+        let header = &mut gpt.table.header;
+        if let Some(first_lba) = dump.first_lba.filter(|&lba| lba > header.first_usable_lba) {
+            header.first_usable_lba = first_lba;
+        }
+
+        if let Some(last_lba) = dump.last_lba.filter(|&lba| lba < header.last_usable_lba) {
+            header.last_usable_lba = last_lba;
+        }
+
+        let (first_usable, last_usable, entries) =
+            (header.first_usable_lba, header.last_usable_lba, header.number_of_partition_entries);
+
+        for partition in &dump.partitions {
+            let number = partition.number;
+            if number == 0 || number > entries || gpt.table[number].is_used() {
+                return Err(PartitionError::NumberInvalid(number));
+            }
+
+            if partition.start < first_usable || partition.end() > last_usable {
+                return Err(PartitionError::OutOfBounds);
+            }
+
+            let overlaps = gpt.table.iter().any(|(_, entry)| {
+                entry.is_used()
+                    && partition.start <= entry.ending_lba
+                    && partition.end() >= entry.starting_lba
+            });
+
+            if overlaps {
+                return Err(PartitionError::Overlap);
+            }
+
+            gpt.table[number] = GPTPartitionEntry {
+                starting_lba:         partition.start,
+                ending_lba:           partition.end(),
+                attribute_bits:       partition.attributes,
+                partition_name:       partition.name.as_ref().map_or("", AsRef::as_ref).into(),
+                partition_type_guid:  parse_guid(&partition.type_id)
+                    .ok_or_else(|| PartitionError::TypeInvalid(partition.type_id.clone()))?,
+                unique_parition_guid: match partition.uuid {
+                    Some(ref uuid) => {
+                        parse_guid(uuid).ok_or_else(|| PartitionError::UuidInvalid(uuid.clone()))?
+                    }
+                    None => generate_random_uuid(),
+                },
+            };
+        }
+
+        Ok(gpt)
+    }
+
+    /// Moves the backup header and partition entry array to the end of a device which has grown
+    /// to the given number of sectors, and extends the usable sectors to match.
+    ///
+    /// The relocated headers are written on `write()`.
+    pub fn relocate_backup(&mut self, sectors: u64) -> PartitionResult<()> {
+        let sector_size = self.table.sector_size;
+        let header = &mut self.table.header;
+        if sectors <= header.backup_lba + 1 {
+            return Err(PartitionError::NotGrown);
+        }
+
+        let entries_size = u64::from(header.number_of_partition_entries)
+            * u64::from(header.size_of_partition_entry);
+        let entry_sectors = (entries_size + sector_size - 1) / sector_size;
+
+        header.backup_lba = sectors - 1;
+        header.last_usable_lba = sectors - entry_sectors - 2;
+
+        Ok(())
+    }
+
+    /// Renumbers the partitions in the order of their first sector, leaving no unused entries
+    /// between them. The renumbered table is written on `write()`.
+    ///
+    /// Returns the old and new numbers of each partition whose number changed.
+    pub fn sort(&mut self) -> PartitionResult<Vec<(u32, u32)>> {
+        let mut used = self
+            .table
+            .iter()
+            .filter(|(_, entry)| entry.is_used())
+            .map(|(number, entry)| (number, entry.clone()))
+            .collect::<Vec<_>>();
+
+        used.sort_by_key(|(_, entry)| entry.starting_lba);
+
+        let mut renumbered = Vec::new();
+        for (id, (number, _)) in used.iter().enumerate() {
+            if *number != id as u32 + 1 {
+                renumbered.push((*number, id as u32 + 1));
+            }
+        }
+
+        for &(number, _) in &renumbered {
+            self.table
+                .remove(number)
+                .map_err(TableError::from)
+                .map_err(PartitionError::PartitionRemove)?;
+        }
+
+        for (id, (_, entry)) in used.into_iter().enumerate() {
+            self.table[id as u32 + 1] = entry;
+        }
+
+        Ok(renumbered)
     }
 
     fn find(&self, sector: u64) -> PartitionResult<u32> {
@@ -85,24 +312,33 @@ impl Gpt {
     }
 }
 
-impl Partitioner for Gpt {
-    fn add(&mut self, start: u64, end: u64, name: Option<&str>) -> PartitionResult<u32> {
+impl<D: BlockBackend> Partitioner for Gpt<D> {
+    fn add(&mut self, new: NewPartition) -> PartitionResult<u32> {
         let partition = GPTPartitionEntry {
-            starting_lba:         start,
-            ending_lba:           end,
-            attribute_bits:       0,
-            partition_name:       name.unwrap_or("").into(),
-            partition_type_guid:  convert_str_to_array("0FC63DAF-8483-4772-8E79-3D69D8477DE4")
-                .unwrap(),
-            unique_parition_guid: generate_random_uuid(),
+            starting_lba:         new.start,
+            ending_lba:           new.end,
+            attribute_bits:       new.attributes.bits(),
+            partition_name:       new.name.unwrap_or("").into(),
+            partition_type_guid:  convert_str_to_array(new.kind.guid()).unwrap(),
+            unique_parition_guid: new.uuid.unwrap_or_else(generate_random_uuid),
         };
 
-        let id = self
-            .table
-            .iter()
-            .find(|(_, info)| !info.is_used())
-            .map(|(id, _)| id)
-            .ok_or(PartitionError::LimitExceeded)?;
+        let id = match new.number {
+            Some(number) => {
+                let entries = self.table.header.number_of_partition_entries;
+                if number == 0 || number > entries || self.table[number].is_used() {
+                    return Err(PartitionError::NumberInvalid(number));
+                }
+
+                number
+            }
+            None => self
+                .table
+                .iter()
+                .find(|(_, info)| !info.is_used())
+                .map(|(id, _)| id)
+                .ok_or(PartitionError::LimitExceeded)?,
+        };
 
         self.table[id] = partition;
 
@@ -121,8 +357,52 @@ impl Partitioner for Gpt {
         Err(PartitionError::PartitionNotFound)
     }
 
+    fn set_attributes(
+        &mut self,
+        sector: u64,
+        attributes: PartitionAttributes,
+    ) -> PartitionResult<()> {
+        let id = self.find(sector)?;
+        let entry = &mut self.table[id];
+
+        // Bits which are not known to us are preserved.
+        entry.attribute_bits =
+            (entry.attribute_bits & !PartitionAttributes::all().bits()) | attributes.bits();
+
+        Ok(())
+    }
+
+    fn set_partuuid(&mut self, sector: u64, uuid: [u8; 16]) -> PartitionResult<()> {
+        let id = self.find(sector)?;
+        self.table[id].unique_parition_guid = uuid;
+        Ok(())
+    }
+
     fn last_sector(&self) -> u64 { self.table.header.last_usable_lba }
 
+    fn partitions(&self) -> Vec<TablePartition> {
+        let mut partitions = self
+            .table
+            .iter()
+            .filter(|(_, entry)| entry.is_used())
+            .map(|(number, entry)| TablePartition {
+                number,
+                start: entry.starting_lba,
+                end: entry.ending_lba,
+                name: Some(entry.partition_name.as_str())
+                    .filter(|name| !name.is_empty())
+                    .map(Box::from),
+                uuid: convert_array_to_str(&entry.unique_parition_guid).into(),
+                variant: PartitionType::Primary,
+                kind: PartitionKind::from_guid(&convert_array_to_str(&entry.partition_type_guid)),
+                attributes: PartitionAttributes::from_bits_truncate(entry.attribute_bits),
+            })
+            .collect::<Vec<_>>();
+
+        partitions.sort_by_key(|partition| partition.start);
+        partitions
+    }
+
     fn remove(&mut self, sector: u64) -> PartitionResult<()> {
         self.table
             .remove(self.find(sector)?)
@@ -132,25 +412,80 @@ impl Partitioner for Gpt {
         Ok(())
     }
 
+    fn resize(&mut self, sector: u64, start: u64, end: u64) -> PartitionResult<()> {
+        let id = self.find(sector)?;
+        let header = &self.table.header;
+        if start > end || start < header.first_usable_lba || end > header.last_usable_lba {
+            return Err(PartitionError::OutOfBounds);
+        }
+
+        let overlaps = self.table.iter().any(|(other, entry)| {
+            other != id && entry.is_used() && start <= entry.ending_lba && end >= entry.starting_lba
+        });
+
+        if overlaps {
+            return Err(PartitionError::Overlap);
+        }
+
+        let entry = &mut self.table[id];
+        entry.starting_lba = start;
+        entry.ending_lba = end;
+
+        Ok(())
+    }
+
     fn write(&mut self) -> PartitionResult<()> {
         eprintln!("writing table");
+        let (device, sector_size, protective) =
+            (&mut self.device, self.table.sector_size, self.protective);
+
         self.table
-            .write_into(&mut self.device)
+            .write_into(device)
+            .and_then(|_| {
+                if !protective {
+                    return Ok(());
+                }
+
+                write_protective_mbr_into(device, sector_size)
+                    .map_err(|why| gptman::Error::Io(io::Error::new(io::ErrorKind::Other, why)))
+            })
+            .and_then(|_| device.sync().map_err(gptman::Error::Io))
             .map_err(TableError::from)
             .map_err(PartitionError::DeviceWrite)?;
 
+        self.protective = false;
+
         eprintln!("reloading table");
-        gptman::linux::reread_partition_table(&mut self.device)
+        let partitions = self.partitions();
+        self.device
+            .reload(&partitions, self.table.sector_size)
             .map_err(PartitionError::TableReload)?;
 
         Ok(())
     }
 }
 
-fn generate_random_uuid() -> [u8; 16] { rand::thread_rng().gen() }
+/// The size of a block device in logical sectors, as it is currently reported by the kernel.
+pub fn kernel_sectors(device: &Path, sector_size: u64) -> io::Result<u64> {
+    let device = fs::canonicalize(device)?;
+    let name = device
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "device path has no name"))?;
+
+    let size = fs::read_to_string(Path::new("/sys/class/block").join(name).join("size"))?;
+    let size = size
+        .trim()
+        .parse::<u64>()
+        .map_err(|why| io::Error::new(io::ErrorKind::InvalidData, why))?;
+
+    // The kernel reports the size in 512-byte sectors, whatever the sector size of the device.
+    Ok(size * 512 / sector_size)
+}
 
+/// Erases all file system, RAID, and partition table signatures from a device.
 pub fn wipe(device: &Path) -> io::Result<()> {
-    std::process::Command::new("wipefs").arg("-a").arg(device).output().map(|_| ())
+    crate::command::run(std::process::Command::new("wipefs").arg("-a").arg(device))
+        .map_err(io::Error::from)
 }
 
 use bincode::serialize_into;
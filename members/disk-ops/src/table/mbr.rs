@@ -0,0 +1,679 @@
+use disk_types::{PartitionAttributes, PartitionKind, PartitionTable, PartitionType};
+use rand::Rng;
+use std::{
+    cmp,
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use super::{
+    BlockBackend, DumpPartition, NewPartition, PartitionError, PartitionResult, Partitioner,
+    TableDump, TableError, TablePartition,
+};
+
+/// Location of the disk signature in the master boot record.
+const DISK_SIGNATURE: usize = 440;
+/// Location of the first partition entry in a boot record.
+const ENTRIES: usize = 446;
+/// Size of a partition entry in a boot record.
+const ENTRY_SIZE: usize = 16;
+/// Location of the 0x55AA signature in a boot record.
+const BOOT_SIGNATURE: usize = 510;
+
+/// The system ID assigned to new extended partitions.
+const EXTENDED_ID: u8 = 0x05;
+
+/// Logical partitions which are numbered after the four primary partitions.
+const FIRST_LOGICAL: u32 = 5;
+/// Guards against EBR chains which loop back onto themselves.
+const MAX_LOGICAL: usize = 128;
+
+#[derive(Debug, Error)]
+pub enum MbrError {
+    #[error(display = "boot record at sector {} is missing the 0x55AA signature", _0)]
+    BootSignature(u64),
+    #[error(display = "extended boot record at sector {} lies outside the extended partition", _0)]
+    InvalidEbr(u64),
+    #[error(display = "I/O error")]
+    Io(#[error(cause)] io::Error),
+    #[error(display = "extended boot record chain exceeds {} logical partitions", _0)]
+    TooManyLogical(usize),
+}
+
+impl From<io::Error> for MbrError {
+    fn from(error: io::Error) -> Self { MbrError::Io(error) }
+}
+
+/// A partition entry within a boot record, with absolute sector addresses.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct MbrEntry {
+    bootable:  bool,
+    system_id: u8,
+    start:     u64,
+    end:       u64,
+}
+
+impl MbrEntry {
+    fn contains(&self, sector: u64) -> bool { sector >= self.start && sector <= self.end }
+
+    fn is_extended(&self) -> bool {
+        match self.system_id {
+            0x05 | 0x0F | 0x85 => true,
+            _ => false,
+        }
+    }
+
+    fn overlaps(&self, start: u64, end: u64) -> bool { start <= self.end && end >= self.start }
+
+    /// Parses an entry whose starting sector is relative to `base`.
+    fn read(raw: &[u8], base: u64) -> Option<Self> {
+        let system_id = raw[4];
+        let start = u32::from_le_bytes([raw[8], raw[9], raw[10], raw[11]]) as u64;
+        let sectors = u32::from_le_bytes([raw[12], raw[13], raw[14], raw[15]]) as u64;
+
+        if system_id == 0 || sectors == 0 {
+            return None;
+        }
+
+        Some(MbrEntry {
+            bootable: raw[0] == 0x80,
+            system_id,
+            start: base + start,
+            end: base + start + sectors - 1,
+        })
+    }
+
+    /// Serializes the entry with its starting sector relative to `base`.
+    fn write(entry: Option<&Self>, raw: &mut [u8], base: u64) {
+        let entry = match entry {
+            Some(entry) => entry,
+            None => {
+                raw.iter_mut().for_each(|byte| *byte = 0);
+                return;
+            }
+        };
+
+        let start = (entry.start - base) as u32;
+        let sectors = (entry.end - entry.start + 1) as u32;
+
+        raw[0] = if entry.bootable { 0x80 } else { 0x00 };
+        raw[1..4].copy_from_slice(&chs(entry.start));
+        raw[4] = entry.system_id;
+        raw[5..8].copy_from_slice(&chs(entry.end));
+        raw[8..12].copy_from_slice(&start.to_le_bytes());
+        raw[12..16].copy_from_slice(&sectors.to_le_bytes());
+    }
+}
+
+/// A logical partition, and the sector of the extended boot record which describes it.
+#[derive(Debug, Clone, Copy)]
+struct Logical {
+    ebr:   u64,
+    entry: MbrEntry,
+}
+
+/// Locates a partition within the table.
+#[derive(Debug, Clone, Copy)]
+enum Slot {
+    Primary(usize),
+    Logical(usize),
+}
+
+/// A MBR partition table, with up to four primary partitions.
+///
+/// One of the primary partitions may be an extended partition, which contains a chain of
+/// extended boot records (EBRs) describing logical partitions. The first EBR in the chain always
+/// resides at the first sector of the extended partition. Each additional logical partition
+/// stores its EBR in the sector immediately preceding it.
+pub struct Mbr<D: BlockBackend = File> {
+    device:      D,
+    boot_sector: Vec<u8>,
+    sector_size: u64,
+    sectors:     u64,
+    primaries:   [Option<MbrEntry>; 4],
+    logicals:    Vec<Logical>,
+}
+
+impl Mbr {
+    pub fn create(device: &Path, sector_size: u64) -> PartitionResult<Self> {
+        Self::create_from(open(device)?, sector_size)
+    }
+
+    pub fn open(device: &Path) -> PartitionResult<Self> { Self::open_from(open(device)?) }
+
+    /// Creates a new partition table from the layout of a dump, which is written on `write()`.
+    ///
+    /// Primary partitions keep their numbers, and logical partitions must be numbered
+    /// consecutively from 5.
+    pub fn import(device: &Path, sector_size: u64, dump: &TableDump) -> PartitionResult<Self> {
+        Self::import_from(open(device)?, sector_size, dump)
+    }
+}
+
+impl<D: BlockBackend> Mbr<D> {
+    /// Creates a new, empty table spanning the whole backend, which is written on `write()`.
+    pub fn create_from(mut device: D, sector_size: u64) -> PartitionResult<Self> {
+        let sectors =
+            device.seek(SeekFrom::End(0)).map_err(PartitionError::DeviceSeek)? / sector_size;
+
+        let mut boot_sector = vec![0; sector_size as usize];
+        let signature: u32 = rand::thread_rng().gen();
+        boot_sector[DISK_SIGNATURE..DISK_SIGNATURE + 4].copy_from_slice(&signature.to_le_bytes());
+
+        Ok(Mbr {
+            device,
+            boot_sector,
+            sector_size,
+            sectors,
+            primaries: [None; 4],
+            logicals: Vec::new(),
+        })
+    }
+
+    /// Reads the table, and the EBR chain of its extended partition, from a backend.
+    pub fn open_from(mut device: D) -> PartitionResult<Self> {
+        let sector_size = device.logical_sector_size();
+        let sectors =
+            device.seek(SeekFrom::End(0)).map_err(PartitionError::DeviceSeek)? / sector_size;
+
+        let boot_sector = read_boot_record(&mut device, 0, sector_size)
+            .map_err(TableError::from)
+            .map_err(PartitionError::TableRead)?;
+
+        let mut primaries = [None; 4];
+        for (id, primary) in primaries.iter_mut().enumerate() {
+            *primary = MbrEntry::read(&boot_sector[entry_range(id)], 0);
+        }
+
+        let mut mbr =
+            Mbr { device, boot_sector, sector_size, sectors, primaries, logicals: Vec::new() };
+
+        if let Some(extended) = mbr.extended() {
+            mbr.logicals = read_ebr_chain(&mut mbr.device, &extended, sector_size)
+                .map_err(TableError::from)
+                .map_err(PartitionError::TableRead)?;
+
+            // The chain may begin with an empty EBR, so ensure that the head is where it belongs.
+            if let Some(head) = mbr.logicals.first_mut() {
+                head.ebr = extended.start;
+            }
+        }
+
+        Ok(mbr)
+    }
+
+    /// Gives back the backend that the table is kept on.
+    pub fn into_inner(self) -> D { self.device }
+
+    /// Assigns the disk signature, which is written on `write()`.
+    pub fn set_signature(&mut self, signature: u32) {
+        self.boot_sector[DISK_SIGNATURE..DISK_SIGNATURE + 4]
+            .copy_from_slice(&signature.to_le_bytes());
+    }
+
+    /// Assigns the boot code which precedes the disk signature, which is written on `write()`.
+    ///
+    /// Code beyond the 440 bytes that the boot code may occupy is ignored.
+    pub fn set_boot_code(&mut self, code: &[u8]) {
+        let length = cmp::min(code.len(), DISK_SIGNATURE);
+        self.boot_sector[..length].copy_from_slice(&code[..length]);
+    }
+
+    /// Describes the partition table in the layout of a dump.
+    pub fn export(&self) -> TableDump {
+        let partition = |number: u32, entry: &MbrEntry| DumpPartition {
+            number,
+            start: entry.start,
+            size: entry.end - entry.start + 1,
+            type_id: format!("{:x}", entry.system_id).into(),
+            uuid: None,
+            name: None,
+            attributes: if entry.bootable {
+                PartitionAttributes::LEGACY_BOOTABLE.bits()
+            } else {
+                0
+            },
+        };
+
+        let primaries =
+            self.primaries.iter().enumerate().filter_map(|(id, entry)| {
+                entry.as_ref().map(|entry| partition(id as u32 + 1, entry))
+            });
+
+        let logicals = self
+            .logicals
+            .iter()
+            .enumerate()
+            .map(|(id, logical)| partition(FIRST_LOGICAL + id as u32, &logical.entry));
+
+        TableDump {
+            table:       PartitionTable::Mbr,
+            label_id:    Some(format!("0x{:08x}", self.signature()).into()),
+            device:      None,
+            first_lba:   None,
+            last_lba:    None,
+            sector_size: Some(self.sector_size),
+            partitions:  primaries.chain(logicals).collect(),
+        }
+    }
+
+    /// Creates a new partition table on a backend from the layout of a dump.
+    pub fn import_from(device: D, sector_size: u64, dump: &TableDump) -> PartitionResult<Self> {
+        let mut mbr = Self::create_from(device, sector_size)?;
+
+        if let Some(ref label_id) = dump.label_id {
+            let digits = label_id.trim_start_matches("0x");
+            let signature = u32::from_str_radix(digits, 16)
+                .map_err(|_| PartitionError::UuidInvalid(label_id.clone()))?;
+            mbr.set_signature(signature);
+        }
+
+        let mut partitions = dump.partitions.iter().collect::<Vec<_>>();
+        partitions.sort_by_key(|partition| partition.number);
+
+        for partition in partitions {
+            let system_id = u8::from_str_radix(partition.type_id.trim_start_matches("0x"), 16)
+                .map_err(|_| PartitionError::TypeInvalid(partition.type_id.clone()))?;
+
+            let entry = MbrEntry {
+                bootable: partition.attributes & PartitionAttributes::LEGACY_BOOTABLE.bits() != 0,
+                system_id,
+                start: partition.start,
+                end: partition.end(),
+            };
+
+            if entry.start == 0 || entry.end > mbr.last_sector() {
+                return Err(PartitionError::OutOfBounds);
+            }
+
+            let number = partition.number;
+            if number >= FIRST_LOGICAL {
+                if mbr.add_logical(entry)? != number {
+                    return Err(PartitionError::NumberInvalid(number));
+                }
+
+                continue;
+            }
+
+            let id = match number.checked_sub(1) {
+                Some(id) if mbr.primaries[id as usize].is_none() => id as usize,
+                _ => return Err(PartitionError::NumberInvalid(number)),
+            };
+
+            if entry.is_extended() && mbr.extended().is_some() {
+                return Err(PartitionError::ExtendedExists);
+            }
+
+            if mbr.primaries.iter().filter_map(|p| *p).any(|p| p.overlaps(entry.start, entry.end)) {
+                return Err(PartitionError::Overlap);
+            }
+
+            mbr.primaries[id] = Some(entry);
+        }
+
+        Ok(mbr)
+    }
+
+    /// The disk signature, which is also used as the prefix of each partition's PARTUUID.
+    fn signature(&self) -> u32 {
+        let raw = &self.boot_sector[DISK_SIGNATURE..DISK_SIGNATURE + 4];
+        u32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]])
+    }
+
+    fn extended(&self) -> Option<MbrEntry> {
+        self.primaries.iter().filter_map(|entry| *entry).find(MbrEntry::is_extended)
+    }
+
+    fn find(&self, sector: u64) -> PartitionResult<Slot> {
+        if let Some(id) = self.logicals.iter().position(|logical| logical.entry.contains(sector)) {
+            return Ok(Slot::Logical(id));
+        }
+
+        self.primaries
+            .iter()
+            .position(|entry| entry.map_or(false, |entry| entry.contains(sector)))
+            .map(Slot::Primary)
+            .ok_or(PartitionError::PartitionNotFound)
+    }
+
+    fn add_logical(&mut self, entry: MbrEntry) -> PartitionResult<u32> {
+        let extended = self.extended().ok_or(PartitionError::ExtendedNotFound)?;
+
+        // The sector before a logical partition is reserved for its EBR.
+        if entry.start <= extended.start || entry.end > extended.end {
+            return Err(PartitionError::OutsideExtended);
+        }
+
+        let ebr = if self.logicals.is_empty() { extended.start } else { entry.start - 1 };
+
+        let overlaps = self.logicals.iter().any(|logical| {
+            logical.entry.overlaps(ebr, entry.end)
+                || entry.contains(logical.ebr)
+                || ebr == logical.ebr
+        });
+
+        if overlaps {
+            return Err(PartitionError::Overlap);
+        }
+
+        self.logicals.push(Logical { ebr, entry });
+        Ok(FIRST_LOGICAL + self.logicals.len() as u32 - 1)
+    }
+
+    fn add_primary(&mut self, entry: MbrEntry, number: Option<u32>) -> PartitionResult<u32> {
+        if entry.is_extended() && self.extended().is_some() {
+            return Err(PartitionError::ExtendedExists);
+        }
+
+        if self.primaries.iter().filter_map(|p| *p).any(|p| p.overlaps(entry.start, entry.end)) {
+            return Err(PartitionError::Overlap);
+        }
+
+        let id = match number {
+            Some(number) => match number.checked_sub(1) {
+                Some(id) if id < 4 && self.primaries[id as usize].is_none() => id as usize,
+                _ => return Err(PartitionError::NumberInvalid(number)),
+            },
+            None => self
+                .primaries
+                .iter()
+                .position(Option::is_none)
+                .ok_or(PartitionError::PrimaryLimitExceeded)?,
+        };
+
+        self.primaries[id] = Some(entry);
+        Ok(id as u32 + 1)
+    }
+
+    fn write_records(&mut self) -> Result<(), MbrError> {
+        let mut boot_sector = self.boot_sector.clone();
+        for (id, primary) in self.primaries.iter().enumerate() {
+            MbrEntry::write(primary.as_ref(), &mut boot_sector[entry_range(id)], 0);
+        }
+
+        write_boot_record(&mut self.device, 0, self.sector_size, &mut boot_sector)?;
+
+        if let Some(extended) = self.extended() {
+            let mut record = vec![0; self.sector_size as usize];
+
+            // An extended partition without logical partitions has an empty EBR.
+            if self.logicals.is_empty() {
+                write_boot_record(&mut self.device, extended.start, self.sector_size, &mut record)?;
+            }
+
+            for (id, logical) in self.logicals.iter().enumerate() {
+                let next = self.logicals.get(id + 1).map(|next| MbrEntry {
+                    bootable:  false,
+                    system_id: EXTENDED_ID,
+                    start:     next.ebr,
+                    end:       next.entry.end,
+                });
+
+                MbrEntry::write(Some(&logical.entry), &mut record[entry_range(0)], logical.ebr);
+                MbrEntry::write(next.as_ref(), &mut record[entry_range(1)], extended.start);
+                write_boot_record(&mut self.device, logical.ebr, self.sector_size, &mut record)?;
+            }
+        }
+
+        self.boot_sector = boot_sector;
+        self.device.sync().map_err(MbrError::Io)
+    }
+}
+
+impl<D: BlockBackend> Partitioner for Mbr<D> {
+    fn add(&mut self, new: NewPartition) -> PartitionResult<u32> {
+        // The first sector is reserved for the master boot record.
+        if new.start == 0 || new.start > new.end || new.end > self.last_sector() {
+            return Err(PartitionError::OutOfBounds);
+        }
+
+        // PARTUUIDs are derived from the disk signature and the partition number.
+        if new.uuid.is_some() {
+            return Err(PartitionError::UuidUnsupported);
+        }
+
+        let system_id = match new.variant {
+            PartitionType::Extended => EXTENDED_ID,
+            _ => new.kind.mbr_id().ok_or(PartitionError::KindUnsupported(new.kind))?,
+        };
+
+        let bootable = legacy_bootable(new.attributes)?;
+        let entry = MbrEntry { bootable, system_id, start: new.start, end: new.end };
+
+        match new.variant {
+            PartitionType::Primary | PartitionType::Extended => self.add_primary(entry, new.number),
+            PartitionType::Logical => {
+                // Logical partitions are numbered by their position in the EBR chain.
+                let next = FIRST_LOGICAL + self.logicals.len() as u32;
+                match new.number {
+                    Some(number) if number != next => Err(PartitionError::NumberInvalid(number)),
+                    _ => self.add_logical(entry),
+                }
+            }
+        }
+    }
+
+    fn label(&mut self, _sector: u64, _label: &str) -> PartitionResult<()> {
+        Err(PartitionError::LabelsUnsupported)
+    }
+
+    fn set_attributes(
+        &mut self,
+        sector: u64,
+        attributes: PartitionAttributes,
+    ) -> PartitionResult<()> {
+        let bootable = legacy_bootable(attributes)?;
+
+        match self.find(sector)? {
+            Slot::Logical(id) => self.logicals[id].entry.bootable = bootable,
+            Slot::Primary(id) => {
+                if let Some(entry) = self.primaries[id].as_mut() {
+                    entry.bootable = bootable;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// MBR tables derive the unique IDs of partitions from the disk signature.
+    fn set_partuuid(&mut self, _sector: u64, _uuid: [u8; 16]) -> PartitionResult<()> {
+        Err(PartitionError::UuidUnsupported)
+    }
+
+    fn last_sector(&self) -> u64 { cmp::min(self.sectors - 1, u64::from(u32::max_value())) }
+
+    fn partitions(&self) -> Vec<TablePartition> {
+        let partition = |number: u32, entry: &MbrEntry, variant: PartitionType| TablePartition {
+            number,
+            start: entry.start,
+            end: entry.end,
+            name: None,
+            uuid: format!("{:08x}-{:02x}", self.signature(), number).into(),
+            variant,
+            kind: PartitionKind::from_mbr_id(entry.system_id),
+            attributes: if entry.bootable {
+                PartitionAttributes::LEGACY_BOOTABLE
+            } else {
+                PartitionAttributes::empty()
+            },
+        };
+
+        let primaries = self.primaries.iter().enumerate().filter_map(|(id, entry)| {
+            entry.as_ref().map(|entry| {
+                let variant = if entry.is_extended() {
+                    PartitionType::Extended
+                } else {
+                    PartitionType::Primary
+                };
+                partition(id as u32 + 1, entry, variant)
+            })
+        });
+
+        let logicals = self.logicals.iter().enumerate().map(|(id, logical)| {
+            partition(FIRST_LOGICAL + id as u32, &logical.entry, PartitionType::Logical)
+        });
+
+        let mut partitions = primaries.chain(logicals).collect::<Vec<_>>();
+        partitions.sort_by_key(|partition| partition.start);
+        partitions
+    }
+
+    fn remove(&mut self, sector: u64) -> PartitionResult<()> {
+        match self.find(sector)? {
+            Slot::Logical(id) => {
+                let removed = self.logicals.remove(id);
+
+                // The next logical partition takes over the head of the EBR chain.
+                if id == 0 {
+                    if let Some(head) = self.logicals.first_mut() {
+                        head.ebr = removed.ebr;
+                    }
+                }
+            }
+            Slot::Primary(id) => {
+                // Removing the extended partition also removes its logical partitions.
+                if self.primaries[id].map_or(false, |entry| entry.is_extended()) {
+                    self.logicals.clear();
+                }
+
+                self.primaries[id] = None;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn resize(&mut self, sector: u64, start: u64, end: u64) -> PartitionResult<()> {
+        if start == 0 || start > end || end > self.last_sector() {
+            return Err(PartitionError::OutOfBounds);
+        }
+
+        // Logical partitions would also require their EBRs to be moved.
+        let id = match self.find(sector)? {
+            Slot::Primary(id) => id,
+            Slot::Logical(_) => return Err(PartitionError::ResizeUnsupported),
+        };
+
+        let entry = match self.primaries[id] {
+            Some(entry) if !entry.is_extended() => entry,
+            _ => return Err(PartitionError::ResizeUnsupported),
+        };
+
+        let overlaps = self.primaries.iter().enumerate().any(|(other, primary)| {
+            other != id && primary.map_or(false, |primary| primary.overlaps(start, end))
+        });
+
+        if overlaps {
+            return Err(PartitionError::Overlap);
+        }
+
+        self.primaries[id] = Some(MbrEntry { start, end, ..entry });
+        Ok(())
+    }
+
+    fn write(&mut self) -> PartitionResult<()> {
+        eprintln!("writing table");
+        self.write_records().map_err(TableError::from).map_err(PartitionError::DeviceWrite)?;
+
+        eprintln!("reloading table");
+        let partitions = self.partitions();
+        self.device.reload(&partitions, self.sector_size).map_err(PartitionError::TableReload)?;
+
+        Ok(())
+    }
+}
+
+/// The boot indicator is the only attribute that a MBR partition entry can store.
+fn legacy_bootable(attributes: PartitionAttributes) -> PartitionResult<bool> {
+    let unsupported = attributes - PartitionAttributes::LEGACY_BOOTABLE;
+    if !unsupported.is_empty() {
+        return Err(PartitionError::AttributesUnsupported(unsupported));
+    }
+
+    Ok(attributes.contains(PartitionAttributes::LEGACY_BOOTABLE))
+}
+
+/// Converts a LBA address into the legacy cylinder-head-sector address.
+///
+/// Addresses which cannot be represented by CHS are given the maximum value.
+fn chs(lba: u64) -> [u8; 3] {
+    const HEADS: u64 = 255;
+    const SECTORS: u64 = 63;
+
+    let cylinder = lba / (HEADS * SECTORS);
+    if cylinder > 1023 {
+        return [0xFE, 0xFF, 0xFF];
+    }
+
+    let head = (lba / SECTORS) % HEADS;
+    let sector = lba % SECTORS + 1;
+
+    [head as u8, ((cylinder >> 2) & 0xC0) as u8 | sector as u8, cylinder as u8]
+}
+
+fn entry_range(id: usize) -> std::ops::Range<usize> {
+    ENTRIES + id * ENTRY_SIZE..ENTRIES + (id + 1) * ENTRY_SIZE
+}
+
+/// Follows the EBR chain of an extended partition, collecting its logical partitions.
+fn read_ebr_chain<D: Read + Seek>(
+    device: &mut D,
+    extended: &MbrEntry,
+    sector_size: u64,
+) -> Result<Vec<Logical>, MbrError> {
+    let mut logicals = Vec::new();
+    let mut ebr = extended.start;
+
+    for _ in 0..MAX_LOGICAL {
+        if !extended.contains(ebr) {
+            return Err(MbrError::InvalidEbr(ebr));
+        }
+
+        let record = read_boot_record(device, ebr, sector_size)?;
+
+        if let Some(entry) = MbrEntry::read(&record[entry_range(0)], ebr) {
+            logicals.push(Logical { ebr, entry });
+        }
+
+        match MbrEntry::read(&record[entry_range(1)], extended.start) {
+            Some(next) => ebr = next.start,
+            None => return Ok(logicals),
+        }
+    }
+
+    Err(MbrError::TooManyLogical(MAX_LOGICAL))
+}
+
+fn read_boot_record<D: Read + Seek>(
+    device: &mut D,
+    lba: u64,
+    sector_size: u64,
+) -> Result<Vec<u8>, MbrError> {
+    let mut record = vec![0; sector_size as usize];
+    device.seek(SeekFrom::Start(lba * sector_size))?;
+    device.read_exact(&mut record)?;
+
+    if record[BOOT_SIGNATURE..BOOT_SIGNATURE + 2] != [0x55, 0xAA] {
+        return Err(MbrError::BootSignature(lba));
+    }
+
+    Ok(record)
+}
+
+fn write_boot_record<D: Write + Seek>(
+    device: &mut D,
+    lba: u64,
+    sector_size: u64,
+    record: &mut [u8],
+) -> Result<(), MbrError> {
+    record[BOOT_SIGNATURE..BOOT_SIGNATURE + 2].copy_from_slice(&[0x55, 0xAA]);
+    device.seek(SeekFrom::Start(lba * sector_size))?;
+    device.write_all(record)?;
+    Ok(())
+}
+
+fn open(device: &Path) -> PartitionResult<File> {
+    OpenOptions::new().read(true).write(true).open(device).map_err(PartitionError::DeviceOpen)
+}
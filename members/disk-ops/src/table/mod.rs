@@ -1,61 +1,184 @@
+mod backend;
+mod convert;
+mod dump;
 mod gpt;
+mod mbr;
+mod sync;
+mod uuids;
+mod verify;
 
-pub use self::gpt::*;
+pub use self::{backend::*, convert::*, dump::*, gpt::*, mbr::*, sync::*, uuids::*, verify::*};
 
-use disk_types::PartitionTable;
+use disk_types::{PartitionAttributes, PartitionKind, PartitionTable, PartitionType};
 use gptman::{GPTPartitionEntry, GPT};
 use rand::Rng;
 use std::{
     fs::{File, OpenOptions},
     io::{self, Seek, SeekFrom},
+    os::unix::io::AsRawFd,
     path::Path,
 };
 
+/// Describes a partition that is to be added to a partition table.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NewPartition<'a> {
+    /// The first sector of the partition.
+    pub start:      u64,
+    /// The last sector of the partition, inclusive.
+    pub end:        u64,
+    /// The name of the partition, which is only supported by GPT tables.
+    pub name:       Option<&'a str>,
+    /// Whether the partition is primary, extended, or logical. Only applies to MBR tables.
+    pub variant:    PartitionType,
+    /// The type GUID of the partition on GPT tables, or its system ID on MBR tables.
+    pub kind:       PartitionKind,
+    /// Attribute bits to assign to the partition.
+    pub attributes: PartitionAttributes,
+    /// The PARTUUID to assign, which is generated if not given. Only applies to GPT tables.
+    pub uuid:       Option<[u8; 16]>,
+    /// The number to assign, instead of the first that is unused.
+    pub number:     Option<u32>,
+}
+
+/// A partition which exists within a partition table.
+#[derive(Debug, Clone)]
+pub struct TablePartition {
+    /// The number that the partition is identified by.
+    pub number:     u32,
+    /// The first sector of the partition.
+    pub start:      u64,
+    /// The last sector of the partition, inclusive.
+    pub end:        u64,
+    /// The name of the partition, if it has one.
+    pub name:       Option<Box<str>>,
+    /// The PARTUUID of the partition.
+    pub uuid:       Box<str>,
+    /// Whether the partition is primary, extended, or logical.
+    pub variant:    PartitionType,
+    /// The partition type, if it is a well-known type.
+    pub kind:       Option<PartitionKind>,
+    /// Attribute bits assigned to the partition.
+    pub attributes: PartitionAttributes,
+}
+
 pub trait Partitioner {
     /// Adds a new partition to the in-memory partition table.
-    fn add(&mut self, start: u64, end: u64, name: Option<&str>) -> PartitionResult<u32>;
+    fn add(&mut self, partition: NewPartition) -> PartitionResult<u32>;
 
     /// Set the label of the partition at the sector.
     fn label(&mut self, sector: u64, label: &str) -> PartitionResult<()>;
 
+    /// Set the attribute bits of the partition at the sector.
+    fn set_attributes(
+        &mut self,
+        sector: u64,
+        attributes: PartitionAttributes,
+    ) -> PartitionResult<()>;
+
+    /// Set the unique GUID of the partition at the sector.
+    fn set_partuuid(&mut self, sector: u64, uuid: [u8; 16]) -> PartitionResult<()>;
+
     /// The last addressable sector in the table.
     fn last_sector(&self) -> u64;
 
+    /// All partitions in the table, sorted by their first sector.
+    fn partitions(&self) -> Vec<TablePartition>;
+
     /// Removes the partition that resides at the given sector.
     fn remove(&mut self, sector: u64) -> PartitionResult<()>;
 
+    /// Moves the first and last sectors of the partition that resides at the given sector.
+    fn resize(&mut self, sector: u64, start: u64, end: u64) -> PartitionResult<()>;
+
     /// Writes the in-memory partition table to the device.
     fn write(&mut self) -> PartitionResult<()>;
 }
 
 #[derive(Debug, Error)]
 pub enum PartitionError {
+    #[error(display = "the partition table does not support the {:?} attributes", _0)]
+    AttributesUnsupported(PartitionAttributes),
     #[error(display = "device could not be opened")]
     DeviceOpen(#[error(cause)] io::Error),
+    #[error(display = "device read failed")]
+    DeviceRead(#[error(cause)] io::Error),
     #[error(display = "device seek failed")]
     DeviceSeek(#[error(cause)] io::Error),
     #[error(display = "device write failed")]
     DeviceWrite(#[error(cause)] TableError),
+    #[error(display = "an extended partition already exists on the device")]
+    ExtendedExists,
+    #[error(display = "logical partitions require an extended partition")]
+    ExtendedNotFound,
+    #[error(display = "{:?} partitions are not supported by the partition table", _0)]
+    KindUnsupported(PartitionKind),
+    #[error(display = "the partition table does not support partition labels")]
+    LabelsUnsupported,
     #[error(display = "partition limit on device exceeded")]
     LimitExceeded,
+    #[error(display = "the device has not grown beyond the end of its partition table")]
+    NotGrown,
+    #[error(display = "partition number {} is invalid or already in use", _0)]
+    NumberInvalid(u32),
+    #[error(display = "logical partition does not fit within the extended partition")]
+    OutsideExtended,
+    #[error(display = "partition lies outside of the usable sectors of the table")]
+    OutOfBounds,
+    #[error(display = "new partition overlaps an existing partition")]
+    Overlap,
     #[error(display = "partition not found")]
     PartitionNotFound,
     #[error(display = "partition could not be removed")]
     PartitionRemove(#[error(cause)] TableError),
+    #[error(display = "MBR tables are limited to four primary partitions")]
+    PrimaryLimitExceeded,
+    #[error(display = "neither header of the GPT table is intact")]
+    RepairImpossible,
+    #[error(display = "repaired GPT header could not be written")]
+    RepairWrite(#[error(cause)] io::Error),
+    #[error(display = "extended and logical partitions cannot be resized")]
+    ResizeUnsupported,
+    #[error(display = "expected a sector size of {}, but the device has {}", _0, _1)]
+    SectorSizeMismatch(u64, u64),
     #[error(display = "partition table could not be read")]
     TableRead(#[error(cause)] TableError),
     #[error(display = "partition table could not be reloaded")]
-    TableReload(#[error(cause)] gptman::linux::BlockError),
+    TableReload(#[error(cause)] SyncError),
+    #[error(display = "{} is not a valid partition type", _0)]
+    TypeInvalid(Box<str>),
+    #[error(display = "{} is not a valid GUID or disk signature", _0)]
+    UuidInvalid(Box<str>),
+    #[error(display = "the partition table does not support choosing partition UUIDs")]
+    UuidUnsupported,
 }
 
 #[derive(Debug, Error)]
 pub enum TableError {
     #[error(display = "GPT table error")]
     Gpt(#[error(cause)] gptman::Error),
+    #[error(display = "MBR table error")]
+    Mbr(#[error(cause)] MbrError),
 }
 
 impl From<gptman::Error> for TableError {
     fn from(error: gptman::Error) -> Self { TableError::Gpt(error) }
 }
 
+impl From<MbrError> for TableError {
+    fn from(error: MbrError) -> Self { TableError::Mbr(error) }
+}
+
 pub type PartitionResult<T> = Result<T, PartitionError>;
+
+ioctl_read_bad!(blksszget, 0x1268, nix::libc::c_int);
+
+/// Fetches the logical sector size of a block device.
+///
+/// Files which are not block devices, such as disk images, are assumed to have 512-byte sectors.
+pub fn logical_sector_size(device: &File) -> u64 {
+    let mut size: nix::libc::c_int = 0;
+    match unsafe { blksszget(device.as_raw_fd(), &mut size) } {
+        Ok(_) if size > 0 => size as u64,
+        _ => 512,
+    }
+}
@@ -0,0 +1,214 @@
+//! Informs the kernel of changes that were written to a partition table.
+//!
+//! Rereading the whole table with BLKRRPART fails when any partition on the device is in use,
+//! so only the partitions which differ from the kernel's view are added, removed, or resized
+//! with the BLKPG ioctl. The whole table is only reread if the device does not support BLKPG.
+
+use super::TablePartition;
+use disk_types::PartitionType;
+use nix::libc::{c_char, c_int, c_longlong, c_void, EBUSY};
+use std::{
+    fs::{self, File},
+    io,
+    os::unix::{
+        fs::{FileTypeExt, MetadataExt},
+        io::AsRawFd,
+    },
+    path::Path,
+};
+
+const BLKPG_ADD_PARTITION: c_int = 1;
+const BLKPG_DEL_PARTITION: c_int = 2;
+const BLKPG_RESIZE_PARTITION: c_int = 3;
+
+/// The kernel presents extended partitions as this many bytes, to protect their boot records.
+const EXTENDED_LENGTH: u64 = 1024;
+
+#[repr(C)]
+struct BlkpgPartition {
+    start:   c_longlong,
+    length:  c_longlong,
+    pno:     c_int,
+    devname: [c_char; 64],
+    volname: [c_char; 64],
+}
+
+#[repr(C)]
+struct BlkpgIoctlArg {
+    op:      c_int,
+    flags:   c_int,
+    datalen: c_int,
+    data:    *mut c_void,
+}
+
+ioctl_write_ptr_bad!(blkpg, 0x1269, BlkpgIoctlArg);
+ioctl_none_bad!(blkrrpart, 0x125f);
+
+#[derive(Debug, Error)]
+pub enum SyncError {
+    #[error(display = "partition {} is in use, so the kernel cannot be told of its changes", _0)]
+    Busy(u32, #[error(cause)] io::Error),
+    #[error(display = "unable to read the partitions known to the kernel")]
+    KernelRead(#[error(cause)] io::Error),
+    #[error(display = "unable to reread the partition table")]
+    Reread(#[error(cause)] io::Error),
+}
+
+/// A partition as the kernel knows it, in bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KernelPartition {
+    pub number: u32,
+    pub start:  u64,
+    pub length: u64,
+}
+
+/// A change to the kernel's view of a partition, which is made with BLKPG.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KernelChange {
+    Remove(KernelPartition),
+    Resize(KernelPartition),
+    Add(KernelPartition),
+}
+
+/// Brings the kernel's view of the partitions of a device in line with the given partitions.
+///
+/// Files which are not block devices, such as disk images, are ignored.
+pub fn sync_kernel(
+    device: &File,
+    partitions: &[TablePartition],
+    sector_size: u64,
+) -> Result<(), SyncError> {
+    let known = match kernel_partitions(device).map_err(SyncError::KernelRead)? {
+        Some(known) => known,
+        None => return Ok(()),
+    };
+
+    for change in kernel_changes(&known, partitions, sector_size) {
+        let (op, partition) = match change {
+            KernelChange::Remove(old) => (BLKPG_DEL_PARTITION, old),
+            KernelChange::Resize(new) => (BLKPG_RESIZE_PARTITION, new),
+            KernelChange::Add(new) => (BLKPG_ADD_PARTITION, new),
+        };
+
+        if let Err(why) = update(device, op, partition) {
+            // Rereading the table would fail for the same reason.
+            if why.raw_os_error() == Some(EBUSY) {
+                return Err(SyncError::Busy(partition.number, why));
+            }
+
+            // Otherwise, the device may not support BLKPG.
+            return unsafe { blkrrpart(device.as_raw_fd()) }
+                .map(|_| ())
+                .map_err(|_| SyncError::Reread(io::Error::last_os_error()));
+        }
+    }
+
+    Ok(())
+}
+
+/// The changes which bring the partitions known to the kernel in line with the given partitions,
+/// in the order that they must be made.
+///
+/// Partitions are removed first, then shrunk, then grown, and then added, so that no two
+/// partitions overlap at any moment.
+pub fn kernel_changes(
+    known: &[KernelPartition],
+    partitions: &[TablePartition],
+    sector_size: u64,
+) -> Vec<KernelChange> {
+    let wanted = partitions
+        .iter()
+        .map(|partition| KernelPartition {
+            number: partition.number,
+            start:  partition.start * sector_size,
+            length: match partition.variant {
+                PartitionType::Extended => EXTENDED_LENGTH,
+                _ => (partition.end - partition.start + 1) * sector_size,
+            },
+        })
+        .collect::<Vec<_>>();
+
+    let mut removed = Vec::new();
+    let mut resized = Vec::new();
+    for old in known {
+        match wanted.iter().find(|new| new.number == old.number) {
+            // The kernel can only resize a partition whose start is unchanged.
+            Some(new) if new.start == old.start => {
+                if new.length != old.length {
+                    resized.push((*old, *new));
+                }
+            }
+            _ => removed.push(*old),
+        }
+    }
+
+    let added = wanted
+        .iter()
+        .filter(|new| !known.iter().any(|old| old.number == new.number && old.start == new.start));
+
+    resized.sort_by_key(|(old, new)| new.length > old.length);
+
+    removed
+        .into_iter()
+        .map(KernelChange::Remove)
+        .chain(resized.into_iter().map(|(_, new)| KernelChange::Resize(new)))
+        .chain(added.map(|&new| KernelChange::Add(new)))
+        .collect()
+}
+
+fn update(device: &File, op: c_int, partition: KernelPartition) -> io::Result<()> {
+    let mut data = BlkpgPartition {
+        start:   partition.start as c_longlong,
+        length:  partition.length as c_longlong,
+        pno:     partition.number as c_int,
+        devname: [0; 64],
+        volname: [0; 64],
+    };
+
+    let arg = BlkpgIoctlArg {
+        op,
+        flags:   0,
+        datalen: std::mem::size_of::<BlkpgPartition>() as c_int,
+        data:    &mut data as *mut BlkpgPartition as *mut c_void,
+    };
+
+    unsafe { blkpg(device.as_raw_fd(), &arg) }.map(|_| ()).map_err(|_| io::Error::last_os_error())
+}
+
+/// The partitions of a block device which the kernel currently knows of, from sysfs.
+///
+/// Returns `None` if the file is not a block device.
+fn kernel_partitions(device: &File) -> io::Result<Option<Vec<KernelPartition>>> {
+    let metadata = device.metadata()?;
+    if !metadata.file_type().is_block_device() {
+        return Ok(None);
+    }
+
+    let rdev = metadata.rdev();
+    let major = ((rdev >> 8) & 0xfff) | ((rdev >> 32) & !0xfff);
+    let minor = (rdev & 0xff) | ((rdev >> 12) & 0xffff_ff00);
+
+    let read = |path: &Path| -> io::Result<u64> {
+        fs::read_to_string(path)?
+            .trim()
+            .parse::<u64>()
+            .map_err(|why| io::Error::new(io::ErrorKind::InvalidData, why))
+    };
+
+    let mut partitions = Vec::new();
+    for entry in fs::read_dir(format!("/sys/dev/block/{}:{}", major, minor))? {
+        let path = entry?.path();
+        if !path.join("partition").exists() {
+            continue;
+        }
+
+        // Sysfs reports sectors of 512 bytes, whatever the sector size of the device.
+        partitions.push(KernelPartition {
+            number: read(&path.join("partition"))? as u32,
+            start:  read(&path.join("start"))? * 512,
+            length: read(&path.join("size"))? * 512,
+        });
+    }
+
+    Ok(Some(partitions))
+}
@@ -0,0 +1,218 @@
+//! Choosing the disk GUIDs of new tables, and the UUIDs of new partitions.
+//!
+//! Seeded UUIDs are the leading half of a HMAC-SHA256 of a distinguishing message, keyed with the
+//! seed, and marked as a version 4 UUID. The same seed and layout will always produce the same
+//! UUIDs. Partitions are distinguished by their type GUID and first sector.
+
+use disk_types::PartitionKind;
+use rand::Rng;
+
+/// Determines how the UUIDs of a new table or partition are chosen.
+///
+/// UUIDs are given in the byte order that GPT stores them in, which is what
+/// `convert_str_to_array` returns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UuidPolicy {
+    /// UUIDs are generated at random.
+    Random,
+    /// UUIDs are derived from a seed, such as the machine ID.
+    Seeded([u8; 16]),
+    /// The given UUID is used as it is.
+    Explicit([u8; 16]),
+}
+
+impl Default for UuidPolicy {
+    fn default() -> Self { UuidPolicy::Random }
+}
+
+impl UuidPolicy {
+    /// The GUID of a new partition table.
+    ///
+    /// MBR tables take their disk signature from the first four bytes.
+    pub fn disk_guid(self) -> [u8; 16] {
+        match self {
+            UuidPolicy::Random => generate_random_uuid(),
+            UuidPolicy::Seeded(seed) => hashed_uuid(&seed, b"disk-guid"),
+            UuidPolicy::Explicit(uuid) => uuid,
+        }
+    }
+
+    /// The UUID of a new partition of the given type, starting at the given sector.
+    pub fn partition_uuid(self, kind: PartitionKind, start: u64) -> [u8; 16] {
+        match self {
+            UuidPolicy::Random => generate_random_uuid(),
+            UuidPolicy::Seeded(seed) => {
+                let mut message = kind.guid().as_bytes().to_vec();
+                message.extend_from_slice(&start.to_le_bytes());
+                hashed_uuid(&seed, &message)
+            }
+            UuidPolicy::Explicit(uuid) => uuid,
+        }
+    }
+}
+
+pub(crate) fn generate_random_uuid() -> [u8; 16] { rand::thread_rng().gen() }
+
+fn hashed_uuid(seed: &[u8; 16], message: &[u8]) -> [u8; 16] {
+    let mut uuid = [0; 16];
+    uuid.copy_from_slice(&hmac_sha256(seed, message)[..16]);
+
+    // The version is in the high nibble of the fourth field, which GPT stores little-endian.
+    uuid[7] = (uuid[7] & 0x0F) | 0x40;
+    uuid[8] = (uuid[8] & 0x3F) | 0x80;
+    uuid
+}
+
+/// The HMAC-SHA256 of a message, as defined by RFC 2104.
+pub fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    // Keys longer than a block are replaced by their digest.
+    let hashed;
+    let key = if key.len() > 64 {
+        hashed = sha256(key);
+        &hashed[..]
+    } else {
+        key
+    };
+
+    let mut inner = vec![0x36; 64];
+    let mut outer = vec![0x5C; 64];
+    for (id, byte) in key.iter().enumerate() {
+        inner[id] ^= byte;
+        outer[id] ^= byte;
+    }
+
+    inner.extend_from_slice(message);
+    outer.extend_from_slice(&sha256(&inner));
+    sha256(&outer)
+}
+
+const K: [u32; 64] = [
+    0x428a_2f98,
+    0x7137_4491,
+    0xb5c0_fbcf,
+    0xe9b5_dba5,
+    0x3956_c25b,
+    0x59f1_11f1,
+    0x923f_82a4,
+    0xab1c_5ed5,
+    0xd807_aa98,
+    0x1283_5b01,
+    0x2431_85be,
+    0x550c_7dc3,
+    0x72be_5d74,
+    0x80de_b1fe,
+    0x9bdc_06a7,
+    0xc19b_f174,
+    0xe49b_69c1,
+    0xefbe_4786,
+    0x0fc1_9dc6,
+    0x240c_a1cc,
+    0x2de9_2c6f,
+    0x4a74_84aa,
+    0x5cb0_a9dc,
+    0x76f9_88da,
+    0x983e_5152,
+    0xa831_c66d,
+    0xb003_27c8,
+    0xbf59_7fc7,
+    0xc6e0_0bf3,
+    0xd5a7_9147,
+    0x06ca_6351,
+    0x1429_2967,
+    0x27b7_0a85,
+    0x2e1b_2138,
+    0x4d2c_6dfc,
+    0x5338_0d13,
+    0x650a_7354,
+    0x766a_0abb,
+    0x81c2_c92e,
+    0x9272_2c85,
+    0xa2bf_e8a1,
+    0xa81a_664b,
+    0xc24b_8b70,
+    0xc76c_51a3,
+    0xd192_e819,
+    0xd699_0624,
+    0xf40e_3585,
+    0x106a_a070,
+    0x19a4_c116,
+    0x1e37_6c08,
+    0x2748_774c,
+    0x34b0_bcb5,
+    0x391c_0cb3,
+    0x4ed8_aa4a,
+    0x5b9c_ca4f,
+    0x682e_6ff3,
+    0x748f_82ee,
+    0x78a5_636f,
+    0x84c8_7814,
+    0x8cc7_0208,
+    0x90be_fffa,
+    0xa450_6ceb,
+    0xbef9_a3f7,
+    0xc671_78f2,
+];
+
+/// The SHA-256 digest of a message, as defined by FIPS 180-4.
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut state: [u32; 8] = [
+        0x6a09_e667,
+        0xbb67_ae85,
+        0x3c6e_f372,
+        0xa54f_f53a,
+        0x510e_527f,
+        0x9b05_688c,
+        0x1f83_d9ab,
+        0x5be0_cd19,
+    ];
+
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut w = [0u32; 64];
+        for (id, word) in block.chunks(4).enumerate() {
+            w[id] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = state;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = h.wrapping_add(s1).wrapping_add(ch).wrapping_add(K[i]).wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+
+        for (word, value) in state.iter_mut().zip(&[a, b, c, d, e, f, g, h]) {
+            *word = word.wrapping_add(*value);
+        }
+    }
+
+    let mut digest = [0; 32];
+    for (bytes, word) in digest.chunks_mut(4).zip(&state) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+
+    digest
+}
@@ -0,0 +1,302 @@
+//! Verification and repair of the primary and backup headers of a GPT table.
+
+use super::{Gpt, PartitionError, PartitionResult};
+use std::{
+    fs::OpenOptions,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+const SIGNATURE: &[u8; 8] = b"EFI PART";
+
+/// The condition of one of the two headers of a GPT table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HeaderHealth {
+    /// The header, and its partition entry array, are intact.
+    Valid,
+    /// A header was not found where it was expected to be.
+    Missing,
+    /// The header was found, but its checksum does not match its contents.
+    HeaderCorrupt,
+    /// The header is intact, but the checksum of its partition entry array does not match.
+    EntriesCorrupt,
+}
+
+impl HeaderHealth {
+    pub fn is_valid(self) -> bool { self == HeaderHealth::Valid }
+}
+
+/// The integrity of a GPT table, as found by `Gpt::verify`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GptHealth {
+    /// The header at the second sector of the device.
+    pub primary:          HeaderHealth,
+    /// The header at the sector which the primary header points to, or the last sector.
+    pub backup:           HeaderHealth,
+    /// The backup header is not stored in the last sector of the device.
+    ///
+    /// This happens when an image is written to a larger disk, or a disk is grown.
+    pub backup_misplaced: bool,
+    /// Pairs of partition numbers whose sectors overlap.
+    pub overlapping:      Vec<(u32, u32)>,
+    /// The number of partition entries of the intact header, which is the highest partition
+    /// number the table may have, or 0 if neither header is intact.
+    pub entries:          u32,
+}
+
+impl GptHealth {
+    /// Both headers are intact, the backup is in place, and no partitions overlap.
+    pub fn is_healthy(&self) -> bool {
+        self.primary.is_valid()
+            && self.backup.is_valid()
+            && !self.backup_misplaced
+            && self.overlapping.is_empty()
+    }
+}
+
+impl Gpt {
+    /// Checks the header and partition entry checksums of both headers, the location of the
+    /// backup header, and whether any partitions overlap.
+    ///
+    /// Unlike `Gpt::open`, this succeeds when either header is damaged.
+    pub fn verify(device: &Path, sector_size: u64) -> PartitionResult<GptHealth> {
+        let mut device =
+            OpenOptions::new().read(true).open(device).map_err(PartitionError::DeviceOpen)?;
+
+        Headers::read(&mut device, sector_size).map(|headers| headers.health())
+    }
+
+    /// Rebuilds a damaged or missing header from the one which is intact.
+    ///
+    /// The backup header is rebuilt at the sector that the primary header points to. Moving
+    /// it to the end of a disk which has grown is left to the caller.
+    pub fn repair(device: &Path, sector_size: u64) -> PartitionResult<GptHealth> {
+        let mut device = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(device)
+            .map_err(PartitionError::DeviceOpen)?;
+
+        let headers = Headers::read(&mut device, sector_size)?;
+        match (headers.primary, headers.backup) {
+            (Ok(ref primary), Err(_)) => {
+                let lba = primary.alternate_lba;
+                if lba >= headers.sectors {
+                    return Err(PartitionError::RepairImpossible);
+                }
+
+                primary
+                    .rebuild(
+                        &mut device,
+                        sector_size,
+                        lba,
+                        1,
+                        lba - primary.entry_sectors(sector_size),
+                    )
+                    .map_err(PartitionError::RepairWrite)?;
+            }
+            (Err(_), Ok(ref backup)) => {
+                backup
+                    .rebuild(&mut device, sector_size, 1, backup.my_lba, 2)
+                    .map_err(PartitionError::RepairWrite)?;
+            }
+            (Err(_), Err(_)) => return Err(PartitionError::RepairImpossible),
+            (Ok(_), Ok(_)) => (),
+        }
+
+        device.sync_all().map_err(PartitionError::RepairWrite)?;
+        Headers::read(&mut device, sector_size).map(|headers| headers.health())
+    }
+}
+
+/// Both headers of a table, as they were read from the device.
+struct Headers {
+    primary: Result<RawHeader, HeaderHealth>,
+    backup:  Result<RawHeader, HeaderHealth>,
+    sectors: u64,
+}
+
+impl Headers {
+    fn read<D: Read + Seek>(device: &mut D, sector_size: u64) -> PartitionResult<Self> {
+        let sectors =
+            device.seek(SeekFrom::End(0)).map_err(PartitionError::DeviceSeek)? / sector_size;
+
+        let primary = RawHeader::read(device, sector_size, 1)?;
+
+        // If the primary header is lost, the backup is expected in the last sector.
+        let backup_lba = match primary {
+            Ok(ref primary) if primary.alternate_lba < sectors => primary.alternate_lba,
+            Ok(_) => return Ok(Headers { primary, backup: Err(HeaderHealth::Missing), sectors }),
+            Err(_) => sectors - 1,
+        };
+
+        let backup = RawHeader::read(device, sector_size, backup_lba)?;
+        Ok(Headers { primary, backup, sectors })
+    }
+
+    fn health(&self) -> GptHealth {
+        let state = |header: &Result<RawHeader, HeaderHealth>| match *header {
+            Ok(_) => HeaderHealth::Valid,
+            Err(health) => health,
+        };
+
+        let valid = self.primary.as_ref().ok().or_else(|| self.backup.as_ref().ok());
+
+        GptHealth {
+            primary:          state(&self.primary),
+            backup:           state(&self.backup),
+            backup_misplaced: match self.backup {
+                Ok(ref backup) => backup.my_lba != self.sectors - 1,
+                Err(_) => false,
+            },
+            overlapping:      valid.map_or_else(Vec::new, RawHeader::overlapping),
+            entries:          valid
+                .map_or(0, |header| (header.entries.len() / header.entry_size) as u32),
+        }
+    }
+}
+
+/// The fields of a GPT header which are needed to verify and rebuild it.
+struct RawHeader {
+    /// The header, as it was read from the device.
+    bytes:         Vec<u8>,
+    /// The partition entry array, as it was read from the device.
+    entries:       Vec<u8>,
+    my_lba:        u64,
+    alternate_lba: u64,
+    entry_size:    usize,
+}
+
+impl RawHeader {
+    /// Reads the header at the given sector, along with its partition entry array.
+    fn read<D: Read + Seek>(
+        device: &mut D,
+        sector_size: u64,
+        lba: u64,
+    ) -> PartitionResult<Result<Self, HeaderHealth>> {
+        let mut bytes = vec![0u8; sector_size as usize];
+        read_at(device, lba * sector_size, &mut bytes)?;
+
+        if &bytes[..8] != SIGNATURE {
+            return Ok(Err(HeaderHealth::Missing));
+        }
+
+        let header_size = le_u32(&bytes[12..]) as usize;
+        if header_size < 92 || header_size > bytes.len() {
+            return Ok(Err(HeaderHealth::HeaderCorrupt));
+        }
+
+        bytes.truncate(header_size);
+        let expected = le_u32(&bytes[16..]);
+        if header_crc(&bytes) != expected {
+            return Ok(Err(HeaderHealth::HeaderCorrupt));
+        }
+
+        let entry_lba = le_u64(&bytes[72..]);
+        let entry_size = le_u32(&bytes[84..]) as usize;
+        let length = le_u32(&bytes[80..]) as usize * entry_size;
+        if entry_size < 128 || length > 1024 * 1024 {
+            return Ok(Err(HeaderHealth::HeaderCorrupt));
+        }
+
+        let mut entries = vec![0u8; length];
+        read_at(device, entry_lba * sector_size, &mut entries)?;
+        if crc32(&entries) != le_u32(&bytes[88..]) {
+            return Ok(Err(HeaderHealth::EntriesCorrupt));
+        }
+
+        Ok(Ok(RawHeader {
+            my_lba: le_u64(&bytes[24..]),
+            alternate_lba: le_u64(&bytes[32..]),
+            bytes,
+            entries,
+            entry_size,
+        }))
+    }
+
+    /// The number of sectors that the partition entry array occupies.
+    fn entry_sectors(&self, sector_size: u64) -> u64 {
+        (self.entries.len() as u64 + sector_size - 1) / sector_size
+    }
+
+    /// Writes a copy of this header, and its entries, to another location.
+    fn rebuild<D: Write + Seek>(
+        &self,
+        device: &mut D,
+        sector_size: u64,
+        my_lba: u64,
+        alternate_lba: u64,
+        entry_lba: u64,
+    ) -> io::Result<()> {
+        let mut bytes = self.bytes.clone();
+        bytes[24..32].copy_from_slice(&my_lba.to_le_bytes());
+        bytes[32..40].copy_from_slice(&alternate_lba.to_le_bytes());
+        bytes[72..80].copy_from_slice(&entry_lba.to_le_bytes());
+        let crc = header_crc(&bytes);
+        bytes[16..20].copy_from_slice(&crc.to_le_bytes());
+
+        device.seek(SeekFrom::Start(entry_lba * sector_size))?;
+        device.write_all(&self.entries)?;
+        device.seek(SeekFrom::Start(my_lba * sector_size))?;
+        device.write_all(&bytes)
+    }
+
+    /// Pairs of used partition entries whose sectors overlap.
+    fn overlapping(&self) -> Vec<(u32, u32)> {
+        let used = self
+            .entries
+            .chunks(self.entry_size)
+            .enumerate()
+            .filter(|(_, entry)| entry[..16].iter().any(|&byte| byte != 0))
+            .map(|(id, entry)| (id as u32 + 1, le_u64(&entry[32..]), le_u64(&entry[40..])))
+            .collect::<Vec<_>>();
+
+        let mut overlapping = Vec::new();
+        for (position, &(a, a_start, a_end)) in used.iter().enumerate() {
+            for &(b, b_start, b_end) in &used[position + 1..] {
+                if a_start <= b_end && b_start <= a_end {
+                    overlapping.push((a, b));
+                }
+            }
+        }
+
+        overlapping
+    }
+}
+
+fn read_at<D: Read + Seek>(device: &mut D, offset: u64, buffer: &mut [u8]) -> PartitionResult<()> {
+    device.seek(SeekFrom::Start(offset)).map_err(PartitionError::DeviceSeek)?;
+    device.read_exact(buffer).map_err(PartitionError::DeviceRead)
+}
+
+/// The checksum of a header, which is calculated with its own checksum field zeroed.
+fn header_crc(header: &[u8]) -> u32 {
+    let mut header = header.to_vec();
+    header[16..20].copy_from_slice(&[0; 4]);
+    crc32(&header)
+}
+
+/// The CRC-32 checksum used by GPT, with the reversed 0xEDB88320 polynomial.
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &byte| {
+        (0..8).fold(crc ^ u32::from(byte), |crc, _| {
+            if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+fn le_u32(bytes: &[u8]) -> u32 {
+    let mut array = [0; 4];
+    array.copy_from_slice(&bytes[..4]);
+    u32::from_le_bytes(array)
+}
+
+fn le_u64(bytes: &[u8]) -> u64 {
+    let mut array = [0; 8];
+    array.copy_from_slice(&bytes[..8]);
+    u64::from_le_bytes(array)
+}
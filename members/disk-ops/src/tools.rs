@@ -0,0 +1,225 @@
+//! Detection of the programs, and the kernel support, which file system operations depend on.
+
+use disk_types::FileSystem;
+use std::{collections::HashSet, env, fs, os::unix::fs::PermissionsExt, path::Path};
+
+const FILE_SYSTEMS: [FileSystem; 15] = [
+    FileSystem::Btrfs,
+    FileSystem::Exfat,
+    FileSystem::Ext2,
+    FileSystem::Ext3,
+    FileSystem::Ext4,
+    FileSystem::F2fs,
+    FileSystem::Iso9660,
+    FileSystem::Luks,
+    FileSystem::Lvm,
+    FileSystem::Ntfs,
+    FileSystem::Squashfs,
+    FileSystem::Swap,
+    FileSystem::Vfat,
+    FileSystem::Xfs,
+    FileSystem::Zfs,
+];
+
+const OPERATIONS: [Operation; 5] =
+    [Operation::Create, Operation::Resize, Operation::Label, Operation::Uuid, Operation::Check];
+
+/// An operation on a file system, which is carried out by external programs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    Create,
+    Resize,
+    Label,
+    Uuid,
+    Check,
+}
+
+/// Why an operation cannot be carried out on this host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum Unavailable {
+    #[error(display = "{} was not found in PATH", _0)]
+    Tool(&'static str),
+    #[error(display = "the kernel does not support mounting {} file systems", _0)]
+    Kernel(FileSystem),
+}
+
+/// Whether each operation can be carried out on a file system on this host.
+///
+/// Operations which `disk_ops` does not support for the file system are never available.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ToolSupport {
+    pub create: bool,
+    pub resize: bool,
+    pub label:  bool,
+    pub uuid:   bool,
+    pub check:  bool,
+    /// Whether the running kernel can mount the file system.
+    pub kernel: bool,
+}
+
+/// The programs, and the file systems supported by the kernel, which were found on a host.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SupportMatrix {
+    tools:  HashSet<&'static str>,
+    kernel: HashSet<FileSystem>,
+}
+
+impl SupportMatrix {
+    /// A support matrix for a host with the given programs and kernel file systems.
+    pub fn new(
+        tools: impl IntoIterator<Item = &'static str>,
+        kernel: impl IntoIterator<Item = FileSystem>,
+    ) -> Self {
+        SupportMatrix { tools: tools.into_iter().collect(), kernel: kernel.into_iter().collect() }
+    }
+
+    /// Whether an operation can be carried out on a file system, or what it lacks.
+    ///
+    /// Operations which `disk_ops` does not support for the file system require nothing, so
+    /// they are rejected by the file system's capabilities, rather than here.
+    pub fn available(&self, fs: FileSystem, op: Operation) -> Result<(), Unavailable> {
+        if let Some(tool) = required_tools(fs, op).into_iter().find(|t| !self.tools.contains(t)) {
+            return Err(Unavailable::Tool(tool));
+        }
+
+        if requires_mount(fs, op) && !self.kernel.contains(&fs) {
+            return Err(Unavailable::Kernel(fs));
+        }
+
+        Ok(())
+    }
+
+    /// The operations which are available for a file system.
+    pub fn support(&self, fs: FileSystem) -> ToolSupport {
+        let supports = |op| !required_tools(fs, op).is_empty() && self.available(fs, op).is_ok();
+
+        ToolSupport {
+            create: supports(Operation::Create),
+            resize: supports(Operation::Resize),
+            label:  supports(Operation::Label),
+            uuid:   supports(Operation::Uuid),
+            check:  supports(Operation::Check),
+            kernel: self.kernel.contains(&fs),
+        }
+    }
+
+    /// The support of every file system.
+    pub fn rows<'a>(&'a self) -> impl Iterator<Item = (FileSystem, ToolSupport)> + 'a {
+        FILE_SYSTEMS.iter().map(move |&fs| (fs, self.support(fs)))
+    }
+}
+
+/// Finds which of the programs used by `disk_ops` are in PATH, and which file systems the
+/// running kernel supports, either built in or as a module which has yet to be loaded.
+pub fn probe() -> SupportMatrix {
+    let paths =
+        env::var_os("PATH").map_or_else(Vec::new, |paths| env::split_paths(&paths).collect());
+
+    let tools = FILE_SYSTEMS
+        .iter()
+        .flat_map(|&fs| OPERATIONS.iter().flat_map(move |&op| required_tools(fs, op)))
+        .filter(|tool| paths.iter().any(|path| is_executable(&path.join(tool))))
+        .collect::<Vec<_>>();
+
+    let registered = fs::read_to_string("/proc/filesystems").unwrap_or_default();
+    let registered = registered
+        .lines()
+        .filter_map(|line| line.split_whitespace().last())
+        .collect::<HashSet<&str>>();
+
+    let modules = fs::read_to_string("/proc/sys/kernel/osrelease")
+        .ok()
+        .map(|release| Path::new("/lib/modules").join(release.trim()).join("kernel/fs"));
+
+    let kernel = FILE_SYSTEMS.iter().cloned().filter(|&fs| {
+        let (names, module_dirs) = kernel_names(fs);
+        names.iter().any(|name| registered.contains(name))
+            || modules
+                .as_ref()
+                .map_or(false, |modules| module_dirs.iter().any(|dir| modules.join(dir).is_dir()))
+    });
+
+    SupportMatrix::new(tools, kernel)
+}
+
+/// The programs which `disk_ops` runs to carry out an operation on a file system.
+pub fn required_tools(fs: FileSystem, op: Operation) -> Vec<&'static str> {
+    let ext = match fs {
+        FileSystem::Ext2 | FileSystem::Ext3 | FileSystem::Ext4 => true,
+        _ => false,
+    };
+
+    let tools: &[&'static str] = match op {
+        Operation::Create => return fs.capabilities().tool.into_iter().collect(),
+        Operation::Check => return fs.check_tool().into_iter().collect(),
+        Operation::Resize if ext => &["e2fsck", "resize2fs"],
+        Operation::Resize => match fs {
+            FileSystem::Btrfs => &["btrfs", "mount", "umount"],
+            FileSystem::F2fs => &["resize.f2fs"],
+            FileSystem::Ntfs => &["ntfsresize"],
+            FileSystem::Vfat => &["fatresize"],
+            FileSystem::Xfs => &["xfs_growfs", "mount", "umount"],
+            _ => &[],
+        },
+        Operation::Label if ext => &["e2label"],
+        Operation::Label => match fs {
+            FileSystem::Btrfs => &["btrfs"],
+            FileSystem::Exfat => &["exfatlabel"],
+            FileSystem::Ntfs => &["ntfslabel"],
+            FileSystem::Swap => &["swaplabel"],
+            FileSystem::Vfat => &["fatlabel"],
+            FileSystem::Xfs => &["xfs_admin"],
+            _ => &[],
+        },
+        Operation::Uuid if ext => &["tune2fs"],
+        Operation::Uuid => match fs {
+            FileSystem::Btrfs => &["btrfstune"],
+            FileSystem::Exfat => &["tune.exfat"],
+            FileSystem::Luks => &["cryptsetup"],
+            FileSystem::Lvm => &["pvchange"],
+            FileSystem::Ntfs => &["ntfslabel"],
+            FileSystem::Swap => &["swaplabel"],
+            FileSystem::Vfat => &["fatlabel"],
+            FileSystem::Xfs => &["xfs_admin"],
+            _ => &[],
+        },
+    };
+
+    tools.to_vec()
+}
+
+/// File systems which are mounted in order to be resized.
+fn requires_mount(fs: FileSystem, op: Operation) -> bool {
+    match (fs, op) {
+        (FileSystem::Btrfs, Operation::Resize) | (FileSystem::Xfs, Operation::Resize) => true,
+        _ => false,
+    }
+}
+
+/// The names which the kernel registers a file system as, in `/proc/filesystems`, and the
+/// directories of the modules which provide it.
+fn kernel_names(fs: FileSystem) -> (&'static [&'static str], &'static [&'static str]) {
+    match fs {
+        FileSystem::Btrfs => (&["btrfs"], &["btrfs"]),
+        FileSystem::Exfat => (&["exfat"], &["exfat"]),
+        // The ext4 driver also registers itself as ext2 and ext3.
+        FileSystem::Ext2 => (&["ext2"], &["ext2", "ext4"]),
+        FileSystem::Ext3 => (&["ext3"], &["ext3", "ext4"]),
+        FileSystem::Ext4 => (&["ext4"], &["ext4"]),
+        FileSystem::F2fs => (&["f2fs"], &["f2fs"]),
+        FileSystem::Iso9660 => (&["iso9660"], &["isofs"]),
+        FileSystem::Ntfs => (&["ntfs", "ntfs3"], &["ntfs", "ntfs3"]),
+        FileSystem::Squashfs => (&["squashfs"], &["squashfs"]),
+        FileSystem::Vfat => (&["vfat"], &["fat"]),
+        FileSystem::Xfs => (&["xfs"], &["xfs"]),
+        // ZFS is built out of tree, so its module is not found among the others.
+        FileSystem::Zfs => (&["zfs"], &[]),
+        FileSystem::Luks | FileSystem::Lvm | FileSystem::Swap => (&[], &[]),
+    }
+}
+
+fn is_executable(path: &Path) -> bool {
+    fs::metadata(path)
+        .ok()
+        .map_or(false, |meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
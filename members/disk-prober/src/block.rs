@@ -1,6 +1,6 @@
 use super::*;
 use blkid::*;
-use disk_types::PartitionTable;
+use disk_types::{PartitionAttributes, PartitionKind, PartitionTable, PartitionType};
 use std::{
     fs,
     path::{Path, PathBuf},
@@ -93,16 +93,22 @@ impl<'a> Probed<'a> {
         let physical_sector_size;
         let logical_sector_size;
         let alignment;
+        let minimum_io_size;
+        let optimal_io_size;
 
         {
             let topology = self.probe.get_topology().map_err(BlockProbeError::Topology)?;
             alignment = topology.get_alignment_offset();
+            minimum_io_size = topology.get_minimum_io_size();
+            optimal_io_size = topology.get_optimal_io_size();
             physical_sector_size = topology.get_physical_sector_size();
             logical_sector_size = topology.get_logical_sector_size();
         }
 
         let fstype = self.probe.lookup_value("TYPE").ok().map(Box::from);
         let uuid = self.probe.lookup_value("UUID").ok().map(Box::from);
+        let uuid_sub = self.probe.lookup_value("UUID_SUB").ok().map(Box::from);
+        let label = self.probe.lookup_value("LABEL").ok().map(Box::from);
         let mut table = None;
         let mut partitions = Vec::new();
 
@@ -133,16 +139,30 @@ impl<'a> Probed<'a> {
                     let probe = Probe::new_from(&path).map_err(BlockProbeError::PartitionNew)?;
 
                     probe.probe_full().map_err(BlockProbeError::PartitionProbe)?;
+
+                    let variant = if partition.is_extended() {
+                        PartitionType::Extended
+                    } else if partition.is_logical() {
+                        PartitionType::Logical
+                    } else {
+                        PartitionType::Primary
+                    };
+
                     partitions.push(ProbePartInfo {
-                        device:    Box::from(device),
-                        no:        partno,
-                        path:      Box::from(path),
-                        sectors:   partition.get_size(),
-                        offset:    partition.get_start(),
-                        partlabel: partition.get_name().map(Box::from),
-                        partuuid:  partition.get_uuid().map(Box::from),
-                        uuid:      probe.lookup_value("UUID").ok().map(Box::from),
-                        fstype:    probe.lookup_value("TYPE").ok().map(Box::from),
+                        device:     Box::from(device),
+                        no:         partno,
+                        path:       Box::from(path),
+                        sectors:    partition.get_size(),
+                        offset:     partition.get_start(),
+                        partlabel:  partition.get_name().map(Box::from),
+                        partuuid:   partition.get_uuid().map(Box::from),
+                        uuid:       probe.lookup_value("UUID").ok().map(Box::from),
+                        uuid_sub:   probe.lookup_value("UUID_SUB").ok().map(Box::from),
+                        label:      probe.lookup_value("LABEL").ok().map(Box::from),
+                        fstype:     probe.lookup_value("TYPE").ok().map(Box::from),
+                        attributes: attributes(table, partition.get_flags()),
+                        kind:       kind(table, &partition),
+                        variant,
                     });
                 }
             }
@@ -176,21 +196,44 @@ impl<'a> Probed<'a> {
             size,
             sectors,
             logical_sector_size,
+            minimum_io_size,
+            optimal_io_size,
             physical_sector_size,
             fstype,
             uuid,
+            uuid_sub,
+            label,
             partitions,
             table,
         })
     }
 }
 
+/// MBR tables report the boot indicator in place of the attribute bits.
+fn attributes(table: Option<PartitionTable>, flags: u64) -> PartitionAttributes {
+    match table {
+        Some(PartitionTable::Mbr) if flags & 0x80 != 0 => PartitionAttributes::LEGACY_BOOTABLE,
+        Some(PartitionTable::Mbr) => PartitionAttributes::empty(),
+        _ => PartitionAttributes::from_bits_truncate(flags),
+    }
+}
+
+/// MBR tables identify partitions by their system ID, and GPT tables by their type GUID.
+fn kind(table: Option<PartitionTable>, partition: &Partition) -> Option<PartitionKind> {
+    match table {
+        Some(PartitionTable::Mbr) => PartitionKind::from_mbr_id(partition.get_type() as u8),
+        _ => partition.get_type_string().and_then(PartitionKind::from_guid),
+    }
+}
+
 pub struct ProbeInfo<'a, 'b> {
     pub alignment:            u64,
     pub device:               &'a str,
     pub devno_major:          u16,
     pub devno_minor:          u16,
     pub logical_sector_size:  u64,
+    pub minimum_io_size:      u64,
+    pub optimal_io_size:      u64,
     pub partitions:           Vec<ProbePartInfo>,
     pub path:                 &'b Path,
     pub physical_sector_size: u64,
@@ -198,20 +241,27 @@ pub struct ProbeInfo<'a, 'b> {
     pub size:                 u64,
     pub fstype:               Option<Box<str>>,
     pub uuid:                 Option<Box<str>>,
+    pub uuid_sub:             Option<Box<str>>,
+    pub label:                Option<Box<str>>,
     pub variant:              DeviceVariant,
     pub table:                Option<PartitionTable>,
 }
 
 pub struct ProbePartInfo {
-    pub device:    Box<str>,
-    pub no:        u32,
-    pub offset:    u64,
-    pub partlabel: Option<Box<str>>,
-    pub partuuid:  Option<Box<str>>,
-    pub path:      Box<Path>,
-    pub sectors:   u64,
-    pub fstype:    Option<Box<str>>,
-    pub uuid:      Option<Box<str>>,
+    pub device:     Box<str>,
+    pub no:         u32,
+    pub offset:     u64,
+    pub partlabel:  Option<Box<str>>,
+    pub partuuid:   Option<Box<str>>,
+    pub path:       Box<Path>,
+    pub sectors:    u64,
+    pub fstype:     Option<Box<str>>,
+    pub uuid:       Option<Box<str>>,
+    pub uuid_sub:   Option<Box<str>>,
+    pub label:      Option<Box<str>>,
+    pub variant:    PartitionType,
+    pub attributes: PartitionAttributes,
+    pub kind:       Option<PartitionKind>,
 }
 
 #[derive(Debug)]
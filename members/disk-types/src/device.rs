@@ -1,4 +1,4 @@
-use crate::sector::SectorExt;
+use crate::{sector::SectorExt, size::Size};
 use std::path::Path;
 
 pub trait DeviceExt {
@@ -7,6 +7,11 @@ pub trait DeviceExt {
     fn sectors(&self) -> u64;
     fn logical_sector_size(&self) -> u64;
     fn physical_sector_size(&self) -> u64;
+
+    /// The size of the device, in bytes.
+    fn size(&self) -> Size {
+        Size::from_bytes(self.sectors().saturating_mul(self.logical_sector_size()))
+    }
 }
 
 #[derive(Debug, Clone)]
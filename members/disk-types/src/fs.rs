@@ -2,7 +2,7 @@ use std::{fmt, str::FromStr};
 // use sys_mount::FilesystemType as MountFS;
 
 /// Describes a file system format, such as ext4 or fat32.
-#[derive(Debug, PartialEq, Copy, Clone, Hash)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash)]
 pub enum FileSystem {
     Btrfs,
     Exfat,
@@ -28,7 +28,155 @@ pub enum PartitionSizeError {
     TooLarge(u64, u64),
 }
 
+bitflags! {
+    /// Whether a file system may be resized while it is mounted, or while it is not.
+    pub struct ResizeSupport: u8 {
+        const OFFLINE = 1;
+        const ONLINE = 1 << 1;
+    }
+}
+
+/// What can be done with a file system, as it is created and resized by `disk_ops`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileSystemCapabilities {
+    /// The smallest size, in bytes, that the file system may have.
+    pub min_size:      u64,
+    /// The largest size, in bytes, that the file system may have.
+    pub max_size:      u64,
+    /// How the file system may be grown to fill a larger partition.
+    pub grow:          ResizeSupport,
+    /// How the file system may be shrunk to fit a smaller partition.
+    pub shrink:        ResizeSupport,
+    /// The longest label that the file system may have, or 0 if it cannot be labeled.
+    pub max_label_len: usize,
+    /// Whether the UUID of an existing file system may be changed.
+    pub uuid_settable: bool,
+    /// Whether the file system can be created, rather than only detected.
+    pub creatable:     bool,
+    /// The external program which creates the file system.
+    pub tool:          Option<&'static str>,
+}
+
+const KIB: u64 = 1024;
+const MIB: u64 = KIB * 1024;
+const GIB: u64 = MIB * 1024;
+const TIB: u64 = GIB * 1024;
+const PIB: u64 = TIB * 1024;
+
+const FAT32_MIN: u64 = 33 * MIB;
+const FAT32_MAX: u64 = 2 * TIB;
+const EXT4_MAX: u64 = 16 * TIB;
+const BTRFS_MIN: u64 = 250 * MIB;
+
 impl FileSystem {
+    /// What can be done with this file system, and the limits of its size and label.
+    ///
+    /// `Vfat` file systems are always created as FAT32, so they are limited to the sizes of
+    /// FAT32. Labels are measured in bytes, or in UTF-16 code units on exFAT, F2FS and NTFS.
+    pub fn capabilities(self) -> FileSystemCapabilities {
+        let created = FileSystemCapabilities {
+            min_size:      MIB,
+            max_size:      ::std::u64::MAX,
+            grow:          ResizeSupport::empty(),
+            shrink:        ResizeSupport::empty(),
+            max_label_len: 0,
+            uuid_settable: true,
+            creatable:     true,
+            tool:          None,
+        };
+
+        let detected = FileSystemCapabilities {
+            min_size: 0,
+            uuid_settable: false,
+            creatable: false,
+            ..created
+        };
+
+        match self {
+            FileSystem::Btrfs => FileSystemCapabilities {
+                min_size: BTRFS_MIN,
+                grow: ResizeSupport::ONLINE,
+                shrink: ResizeSupport::ONLINE,
+                max_label_len: 255,
+                tool: Some("mkfs.btrfs"),
+                ..created
+            },
+            FileSystem::Exfat => FileSystemCapabilities {
+                max_size: 128 * PIB,
+                max_label_len: 15,
+                tool: Some("mkfs.exfat"),
+                ..created
+            },
+            FileSystem::Ext2 => FileSystemCapabilities {
+                max_size: 16 * TIB,
+                grow: ResizeSupport::all(),
+                shrink: ResizeSupport::OFFLINE,
+                max_label_len: 16,
+                tool: Some("mkfs.ext2"),
+                ..created
+            },
+            FileSystem::Ext3 | FileSystem::Ext4 => FileSystemCapabilities {
+                // Smaller file systems do not have room for a journal.
+                min_size: 4 * MIB,
+                max_size: EXT4_MAX,
+                grow: ResizeSupport::all(),
+                shrink: ResizeSupport::OFFLINE,
+                max_label_len: 16,
+                tool: Some(if self == FileSystem::Ext3 { "mkfs.ext3" } else { "mkfs.ext4" }),
+                ..created
+            },
+            FileSystem::F2fs => FileSystemCapabilities {
+                min_size: 38 * MIB,
+                max_size: 16 * TIB,
+                grow: ResizeSupport::OFFLINE,
+                max_label_len: 512,
+                uuid_settable: false,
+                tool: Some("mkfs.f2fs"),
+                ..created
+            },
+            FileSystem::Iso9660 => FileSystemCapabilities { max_label_len: 32, ..detected },
+            FileSystem::Luks => {
+                FileSystemCapabilities { min_size: 16 * MIB, tool: Some("cryptsetup"), ..created }
+            }
+            FileSystem::Lvm => {
+                FileSystemCapabilities { min_size: 2 * MIB, tool: Some("pvcreate"), ..created }
+            }
+            FileSystem::Ntfs => FileSystemCapabilities {
+                max_size: 256 * TIB,
+                grow: ResizeSupport::OFFLINE,
+                shrink: ResizeSupport::OFFLINE,
+                max_label_len: 128,
+                tool: Some("mkfs.ntfs"),
+                ..created
+            },
+            FileSystem::Squashfs | FileSystem::Zfs => detected,
+            FileSystem::Swap => FileSystemCapabilities {
+                min_size: 40 * KIB,
+                max_label_len: 16,
+                tool: Some("mkswap"),
+                ..created
+            },
+            FileSystem::Vfat => FileSystemCapabilities {
+                min_size: FAT32_MIN,
+                max_size: FAT32_MAX,
+                grow: ResizeSupport::OFFLINE,
+                shrink: ResizeSupport::OFFLINE,
+                max_label_len: 11,
+                tool: Some("mkfs.fat"),
+                ..created
+            },
+            // XFS cannot be shrunk at all.
+            FileSystem::Xfs => FileSystemCapabilities {
+                min_size: 300 * MIB,
+                max_size: 8 * 1024 * PIB,
+                grow: ResizeSupport::ONLINE,
+                max_label_len: 12,
+                tool: Some("mkfs.xfs"),
+                ..created
+            },
+        }
+    }
+
     /// Check if a given size, in bytes, is valid for this file system.
     ///
     /// # Possible Values
@@ -36,26 +184,206 @@ impl FileSystem {
     /// - `Err(PartitionSizeError::TooSmall)` for a partition that is too small.
     /// - `Err(PartitionSizeError::TooLarge)` for a partition that is too large.
     pub fn validate_size(self, size: u64) -> Result<(), PartitionSizeError> {
-        const MIB: u64 = 1024 * 1024;
-        const GIB: u64 = MIB * 1024;
-        const TIB: u64 = GIB * 1024;
+        let caps = self.capabilities();
+        if size < caps.min_size {
+            Err(PartitionSizeError::TooSmall(size, caps.min_size))
+        } else if size > caps.max_size {
+            Err(PartitionSizeError::TooLarge(size, caps.max_size))
+        } else {
+            Ok(())
+        }
+    }
 
-        const FAT16_MIN: u64 = 16 * MIB;
-        const FAT16_MAX: u64 = (4096 - 1) * MIB;
-        const FAT32_MIN: u64 = 33 * MIB;
-        const FAT32_MAX: u64 = 2 * TIB;
-        const EXT4_MAX: u64 = 16 * TIB;
-        const BTRFS_MIN: u64 = 250 * MIB;
+    /// The length of a label, in the units that the file system limits its labels by.
+    pub fn label_len(self, label: &str) -> usize {
+        match self {
+            FileSystem::Exfat | FileSystem::F2fs | FileSystem::Ntfs => label.encode_utf16().count(),
+            _ => label.len(),
+        }
+    }
 
+    /// Whether the file system can be grown to fill a larger partition.
+    pub fn supports_growing(self) -> bool { !self.capabilities().grow.is_empty() }
+
+    /// Whether the file system can be shrunk to fit a smaller partition.
+    pub fn supports_shrinking(self) -> bool { !self.capabilities().shrink.is_empty() }
+
+    /// The external program which checks and repairs the file system, if it can be checked.
+    pub fn check_tool(self) -> Option<&'static str> {
         match self {
-            FileSystem::Btrfs if size < BTRFS_MIN => {
-                Err(PartitionSizeError::TooSmall(size, BTRFS_MIN))
+            FileSystem::Btrfs => Some("btrfs"),
+            FileSystem::Ext2 | FileSystem::Ext3 | FileSystem::Ext4 => Some("e2fsck"),
+            FileSystem::F2fs => Some("fsck.f2fs"),
+            FileSystem::Ntfs => Some("ntfsfix"),
+            FileSystem::Vfat => Some("fsck.fat"),
+            FileSystem::Xfs => Some("xfs_repair"),
+            _ => None,
+        }
+    }
+}
+
+/// How thoroughly a file system is to be checked, and whether it may be repaired.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash)]
+pub enum CheckMode {
+    /// Reports errors without making any changes.
+    DryRun,
+    /// Repairs errors which can be safely repaired without user intervention.
+    Repair,
+    /// As `Repair`, but the file system is checked even if it is marked clean.
+    ///
+    /// Errors which cannot be safely repaired are left for the user, so these fail the check.
+    ForceRepair,
+    /// Checks the file system even if it is marked clean, and repairs every error found.
+    ///
+    /// This may discard data which the file system cannot recover, such as an XFS log. Btrfs
+    /// file systems are only checked.
+    Force,
+    /// As `Force`, but btrfs file systems are also repaired, with `btrfs check --repair`.
+    ///
+    /// The btrfs developers warn that its repairs may cause further damage, so this should
+    /// only be chosen when the user has explicitly asked for it.
+    ForceUnsafe,
+}
+
+/// The outcome of checking a file system.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash)]
+pub enum CheckVerdict {
+    /// No errors were found.
+    Clean,
+    /// Errors were found, and all of them were repaired.
+    Repaired,
+    /// No errors remain, but the tool does not report whether any were found and repaired.
+    CleanOrRepaired,
+    /// Errors were found which were not repaired.
+    ErrorsRemaining,
+}
+
+/// The profile which btrfs stores data or metadata with.
+///
+/// File systems are created on a single device, so profiles which span devices are not offered.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash)]
+pub enum BtrfsProfile {
+    /// One copy of each block.
+    Single,
+    /// Two copies of each block, on the same device.
+    Dup,
+}
+
+impl From<BtrfsProfile> for &'static str {
+    fn from(profile: BtrfsProfile) -> Self {
+        match profile {
+            BtrfsProfile::Single => "single",
+            BtrfsProfile::Dup => "dup",
+        }
+    }
+}
+
+/// Options for creating a file system, which are given to its mkfs tool.
+///
+/// Options which are not set are left to the defaults of the tool.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct FormatOptions {
+    /// The label of the new file system.
+    pub label:            Option<Box<str>>,
+    /// The block size, in bytes, of ext2/3/4 and XFS file systems.
+    pub block_size:       Option<u32>,
+    /// The number of bytes for each inode of ext2/3/4 file systems.
+    pub inode_ratio:      Option<u32>,
+    /// The cluster size, in bytes, of exFAT, NTFS, and FAT file systems.
+    pub cluster_size:     Option<u32>,
+    /// The profile of data on btrfs file systems.
+    pub data_profile:     Option<BtrfsProfile>,
+    /// The profile of metadata on btrfs file systems.
+    pub metadata_profile: Option<BtrfsProfile>,
+}
+
+/// Indicates that format options cannot be used to create a file system.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FormatOptionsError {
+    /// The named option is not supported by the file system.
+    Unsupported(FileSystem, &'static str),
+    /// The label is longer than the maximum length of the file system's labels.
+    LabelTooLong(usize, usize),
+    /// The block size is not a power of two within the range the file system supports.
+    BlockSizeInvalid(u32),
+    /// The inode ratio is out of range, or is less than the block size.
+    InodeRatioInvalid(u32),
+    /// The cluster size is not a power of two within the range the file system supports.
+    ClusterSizeInvalid(u32),
+}
+
+impl FormatOptions {
+    /// Checks that every option is supported by the file system, and that the options may be
+    /// combined with each other.
+    pub fn validate(&self, fs: FileSystem) -> Result<(), FormatOptionsError> {
+        let is_ext = match fs {
+            FileSystem::Ext2 | FileSystem::Ext3 | FileSystem::Ext4 => true,
+            _ => false,
+        };
+
+        let within = |value: u32, min: u32, max: u32| {
+            value.is_power_of_two() && value >= min && value <= max
+        };
+
+        let unsupported = |option| Err(FormatOptionsError::Unsupported(fs, option));
+
+        if let Some(ref label) = self.label {
+            let max = fs.capabilities().max_label_len;
+            if max == 0 {
+                return unsupported("label");
+            }
+
+            let length = fs.label_len(label);
+            if length > max {
+                return Err(FormatOptionsError::LabelTooLong(length, max));
+            }
+        }
+
+        if let Some(size) = self.block_size {
+            let valid = match fs {
+                _ if is_ext => within(size, 1024, 65536),
+                FileSystem::Xfs => within(size, 512, 65536),
+                _ => return unsupported("block size"),
+            };
+
+            if !valid {
+                return Err(FormatOptionsError::BlockSizeInvalid(size));
             }
-            FileSystem::Ext4 if size > EXT4_MAX => {
-                Err(PartitionSizeError::TooLarge(size, EXT4_MAX))
+        }
+
+        if let Some(ratio) = self.inode_ratio {
+            if !is_ext {
+                return unsupported("inode ratio");
+            }
+
+            // Every block must be able to have an inode of its own.
+            let min = self.block_size.unwrap_or(1024);
+            if ratio < min || ratio > 64 * 1024 * 1024 {
+                return Err(FormatOptionsError::InodeRatioInvalid(ratio));
             }
-            _ => Ok(()),
         }
+
+        if let Some(size) = self.cluster_size {
+            let valid = match fs {
+                FileSystem::Vfat | FileSystem::Ntfs => within(size, 512, 65536),
+                FileSystem::Exfat => within(size, 512, 32 * 1024 * 1024),
+                _ => return unsupported("cluster size"),
+            };
+
+            if !valid {
+                return Err(FormatOptionsError::ClusterSizeInvalid(size));
+            }
+        }
+
+        if fs != FileSystem::Btrfs {
+            if self.data_profile.is_some() {
+                return unsupported("data profile");
+            } else if self.metadata_profile.is_some() {
+                return unsupported("metadata profile");
+            }
+        }
+
+        Ok(())
     }
 }
 
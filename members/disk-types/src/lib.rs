@@ -1,12 +1,17 @@
 #[macro_use]
+extern crate bitflags;
+#[macro_use]
 extern crate shrinkwraprs;
 
 pub mod device;
 pub mod fs;
 pub mod luks;
 pub mod lvm;
+pub mod partition_kind;
 pub mod partitions;
 pub mod sector;
+pub mod size;
+pub mod topology;
 
 pub mod disk {
     use crate::partitions::PartitionTable;
@@ -17,4 +22,7 @@ pub mod disk {
     }
 }
 
-pub use crate::{device::*, disk::*, fs::*, luks::*, lvm::*, partitions::*, sector::*};
+pub use crate::{
+    device::*, disk::*, fs::*, luks::*, lvm::*, partition_kind::*, partitions::*, sector::*,
+    size::*, topology::*,
+};
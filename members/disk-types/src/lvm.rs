@@ -1,4 +1,4 @@
-use crate::{device::DeviceExt, sector::SectorExt};
+use crate::{device::DeviceExt, sector::SectorExt, size::Size};
 use std::path::Path;
 
 #[derive(Debug, Clone)]
@@ -15,6 +15,10 @@ pub struct LvmPv {
     pub size_bytes: u64,
 }
 
+impl LvmPv {
+    pub fn size(&self) -> Size { Size::from_bytes(self.size_bytes) }
+}
+
 #[derive(Debug, Clone)]
 pub struct LvmVg {
     pub name:         Box<str>,
@@ -30,6 +34,8 @@ impl LvmVg {
     }
 
     pub fn sectors_free(&self) -> u64 { self.extent_size_as_512_byte_sectors() * self.extents_free }
+
+    pub fn size_free(&self) -> Size { Size::from_bytes(self.extent_size * self.extents_free) }
 }
 
 impl DeviceExt for LvmVg {
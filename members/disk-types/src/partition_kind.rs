@@ -0,0 +1,202 @@
+use crate::fs::FileSystem;
+
+/// CPU architectures which have their own GPT partition types for root partitions.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Architecture {
+    X86,
+    X86_64,
+    Arm,
+    Aarch64,
+    PowerPc64Le,
+    RiscV64,
+}
+
+impl Architecture {
+    /// The architecture that this library was compiled for, if it is a known architecture.
+    pub fn native() -> Option<Self> {
+        if cfg!(target_arch = "x86_64") {
+            Some(Architecture::X86_64)
+        } else if cfg!(target_arch = "x86") {
+            Some(Architecture::X86)
+        } else if cfg!(target_arch = "aarch64") {
+            Some(Architecture::Aarch64)
+        } else if cfg!(target_arch = "arm") {
+            Some(Architecture::Arm)
+        } else if cfg!(all(target_arch = "powerpc64", target_endian = "little")) {
+            Some(Architecture::PowerPc64Le)
+        } else if cfg!(target_arch = "riscv64") {
+            Some(Architecture::RiscV64)
+        } else {
+            None
+        }
+    }
+}
+
+/// Well-known partition types, which are identified by a type GUID on GPT tables, and by a
+/// system ID on MBR tables.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum PartitionKind {
+    /// An EFI system partition (ESP).
+    EfiSystem,
+    /// Contains the second stage of GRUB when booting from a GPT disk in BIOS mode.
+    BiosBoot,
+    /// A generic Linux file system.
+    LinuxData,
+    /// The root file system of a Linux installation, for a given architecture.
+    LinuxRoot(Architecture),
+    /// Contains `/home` of a Linux installation.
+    LinuxHome,
+    /// Contains `/srv` of a Linux installation.
+    LinuxSrv,
+    /// Contains `/var` of a Linux installation.
+    LinuxVar,
+    /// Contains `/var/tmp` of a Linux installation.
+    LinuxTmp,
+    /// A Linux swap partition.
+    LinuxSwap,
+    /// A LVM physical volume.
+    LinuxLvm,
+    /// A LUKS-encrypted partition.
+    LinuxLuks,
+    /// A member of a Linux software RAID array.
+    LinuxRaid,
+    /// The Microsoft reserved partition (MSR) that Windows creates on GPT disks.
+    MicrosoftReserved,
+    /// A Windows data partition, such as NTFS, exFAT, and FAT file systems.
+    MicrosoftBasicData,
+    /// The Windows recovery environment.
+    WindowsRecovery,
+    /// An Apple HFS+ partition.
+    AppleHfs,
+    /// An Apple APFS container.
+    AppleApfs,
+}
+
+const KINDS: &[PartitionKind] = &[
+    PartitionKind::EfiSystem,
+    PartitionKind::BiosBoot,
+    PartitionKind::LinuxData,
+    PartitionKind::LinuxRoot(Architecture::X86),
+    PartitionKind::LinuxRoot(Architecture::X86_64),
+    PartitionKind::LinuxRoot(Architecture::Arm),
+    PartitionKind::LinuxRoot(Architecture::Aarch64),
+    PartitionKind::LinuxRoot(Architecture::PowerPc64Le),
+    PartitionKind::LinuxRoot(Architecture::RiscV64),
+    PartitionKind::LinuxHome,
+    PartitionKind::LinuxSrv,
+    PartitionKind::LinuxVar,
+    PartitionKind::LinuxTmp,
+    PartitionKind::LinuxSwap,
+    PartitionKind::LinuxLvm,
+    PartitionKind::LinuxLuks,
+    PartitionKind::LinuxRaid,
+    PartitionKind::MicrosoftReserved,
+    PartitionKind::MicrosoftBasicData,
+    PartitionKind::WindowsRecovery,
+    PartitionKind::AppleHfs,
+    PartitionKind::AppleApfs,
+];
+
+impl PartitionKind {
+    /// Finds the partition type that is associated with a GPT partition type GUID.
+    pub fn from_guid(guid: &str) -> Option<Self> {
+        KINDS.iter().cloned().find(|kind| kind.guid().eq_ignore_ascii_case(guid))
+    }
+
+    /// Finds the partition type that is associated with a MBR system ID.
+    ///
+    /// System IDs which are shared by multiple types resolve to the most generic type.
+    pub fn from_mbr_id(id: u8) -> Option<Self> {
+        let kind = match id {
+            0x07 | 0x0B | 0x0C | 0x0E => PartitionKind::MicrosoftBasicData,
+            0x27 => PartitionKind::WindowsRecovery,
+            0x82 => PartitionKind::LinuxSwap,
+            0x83 => PartitionKind::LinuxData,
+            0x8E => PartitionKind::LinuxLvm,
+            0xAF => PartitionKind::AppleHfs,
+            0xE8 => PartitionKind::LinuxLuks,
+            0xEF => PartitionKind::EfiSystem,
+            0xFD => PartitionKind::LinuxRaid,
+            _ => return None,
+        };
+
+        Some(kind)
+    }
+
+    /// The partition type that best describes a partition formatted with this file system.
+    ///
+    /// Partitions without a file system, or with a file system that lacks a dedicated type, are
+    /// given the generic Linux data type.
+    pub fn from_filesystem(fs: Option<FileSystem>) -> Self {
+        match fs {
+            Some(FileSystem::Swap) => PartitionKind::LinuxSwap,
+            Some(FileSystem::Lvm) => PartitionKind::LinuxLvm,
+            Some(FileSystem::Luks) => PartitionKind::LinuxLuks,
+            Some(FileSystem::Exfat) | Some(FileSystem::Ntfs) | Some(FileSystem::Vfat) => {
+                PartitionKind::MicrosoftBasicData
+            }
+            _ => PartitionKind::LinuxData,
+        }
+    }
+
+    /// The type GUID used to identify this partition type on a GPT table.
+    pub fn guid(self) -> &'static str {
+        match self {
+            PartitionKind::EfiSystem => "C12A7328-F81F-11D2-BA4B-00A0C93EC93B",
+            PartitionKind::BiosBoot => "21686148-6449-6E6F-744E-656564454649",
+            PartitionKind::LinuxData => "0FC63DAF-8483-4772-8E79-3D69D8477DE4",
+            PartitionKind::LinuxRoot(arch) => match arch {
+                Architecture::X86 => "44479540-F297-41B2-9AF7-D131D5F0458A",
+                Architecture::X86_64 => "4F68BCE3-E8CD-4DB1-96E7-FBCAF984B709",
+                Architecture::Arm => "69DAD710-2CE4-4E3C-B16C-21A1D49ABED3",
+                Architecture::Aarch64 => "B921B045-1DF0-41C3-AF44-4C6F280D3FAE",
+                Architecture::PowerPc64Le => "C31C45E6-3F39-412E-80FB-4809C4980599",
+                Architecture::RiscV64 => "72EC70A6-CF74-40E6-BD49-4BDA08E8F224",
+            },
+            PartitionKind::LinuxHome => "933AC7E1-2EB4-4F13-B844-0E14E2AEF915",
+            PartitionKind::LinuxSrv => "3B8F8425-20E0-4F3B-907F-1A25A76F98E8",
+            PartitionKind::LinuxVar => "4D21B016-B534-45C2-A9FB-5C16E091FD2D",
+            PartitionKind::LinuxTmp => "7EC6F557-3BC5-4ACA-B293-16EF5DF639D1",
+            PartitionKind::LinuxSwap => "0657FD6D-A4AB-43C4-84E5-0933C84B4F4F",
+            PartitionKind::LinuxLvm => "E6D6D379-F507-44C2-A23C-238F2A3DF928",
+            PartitionKind::LinuxLuks => "CA7D7CCB-63ED-4C53-861C-1742536059CC",
+            PartitionKind::LinuxRaid => "A19D880F-05FC-4D3B-A006-743F0F84911E",
+            PartitionKind::MicrosoftReserved => "E3C9E316-0B5C-4DB8-817D-F92DF00215AE",
+            PartitionKind::MicrosoftBasicData => "EBD0A0A2-B9E5-4433-87C0-68B6B72699C7",
+            PartitionKind::WindowsRecovery => "DE94BBA4-06D1-4D40-A16A-BFD50179D6AC",
+            PartitionKind::AppleHfs => "48465300-0000-11AA-AA11-00306543ECAC",
+            PartitionKind::AppleApfs => "7C3457EF-0000-11AA-AA11-00306543ECAC",
+        }
+    }
+
+    /// The system ID used to identify this partition type on a MBR table.
+    ///
+    /// Types which are exclusive to GPT do not have a system ID.
+    pub fn mbr_id(self) -> Option<u8> {
+        let id = match self {
+            PartitionKind::EfiSystem => 0xEF,
+            PartitionKind::LinuxData
+            | PartitionKind::LinuxRoot(_)
+            | PartitionKind::LinuxHome
+            | PartitionKind::LinuxSrv
+            | PartitionKind::LinuxVar
+            | PartitionKind::LinuxTmp => 0x83,
+            PartitionKind::LinuxSwap => 0x82,
+            PartitionKind::LinuxLvm => 0x8E,
+            PartitionKind::LinuxLuks => 0xE8,
+            PartitionKind::LinuxRaid => 0xFD,
+            PartitionKind::MicrosoftBasicData => 0x07,
+            PartitionKind::WindowsRecovery => 0x27,
+            PartitionKind::AppleHfs => 0xAF,
+            PartitionKind::BiosBoot
+            | PartitionKind::MicrosoftReserved
+            | PartitionKind::AppleApfs => return None,
+        };
+
+        Some(id)
+    }
+}
+
+impl Default for PartitionKind {
+    fn default() -> Self { PartitionKind::LinuxData }
+}
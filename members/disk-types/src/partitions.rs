@@ -1,4 +1,4 @@
-use crate::fs::FileSystem;
+use crate::{fs::FileSystem, partition_kind::PartitionKind};
 use core::str::FromStr;
 
 #[derive(Debug, Default, Clone)]
@@ -10,10 +10,45 @@ pub struct Partition {
     pub partlabel:   Option<Box<str>>,
     pub mbr_variant: PartitionType,
     pub uuid:        Option<Box<str>>,
+    /// The UUID of this device within a multi-device file system, such as btrfs.
+    pub uuid_sub:    Option<Box<str>>,
+    /// The label of the file system, which is distinct from the GPT partition label.
+    pub label:       Option<Box<str>>,
+    /// The type GUID or system ID of the partition in its partition table.
+    pub kind:        Option<PartitionKind>,
+    /// The attribute bits of the partition entry, of which MBR tables only store the boot flag.
+    pub attributes:  PartitionAttributes,
+}
+
+bitflags! {
+    /// Attribute bits of a GPT partition entry.
+    ///
+    /// MBR tables only support the `LEGACY_BOOTABLE` attribute, which is stored as the boot
+    /// indicator of the partition.
+    pub struct PartitionAttributes: u64 {
+        /// The partition is required for the platform to function.
+        const REQUIRED = 1 << 0;
+        /// The firmware should not provide block I/O access to the partition.
+        const NO_BLOCK_IO = 1 << 1;
+        /// Legacy BIOS bootable, which is used for hybrid boot setups.
+        const LEGACY_BOOTABLE = 1 << 2;
+        /// The partition should be mounted read-only.
+        const READ_ONLY = 1 << 60;
+        /// The partition is a shadow copy of another partition.
+        const SHADOW_COPY = 1 << 61;
+        /// The partition should be hidden from the user.
+        const HIDDEN = 1 << 62;
+        /// The partition should not be mounted automatically.
+        const NO_AUTOMOUNT = 1 << 63;
+    }
+}
+
+impl Default for PartitionAttributes {
+    fn default() -> Self { PartitionAttributes::empty() }
 }
 
 /// Specifies whether the partition table on the disk is **MSDOS** or **GPT**.
-#[derive(Debug, PartialEq, Clone, Copy, Hash)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
 pub enum PartitionTable {
     Mbr,
     Guid,
@@ -47,7 +82,7 @@ impl From<PartitionTable> for &'static str {
 /// # Note
 ///
 /// This only applies for MBR partition tables.
-#[derive(Debug, PartialEq, Clone, Copy, Hash)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
 pub enum PartitionType {
     Primary,
     Logical,
@@ -1,35 +1,125 @@
 use crate::DeviceExt;
-use std::str::FromStr;
+use std::{ops::Range, str::FromStr};
 
 /// Trait for getting and sectors from a device.
 pub trait SectorExt: DeviceExt {
     /// Calculates the requested sector from a given `Sector` variant.
-    fn get_sector(&self, sector: Sector) -> u64 {
+    ///
+    /// `Sector::FreePercent` is measured within the usable sectors of the device.
+    fn get_sector(&self, sector: Sector) -> Result<u64, SectorError> {
+        let usable = match self.get_sector_within(Sector::End, 0..0) {
+            Ok(end) => self.get_sector_within(Sector::Start, 0..0)?..end,
+            Err(_) => 0..0,
+        };
+
+        self.get_sector_within(sector, usable)
+    }
+
+    /// Calculates the requested sector from a given `Sector` variant, measuring
+    /// `Sector::FreePercent` within the given region of free sectors.
+    fn get_sector_within(&self, sector: Sector, region: Range<u64>) -> Result<u64, SectorError> {
         const MIB2: u64 = 2 * 1024 * 1024;
 
-        let end = || self.sectors() - (MIB2 / self.logical_sector_size());
-        let megabyte = |size| (size * 1_000_000) / self.logical_sector_size();
+        let sector_size = self.logical_sector_size();
+        let reserved = MIB2 / sector_size;
+        let end = || match self.sectors().checked_sub(reserved) {
+            Some(end) if end > reserved => Ok(end),
+            _ => Err(SectorError::DeviceTooSmall),
+        };
+
+        let from_end = |sectors: u64| end()?.checked_sub(sectors).ok_or(SectorError::OutOfRange);
+        let bytes = |value: u64, unit: u64| {
+            value.checked_mul(unit).map(|bytes| bytes / sector_size).ok_or(SectorError::OutOfRange)
+        };
+
+        let percent = |sectors: u64, value: u16| {
+            (u128::from(sectors) * u128::from(value) / u128::from(::std::u16::MAX)) as u64
+        };
 
         match sector {
-            Sector::Start => MIB2 / self.logical_sector_size(),
+            Sector::Start => Ok(reserved),
             Sector::End => end(),
-            Sector::Megabyte(size) => megabyte(size),
-            Sector::MegabyteFromEnd(size) => end() - megabyte(size),
-            Sector::Unit(size) => size,
-            Sector::UnitFromEnd(size) => end() - size,
-            Sector::Percent(value) => {
-                if value == ::std::u16::MAX {
-                    self.sectors()
-                } else {
-                    ((self.sectors() * self.logical_sector_size()) / ::std::u16::MAX as u64)
-                        * value as u64
-                        / self.logical_sector_size()
-                }
+            Sector::Megabyte(size) => bytes(size, 1_000_000),
+            Sector::MegabyteFromEnd(size) => from_end(bytes(size, 1_000_000)?),
+            Sector::Bytes(size) => bytes(size, 1),
+            Sector::BytesFromEnd(size) => from_end(bytes(size, 1)?),
+            Sector::Unit(size) => Ok(size),
+            Sector::UnitFromEnd(size) => from_end(size),
+            Sector::Percent(value) => Ok(percent(self.sectors(), value)),
+            Sector::FreePercent(value) => {
+                let length = region.end.checked_sub(region.start).ok_or(SectorError::OutOfRange)?;
+                Ok(region.start + percent(length, value))
             }
         }
     }
 }
 
+/// A `Sector` could not be calculated for a device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SectorError {
+    /// The device is too small to reserve space for a partition table at its start and end.
+    DeviceTooSmall,
+    /// The sector would lie before the start of the device, or beyond what can be addressed.
+    OutOfRange,
+}
+
+/// Units which a size in bytes may be given in.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum ByteUnit {
+    /// 1,000 bytes.
+    Kilobyte,
+    /// 1,024 bytes.
+    Kibibyte,
+    /// 1,000,000 bytes.
+    Megabyte,
+    /// 1,048,576 bytes.
+    Mebibyte,
+    /// 1,000,000,000 bytes.
+    Gigabyte,
+    /// 1,073,741,824 bytes.
+    Gibibyte,
+    /// 1,000,000,000,000 bytes.
+    Terabyte,
+    /// 1,099,511,627,776 bytes.
+    Tebibyte,
+}
+
+impl ByteUnit {
+    /// The number of bytes in one of this unit.
+    pub fn bytes(self) -> u64 {
+        match self {
+            ByteUnit::Kilobyte => 1_000,
+            ByteUnit::Kibibyte => 1 << 10,
+            ByteUnit::Megabyte => 1_000_000,
+            ByteUnit::Mebibyte => 1 << 20,
+            ByteUnit::Gigabyte => 1_000_000_000,
+            ByteUnit::Gibibyte => 1 << 30,
+            ByteUnit::Terabyte => 1_000_000_000_000,
+            ByteUnit::Tebibyte => 1 << 40,
+        }
+    }
+}
+
+impl FromStr for ByteUnit {
+    type Err = &'static str;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let unit = match input {
+            "K" => ByteUnit::Kilobyte,
+            "Ki" => ByteUnit::Kibibyte,
+            "M" => ByteUnit::Megabyte,
+            "Mi" => ByteUnit::Mebibyte,
+            "G" => ByteUnit::Gigabyte,
+            "Gi" => ByteUnit::Gibibyte,
+            "T" => ByteUnit::Terabyte,
+            "Ti" => ByteUnit::Tebibyte,
+            _ => return Err("invalid byte unit"),
+        };
+
+        Ok(unit)
+    }
+}
+
 /// Used with the `Disk::get_sector` method for converting a more human-readable unit
 /// into the corresponding sector for the given disk.
 #[derive(Debug, PartialEq, Clone, Copy, Hash)]
@@ -48,9 +138,28 @@ pub enum Sector {
     Megabyte(u64),
     /// Similar to the above, but subtracting from the end.
     MegabyteFromEnd(u64),
+    /// A size in bytes, which is rounded down to a whole sector.
+    Bytes(u64),
+    /// Similar to the above, but subtracting from the end.
+    BytesFromEnd(u64),
     /// The percent can be represented by specifying a value between 0 and
     /// u16::MAX, where u16::MAX is 100%.
     Percent(u16),
+    /// A percent of the region of free space that a partition is being created in, with the
+    /// same representation as `Percent`.
+    FreePercent(u16),
+}
+
+impl Sector {
+    /// A size in the given unit, or `None` if it cannot be represented in bytes.
+    pub fn size(value: u64, unit: ByteUnit) -> Option<Sector> {
+        value.checked_mul(unit.bytes()).map(Sector::Bytes)
+    }
+
+    /// Similar to the above, but subtracting from the end.
+    pub fn size_from_end(value: u64, unit: ByteUnit) -> Option<Sector> {
+        value.checked_mul(unit.bytes()).map(Sector::BytesFromEnd)
+    }
 }
 
 impl From<u64> for Sector {
@@ -60,33 +169,54 @@ impl From<u64> for Sector {
 impl FromStr for Sector {
     type Err = &'static str;
 
+    /// Parses `start`, `end`, a number of sectors, a size such as `512Mi` or `4G`, a number of
+    /// bytes such as `4096B`, a percent of the device such as `50%`, or a percent of the
+    /// region of free space such as `50%free`.
+    ///
+    /// Sectors and sizes which are prefixed with `-` are measured from the end. For
+    /// compatibility, a size in `M` is given as `Sector::Megabyte`.
     fn from_str(input: &str) -> Result<Self, Self::Err> {
-        if input.ends_with('M') {
-            if input.starts_with('-') {
-                if let Ok(value) = input[1..input.len() - 1].parse::<u64>() {
-                    return Ok(Sector::MegabyteFromEnd(value));
-                }
-            } else if let Ok(value) = input[..input.len() - 1].parse::<u64>() {
-                return Ok(Sector::Megabyte(value));
-            }
+        const INVALID: &str = "invalid sector value";
+
+        match input {
+            "start" => return Ok(Sector::Start),
+            "end" => return Ok(Sector::End),
+            _ => (),
+        }
+
+        let percent = |value: &str| {
+            value
+                .parse::<u64>()
+                .ok()
+                .filter(|&value| value <= 100)
+                .map(|value| (value * u64::from(::std::u16::MAX) / 100) as u16)
+                .ok_or(INVALID)
+        };
+
+        if input.ends_with("%free") {
+            return percent(&input[..input.len() - 5]).map(Sector::FreePercent);
         } else if input.ends_with('%') {
-            if let Ok(value) = input[..input.len() - 1].parse::<u16>() {
-                if value <= 100 {
-                    return Ok(Sector::Percent(value));
-                }
-            }
-        } else if input == "start" {
-            return Ok(Sector::Start);
-        } else if input == "end" {
-            return Ok(Sector::End);
-        } else if input.starts_with('-') {
-            if let Ok(value) = input[1..input.len()].parse::<u64>() {
-                return Ok(Sector::UnitFromEnd(value));
-            }
-        } else if let Ok(value) = input[..input.len()].parse::<u64>() {
-            return Ok(Sector::Unit(value));
+            return percent(&input[..input.len() - 1]).map(Sector::Percent);
         }
 
-        Err("invalid sector value")
+        let (from_end, input) =
+            if input.starts_with('-') { (true, &input[1..]) } else { (false, input) };
+
+        let digits = input.find(|c: char| !c.is_ascii_digit()).unwrap_or(input.len());
+        let (value, suffix) = input.split_at(digits);
+        let value = value.parse::<u64>().map_err(|_| INVALID)?;
+
+        let sector = match (suffix, from_end) {
+            ("", false) => Sector::Unit(value),
+            ("", true) => Sector::UnitFromEnd(value),
+            ("M", false) => Sector::Megabyte(value),
+            ("M", true) => Sector::MegabyteFromEnd(value),
+            ("B", false) => Sector::Bytes(value),
+            ("B", true) => Sector::BytesFromEnd(value),
+            (unit, false) => Sector::size(value, unit.parse()?).ok_or(INVALID)?,
+            (unit, true) => Sector::size_from_end(value, unit.parse()?).ok_or(INVALID)?,
+        };
+
+        Ok(sector)
     }
 }
@@ -0,0 +1,92 @@
+use crate::{device::DeviceExt, sector::ByteUnit};
+use std::{fmt, str::FromStr};
+
+/// A size in bytes, which is displayed in human-readable binary units, such as `12.4 GiB`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Size(u64);
+
+impl Size {
+    pub fn from_bytes(bytes: u64) -> Self { Size(bytes) }
+
+    /// The size of the given number of sectors on the device, or `None` if it cannot be
+    /// represented in bytes.
+    pub fn from_sectors<D: DeviceExt + ?Sized>(sectors: u64, device: &D) -> Option<Self> {
+        sectors.checked_mul(device.logical_sector_size()).map(Size)
+    }
+
+    pub fn bytes(self) -> u64 { self.0 }
+
+    /// The number of whole sectors on the device which fit within this size.
+    pub fn to_sectors<D: DeviceExt + ?Sized>(self, device: &D) -> u64 {
+        self.0 / device.logical_sector_size()
+    }
+}
+
+impl From<u64> for Size {
+    fn from(bytes: u64) -> Self { Size(bytes) }
+}
+
+impl fmt::Display for Size {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        const UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+        if self.0 < 1024 {
+            return write!(f, "{} B", self.0);
+        }
+
+        let mut unit = 0;
+        while unit + 1 < UNITS.len() && self.0 >> (10 * (unit + 2)) != 0 {
+            unit += 1;
+        }
+
+        // Rounded to one decimal place without a detour through floating point.
+        let divisor = 1u128 << (10 * (unit + 1));
+        let tenths = (u128::from(self.0) * 10 + divisor / 2) / divisor;
+        write!(f, "{}.{} {}", tenths / 10, tenths % 10, UNITS[unit])
+    }
+}
+
+impl FromStr for Size {
+    type Err = &'static str;
+
+    /// Parses a number of bytes, optionally with a fractional part and a unit, such as `4096`,
+    /// `512 MiB`, `4G`, or `12.4GiB`.
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        const INVALID: &str = "invalid size";
+
+        let input = input.trim();
+        let digits = input.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(input.len());
+        let (value, unit) = input.split_at(digits);
+        let unit = unit.trim_start();
+
+        let unit = match unit {
+            "" | "B" => 1,
+            _ if unit.ends_with('B') => unit[..unit.len() - 1].parse::<ByteUnit>()?.bytes(),
+            _ => unit.parse::<ByteUnit>()?.bytes(),
+        };
+
+        let (whole, fraction) = match value.find('.') {
+            Some(pos) => (&value[..pos], &value[pos + 1..]),
+            None => (value, ""),
+        };
+
+        if whole.is_empty() || fraction.contains('.') || fraction.len() > 18 {
+            return Err(INVALID);
+        }
+
+        let whole = whole.parse::<u128>().map_err(|_| INVALID)?;
+        let mut bytes = whole.checked_mul(u128::from(unit)).ok_or(INVALID)?;
+
+        if !fraction.is_empty() {
+            let scale = 10u128.pow(fraction.len() as u32);
+            let fraction = fraction.parse::<u128>().map_err(|_| INVALID)?;
+            bytes += fraction * u128::from(unit) / scale;
+        }
+
+        if bytes > u128::from(::std::u64::MAX) {
+            return Err(INVALID);
+        }
+
+        Ok(Size(bytes as u64))
+    }
+}
@@ -0,0 +1,102 @@
+use crate::DeviceExt;
+
+/// Partitions are aligned to this many bytes, unless the device prefers another I/O size.
+const MIB: u64 = 1024 * 1024;
+
+/// Controls whether new partitions are aligned to the topology of their device.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum AlignmentPolicy {
+    /// Snap the start and end of new partitions to the optimal alignment of the device.
+    Optimal,
+    /// Create partitions on exactly the sectors that were requested.
+    Off,
+}
+
+impl Default for AlignmentPolicy {
+    fn default() -> Self { AlignmentPolicy::Optimal }
+}
+
+/// The I/O topology of a block device, as reported by the kernel.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct Topology {
+    /// Offset in bytes from the start of the device to its first naturally-aligned sector.
+    pub alignment_offset: u64,
+    /// The smallest I/O size in bytes that avoids a read-modify-write cycle on the device.
+    pub minimum_io_size:  u64,
+    /// The preferred I/O size in bytes, such as the stripe width of a RAID array.
+    ///
+    /// This is zero if the device does not report one.
+    pub optimal_io_size:  u64,
+}
+
+impl Topology {
+    /// The number of bytes that partitions on the device should be aligned to.
+    ///
+    /// This is 1 MiB, unless the optimal I/O size of the device does not evenly divide it. The
+    /// grain is then extended to a multiple of the minimum I/O size, so that partitions never
+    /// begin or end partway through a minimum I/O unit.
+    pub fn grain(&self) -> u64 {
+        let grain = if self.optimal_io_size == 0 || MIB % self.optimal_io_size == 0 {
+            MIB
+        } else {
+            self.optimal_io_size
+        };
+
+        match self.minimum_io_size {
+            0 => grain,
+            minimum => grain / gcd(grain, minimum) * minimum,
+        }
+    }
+
+    /// Whether partitions on the device must be aligned to perform well.
+    ///
+    /// This applies to advanced format drives (512e and 4Kn), RAID arrays which report an
+    /// optimal or minimum I/O size, and devices whose first sector is not naturally aligned.
+    pub fn needs_alignment<D: DeviceExt>(&self, device: &D) -> bool {
+        device.logical_sector_size() > 512
+            || device.physical_sector_size() > 512
+            || self.optimal_io_size != 0
+            || self.minimum_io_size > 512
+            || self.alignment_offset != 0
+    }
+
+    /// Rounds the sector up to the next aligned sector on the device.
+    pub fn align_up<D: DeviceExt>(&self, device: &D, sector: u64) -> u64 {
+        let (grain, offset) = self.grain_sectors(device);
+        if sector <= offset {
+            return offset;
+        }
+
+        match (sector - offset) % grain {
+            0 => sector,
+            remainder => sector + grain - remainder,
+        }
+    }
+
+    /// Rounds the sector down to the previous aligned sector on the device.
+    pub fn align_down<D: DeviceExt>(&self, device: &D, sector: u64) -> u64 {
+        let (grain, offset) = self.grain_sectors(device);
+        if sector < offset {
+            return sector;
+        }
+
+        sector - (sector - offset) % grain
+    }
+
+    /// The grain and alignment offset, in logical sectors of the device.
+    fn grain_sectors<D: DeviceExt>(&self, device: &D) -> (u64, u64) {
+        let sector_size = device.logical_sector_size();
+        let grain = (self.grain() / sector_size).max(1);
+        (grain, (self.alignment_offset / sector_size) % grain)
+    }
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        let remainder = a % b;
+        a = b;
+        b = remainder;
+    }
+
+    a
+}
@@ -21,7 +21,12 @@ use std::{
 };
 
 use self::systems::DiskSystems;
-pub use disk_ops::table::PartitionError;
+pub use disk_ops::{
+    table::{
+        ConvertError, DumpPartition, GptHealth, HeaderHealth, PartitionError, TableDump, UuidPolicy,
+    },
+    tools,
+};
 pub use disk_types;
 use ops::luks::LuksParams;
 use slotmap::new_key_type;
@@ -79,6 +84,10 @@ bitflags! {
         const RESIZE = 1 << 4;
         /// Schedules for the VG data to be reloaded.
         const RELOAD_VGS = 1 << 5;
+        /// Schedule the attribute system to run
+        const ATTRIBUTES = 1 << 6;
+        /// Schedule new UUIDs to be assigned
+        const UUIDS = 1 << 7;
     }
 }
 
@@ -109,6 +118,12 @@ pub struct DiskManager {
 
     /// Flags which control the behavior of the manager.
     flags: ManagerFlags,
+
+    /// Whether new partitions are aligned to the topology of their device.
+    alignment: AlignmentPolicy,
+
+    /// Programs and kernel support found on the host, which operations are checked against.
+    tools: Option<tools::SupportMatrix>,
 }
 
 #[derive(Debug, Default)]
@@ -181,10 +196,36 @@ pub struct DeviceComponents {
     /// Partitions formatted as LVM PVs, which may be assigned to a VG
     pub pvs: SparseSecondaryMap<DeviceEntity, (LvmPv, Option<VgEntity>)>,
 
+    /// The integrity of GPT tables, as it was verified when the device was scanned.
+    pub table_health: SparseSecondaryMap<DeviceEntity, GptHealth>,
+
     /// Partition tables associated with devices.
     ///
     /// Disk and loopback devices may optionally have these.
     pub tables: SparseSecondaryMap<DeviceEntity, PartitionTable>,
+
+    /// The I/O topology of whole devices, which partitions are aligned to.
+    pub topologies: SparseSecondaryMap<DeviceEntity, Topology>,
+
+    /// UUIDs which were found on more than one device when devices were scanned.
+    pub uuid_collisions: Vec<UuidCollision>,
+}
+
+/// Whether a UUID identifies a file system, or a partition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum UuidKind {
+    FileSystem,
+    Partition,
+}
+
+/// A UUID which is shared by more than one device, as happens when a disk has been cloned.
+///
+/// Devices that share a UUID cannot be reliably found by it, such as from the fstab.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UuidCollision {
+    pub kind:     UuidKind,
+    pub uuid:     Box<str>,
+    pub entities: Vec<DeviceEntity>,
 }
 
 /// Stores requested modificactions to an entity.
@@ -193,14 +234,32 @@ pub struct DeviceComponents {
 /// It also helps to reduce logic required for making changes to the system.
 #[derive(Debug, Default)]
 pub struct QueuedChanges {
+    /// Requests to change a partition's attribute bits.
+    pub attributes: SparseSecondaryMap<DeviceEntity, PartitionAttributes>,
+
+    /// GPT tables whose backup header is to be moved to the end of their grown device.
+    pub backup_relocations: SparseSecondaryMap<DeviceEntity, ()>,
+
+    /// Partition tables to rewrite in another format.
+    pub conversions: SparseSecondaryMap<DeviceEntity, PartitionTable>,
+
     /// A device to create.
     pub devices: SparseSecondaryMap<DeviceEntity, Device>,
 
+    /// Disk GUIDs to assign to the tables that are to be created.
+    pub disk_guids: SparseSecondaryMap<DeviceEntity, [u8; 16]>,
+
     /// Secured passphrases for LUKS devices.
     pub device_maps: SparseSecondaryMap<DeviceEntity, Box<str>>,
 
-    /// Requests to change a partition's file system.
-    pub formats: SparseSecondaryMap<DeviceEntity, FileSystem>,
+    /// Requests to change a partition's file system, and the options to create it with.
+    pub formats: SparseSecondaryMap<DeviceEntity, (FileSystem, FormatOptions)>,
+
+    /// Requests to change the label of a file system.
+    pub fs_labels: SparseSecondaryMap<DeviceEntity, Box<str>>,
+
+    /// File systems to give a new, random UUID.
+    pub fs_uuids: SparseSecondaryMap<DeviceEntity, ()>,
 
     /// Requests to change a partition's label.
     pub labels: SparseSecondaryMap<DeviceEntity, Box<str>>,
@@ -217,6 +276,9 @@ pub struct QueuedChanges {
     /// Devices with parent(s) will associate their parent device(s) here.
     pub partitions: SparseSecondaryMap<DeviceEntity, Partition>,
 
+    /// Requests to change the PARTUUID of a partition on a GPT table.
+    pub partuuids: SparseSecondaryMap<DeviceEntity, [u8; 16]>,
+
     /// LVM PVs to associate with volume groups.
     pub pv_parents: SparseSecondaryMap<DeviceEntity, VgEntity>,
 
@@ -229,25 +291,44 @@ pub struct QueuedChanges {
     /// Devices to be associated with a volume group.
     pub vg_parents: SparseSecondaryMap<DeviceEntity, VgEntity>,
 
-    /// Requests to resize a partition.
+    /// Requests to move a partition, with its new offset.
+    pub moves: SparseSecondaryMap<DeviceEntity, u64>,
+
+    /// Requests to resize a partition, with its new offset and length in sectors.
     pub resize: SparseSecondaryMap<DeviceEntity, (u64, u64)>,
 
+    /// Partition table layouts to restore when their table is created.
+    pub restores: SparseSecondaryMap<DeviceEntity, TableDump>,
+
+    /// GPT tables whose partitions are to be renumbered in the order of their first sector.
+    pub sorts: SparseSecondaryMap<DeviceEntity, ()>,
+
     /// Tables to create
     pub tables: SparseSecondaryMap<DeviceEntity, PartitionTable>,
 }
 
 impl QueuedChanges {
     pub fn clear(&mut self) {
+        self.attributes.clear();
+        self.backup_relocations.clear();
+        self.conversions.clear();
         self.devices.clear();
         self.device_maps.clear();
+        self.disk_guids.clear();
         self.formats.clear();
+        self.fs_labels.clear();
+        self.fs_uuids.clear();
         self.labels.clear();
         self.luks.clear();
+        self.moves.clear();
         self.parents.clear();
         self.partitions.clear();
+        self.partuuids.clear();
         self.volume_groups.clear();
         self.vg_parents.clear();
         self.resize.clear();
+        self.restores.clear();
+        self.sorts.clear();
         self.tables.clear();
     }
 
@@ -327,13 +408,32 @@ impl DiskManager {
         });
     }
 
+    /// Sets whether new partitions will be aligned to the topology of their device.
+    pub fn set_alignment_policy(&mut self, policy: AlignmentPolicy) { self.alignment = policy; }
+
+    /// Sets the support matrix which operations are checked against, before they are queued.
+    ///
+    /// The matrix is probed from the host when disks are scanned. Without one, operations are
+    /// not checked for the programs that they require.
+    pub fn set_tool_support(&mut self, tools: Option<tools::SupportMatrix>) { self.tools = tools; }
+
     /// Reloads all disk information from the system.
     pub fn scan(&mut self) -> Result<(), Error> {
         self.clear();
+        self.tools = Some(tools::probe());
         let &mut DiskManager { ref mut entities, ref mut components, .. } = self;
         systems::scan(entities, components)
     }
 
+    /// Whether an operation on a file system can be carried out by this host.
+    pub(crate) fn tool_available(
+        &self,
+        fs: FileSystem,
+        op: tools::Operation,
+    ) -> Result<(), tools::Unavailable> {
+        self.tools.as_ref().map_or(Ok(()), |tools| tools.available(fs, op))
+    }
+
     /// Apply all queued disk operations on the system.
     pub fn apply(&mut self, cancel: &Arc<AtomicBool>) -> Result<(), Error> {
         let result = {
@@ -342,6 +442,7 @@ impl DiskManager {
                 ref mut components,
                 ref mut systems,
                 ref flags,
+                ..
             } = self;
             systems::run(entities, components, systems, flags, cancel)
         };
@@ -0,0 +1,58 @@
+//! Methods for checking and repairing file systems in the world.
+use crate::*;
+
+/// An error that may occur when checking a file system.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(display = "failed to check {} file system on {:?}", _1, _0)]
+    Check(Box<Path>, FileSystem, #[error(cause)] io::Error),
+    #[error(display = "{} file systems cannot be checked", _0)]
+    CheckUnsupported(FileSystem),
+    #[error(display = "device does not have a file system")]
+    NoFileSystem,
+    #[error(display = "cannot check {} file systems on this host: {}", _0, _1)]
+    Unavailable(FileSystem, tools::Unavailable),
+}
+
+impl DiskManager {
+    /// Checks the file system of a device, and repairs it as the mode permits.
+    ///
+    /// Unlike other operations, the check is not queued, and is run immediately. The device
+    /// must not be mounted.
+    pub fn check(&mut self, entity: DeviceEntity, mode: CheckMode) -> Result<CheckVerdict, Error> {
+        let devices = &self.components.devices;
+        let fs = devices
+            .partitions
+            .get(entity)
+            .and_then(|partition| partition.filesystem)
+            .ok_or(Error::NoFileSystem)?;
+
+        if fs.check_tool().is_none() {
+            return Err(Error::CheckUnsupported(fs));
+        }
+
+        self.tool_available(fs, tools::Operation::Check)
+            .map_err(|why| Error::Unavailable(fs, why))?;
+
+        let path = &devices.devices[entity].path;
+        let progress = &mut self.systems.resize.check_progress;
+        disk_ops::partition::check(path, fs, mode, |done, total| {
+            if let Some(ref mut progress) = progress {
+                progress(entity, done, total);
+            }
+        })
+        .map_err(|why| Error::Check(path.clone(), fs, why))
+    }
+
+    /// Sets a callback which receives the progress of file system checks.
+    ///
+    /// The callback is given the partition being checked, the number of steps which have been
+    /// completed so far, and the total number of steps. File systems are also checked by the
+    /// resize system before they are resized.
+    pub fn on_check_progress<F>(&mut self, func: F)
+    where
+        F: FnMut(DeviceEntity, u64, u64) + Send + 'static,
+    {
+        self.systems.resize.check_progress = Some(Box::new(func));
+    }
+}
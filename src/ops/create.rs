@@ -1,5 +1,10 @@
 /// ! Method for creating a new partition entities in the world.
+use super::layout::{Allocation, SegmentKind};
 use crate::*;
+use disk_ops::table::{
+    check_restore, convert_array_to_str, plan_conversion, PlanError, RestoreError, TablePartition,
+    GPT_ENTRIES,
+};
 use disk_types::*;
 use loopdev::LoopControl;
 use std::path::PathBuf;
@@ -12,18 +17,52 @@ const LVM_DEFAULT_EXTENT_SIZE: u64 = 4 * 1024 * 1024;
 /// An error that may occur when adding creation operations to the queue.
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Error)]
 pub enum Error {
+    #[error(display = "{:?} partition tables do not have a backup header", _0)]
+    BackupUnsupported(PartitionTable),
+    #[error(display = "the partition table cannot be converted")]
+    Convert(#[error(cause)] PlanError),
     #[error(display = "the new partition exceeds the size of the parent device")]
     ExceedsDevice,
     #[error(display = "a supplied device entity was expected to be a LVM PV")]
     ExpectedLvmPv,
+    #[error(display = "{} file systems cannot be created with these options: {:?}", _0, _1)]
+    FormatOptions(FileSystem, FormatOptionsError),
+    #[error(display = "{} file systems cannot be created", _0)]
+    FormatUnsupported(FileSystem),
     #[error(display = "the new partition overlaps an existing partition")]
     PartitionOverlap,
+    #[error(display = "extended partitions may only be created on MBR partition tables")]
+    ExtendedUnsupported,
     #[error(display = "the end sector lies before the start sector")]
     InputsInverted,
+    #[error(display = "{:?} partitions are not supported by MBR partition tables", _0)]
+    KindUnsupported(PartitionKind),
+    #[error(display = "partition labels are not supported by MBR partition tables")]
+    LabelUnsupported,
+    #[error(display = "only partitions which are queued to be created can be numbered")]
+    NotQueued,
     #[error(display = "parent device is not partitionable")]
     NotPartitionable,
+    #[error(display = "partition number {} is invalid or already in use", _0)]
+    NumberInvalid(u32),
+    #[error(display = "device does not have a partition table")]
+    TableNotFound,
     #[error(display = "cannot create table on device")]
     TablesUnsupported,
+    #[error(display = "the partition table cannot be restored")]
+    Restore(#[error(cause)] RestoreError),
+    #[error(display = "the sector could not be calculated: {:?}", _0)]
+    Sector(SectorError),
+    #[error(display = "{:?} partition tables cannot be sorted", _0)]
+    SortUnsupported(PartitionTable),
+    #[error(display = "{} file systems may not be larger than {} bytes", _0, _1)]
+    TooLarge(FileSystem, u64),
+    #[error(display = "{} file systems may not be smaller than {} bytes", _0, _1)]
+    TooSmall(FileSystem, u64),
+    #[error(display = "cannot create {} file systems on this host: {}", _0, _1)]
+    Unavailable(FileSystem, tools::Unavailable),
+    #[error(display = "partition UUIDs on MBR tables are derived from the disk signature")]
+    UuidUnsupported,
 }
 
 #[derive(Debug, Error)]
@@ -44,12 +83,22 @@ pub enum LoopbackError {
     ProbeNotFound,
 }
 
+/// A partition whose device path will change once its partition table has been sorted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Renumbered {
+    pub entity: DeviceEntity,
+    pub from:   Box<Path>,
+    pub to:     Box<Path>,
+}
+
 /// Defines to create either a plain partition, or a LUKS-encrypted partition.
 pub enum PartitionCreate {
-    /// Create a simple, plain file system on the partition.
-    Plain(FileSystem),
+    /// Create a simple, plain file system on the partition, with the given options.
+    Plain(FileSystem, FormatOptions),
     /// Create a LUKS device, with an optional passphrase.
     Luks(LuksParams),
+    /// Create an extended partition on a MBR partition table, to contain logical partitions.
+    Extended,
 }
 
 /// Defines how the new file system will be created.
@@ -117,10 +166,16 @@ impl DiskManager {
                                 partlabel:   None,
                                 mbr_variant: PartitionType::Primary,
                                 uuid:        info.uuid,
+                                uuid_sub:    info.uuid_sub,
+                                label:       info.label,
+                                kind:        None,
+                                attributes:  PartitionAttributes::empty(),
                             },
                         );
                     }
 
+                    self.components.devices.children.insert(entity, Vec::new());
+
                     // TODO: Handle loopback devices with LUKS and LVM partitions.
 
                     return Ok(entity);
@@ -133,14 +188,33 @@ impl DiskManager {
     }
 
     /// Create a file system directly on a device.
+    ///
+    /// A file system label which was queued with `fs_label` is given to the new file system,
+    /// unless the format options have a label of their own. A new LUKS device discards it, along
+    /// with the file system that it was queued for.
     pub fn create_on(&mut self, device: DeviceEntity, what: PartitionCreate) -> Result<(), Error> {
         self.assert_not_creating_table_on(device);
 
+        let what = match what {
+            PartitionCreate::Extended => return Err(Error::ExtendedUnsupported),
+            PartitionCreate::Plain(fs, mut options) => {
+                if options.label.is_none() {
+                    options.label = self.components.queued_changes.fs_labels.get(device).cloned();
+                }
+
+                PartitionCreate::Plain(fs, options)
+            }
+            what => what,
+        };
+
         let (sectors, logical_sector_size, physical_sector_size) = {
             let device_info = &self.components.devices.devices[device];
             (device_info.sectors, device_info.logical_sector_size, device_info.physical_sector_size)
         };
 
+        self.validate_format(&what, sectors * logical_sector_size)?;
+        self.components.queued_changes.fs_labels.remove(device);
+
         let offset = match self.components.devices.partitions.get(device) {
             Some(partition) => partition.offset,
             None => 0,
@@ -150,10 +224,12 @@ impl DiskManager {
             what,
             device,
             None,
+            None,
             offset,
             sectors,
             logical_sector_size,
             physical_sector_size,
+            PartitionType::Primary,
             |manager| {
                 let device_components = &manager.components.devices;
                 let device = manager
@@ -183,6 +259,10 @@ impl DiskManager {
         name: Box<str>,
         what: PartitionCreate,
     ) -> Result<DeviceEntity, Error> {
+        if let PartitionCreate::Extended = what {
+            return Err(Error::ExtendedUnsupported);
+        }
+
         let mut lazy_lvpath = None;
         let (length, dmname);
 
@@ -197,6 +277,7 @@ impl DiskManager {
                 .expect("vg entity without vg component");
 
             length = self.can_create_on_vg(parent, vg, sector)?;
+            self.validate_format(&what, length * 512)?;
 
             dmname = [vg.name.replace("-", "--").as_str(), "-", name.replace("-", "--").as_str()]
                 .concat()
@@ -226,7 +307,18 @@ impl DiskManager {
             path
         };
 
-        self.create_partition(what, entity, None, 0, length, 512, 512, lvpf);
+        self.create_partition(
+            what,
+            entity,
+            None,
+            None,
+            0,
+            length,
+            512,
+            512,
+            PartitionType::Primary,
+            lvpf,
+        );
 
         // Associate the newly-queued device with the parent.
         self.components.queued_changes.vg_parents.insert(entity, parent);
@@ -244,29 +336,83 @@ impl DiskManager {
     }
 
     /// Create a new partition on a partitionable device.
+    ///
+    /// If a `kind` is not given, the partition type is derived from the file system.
+    ///
+    /// On GPT tables, the PARTUUID is chosen by the `uuid` policy, and recorded immediately. MBR
+    /// tables derive the PARTUUID from the partition number, so it is only known once the
+    /// partition has been written, and a policy other than `UuidPolicy::Random` is rejected.
+    /// MBR tables also have no partition labels, so the `label` must be empty.
     pub fn create_as_child_of(
         &mut self,
         parent: DeviceEntity,
         start: Sector,
         end: Sector,
         label: Box<str>,
+        kind: Option<PartitionKind>,
+        uuid: UuidPolicy,
         what: PartitionCreate,
     ) -> Result<DeviceEntity, Error> {
-        self.assert_not_creating_table_on(parent);
+        self.assert_not_creating_partition_on(parent);
 
         let (offset, length, logical_sector_size, physical_sector_size) = {
+            // Devices which are queued to be created only exist among the queued changes.
             let device_components = &self.components.devices;
-            let device = if self.entities.devices[parent].contains(EntityFlags::CREATE) {
-                &self.components.queued_changes.devices[parent]
-            } else {
-                &device_components.devices[parent]
-            };
+            let device = self
+                .components
+                .queued_changes
+                .devices
+                .get(parent)
+                .unwrap_or_else(|| &device_components.devices[parent]);
 
             let sectors = self.can_create_on_device(parent, device, start, end)?;
 
             (sectors.0, sectors.1, device.logical_sector_size, device.physical_sector_size)
         };
 
+        self.validate_format(&what, length * logical_sector_size)?;
+
+        let table = self.table_of(parent);
+        let mbr_variant = match table {
+            Some(PartitionTable::Mbr) => match what {
+                PartitionCreate::Extended => PartitionType::Extended,
+                _ => {
+                    if let Some(kind) = kind.filter(|kind| kind.mbr_id().is_none()) {
+                        return Err(Error::KindUnsupported(kind));
+                    }
+
+                    if self.within_extended(parent, offset, offset + length) {
+                        PartitionType::Logical
+                    } else {
+                        PartitionType::Primary
+                    }
+                }
+            },
+            _ => match what {
+                PartitionCreate::Extended => return Err(Error::ExtendedUnsupported),
+                _ => PartitionType::Primary,
+            },
+        };
+
+        let partuuid = match table {
+            Some(PartitionTable::Mbr) if !label.is_empty() => return Err(Error::LabelUnsupported),
+            Some(PartitionTable::Mbr) if uuid != UuidPolicy::Random => {
+                return Err(Error::UuidUnsupported)
+            }
+            Some(PartitionTable::Mbr) => None,
+            _ => {
+                let kind = kind.unwrap_or_else(|| {
+                    PartitionKind::from_filesystem(match what {
+                        PartitionCreate::Plain(fs, _) => Some(fs),
+                        PartitionCreate::Luks(_) => Some(FileSystem::Luks),
+                        PartitionCreate::Extended => None,
+                    })
+                });
+
+                Some(convert_array_to_str(&uuid.partition_uuid(kind, offset)).into())
+            }
+        };
+
         // Create a new device entity for the new partition.
         let entity = self.entities.devices.insert(EntityFlags::CREATE);
 
@@ -274,10 +420,12 @@ impl DiskManager {
             what,
             entity,
             Some(label),
+            kind,
             offset,
             length,
             logical_sector_size,
             physical_sector_size,
+            mbr_variant,
             |manager| {
                 let device_components = &manager.components.devices;
                 let device = manager
@@ -292,6 +440,7 @@ impl DiskManager {
             },
         );
 
+        self.components.queued_changes.partitions[entity].partuuid = partuuid;
         self.components.queued_changes.parents.insert(entity, parent);
 
         self.entities.devices[parent] |= EntityFlags::CREATE | EntityFlags::CREATE_CHILDREN;
@@ -303,10 +452,14 @@ impl DiskManager {
     }
 
     /// Define that a new partition table will be written to this device.
+    ///
+    /// The disk GUID of the table is chosen by the `uuid` policy. MBR tables take their disk
+    /// signature from its first four bytes.
     pub fn create_table(
         &mut self,
         entity: DeviceEntity,
         kind: PartitionTable,
+        uuid: UuidPolicy,
     ) -> Result<(), Error> {
         self.assert_not_creating_partition_on(entity);
 
@@ -314,7 +467,9 @@ impl DiskManager {
             return Err(Error::TablesUnsupported);
         }
 
-        self.components.queued_changes.tables.insert(entity, kind);
+        let queued = &mut self.components.queued_changes;
+        queued.tables.insert(entity, kind);
+        queued.disk_guids.insert(entity, uuid.disk_guid());
 
         self.entities.devices[entity] |= EntityFlags::CREATE;
         self.flags |= ManagerFlags::CREATE;
@@ -325,6 +480,207 @@ impl DiskManager {
         Ok(())
     }
 
+    /// Define that a partition table will be written to this device from the layout of a dump.
+    ///
+    /// Like `create_table`, the existing table of the device, and its partitions, are replaced.
+    /// The dump is checked against the size of the device first, so that a dump which cannot be
+    /// restored is rejected before the device is wiped.
+    pub fn restore_table(&mut self, entity: DeviceEntity, dump: TableDump) -> Result<(), Error> {
+        {
+            let device = &self.components.devices.devices[entity];
+            check_restore(&dump, device.sectors, device.logical_sector_size)
+                .map_err(Error::Restore)?;
+        }
+
+        self.create_table(entity, dump.table, UuidPolicy::Random)?;
+
+        // The disk GUID, or signature, is taken from the dump instead.
+        let queued = &mut self.components.queued_changes;
+        queued.disk_guids.remove(entity);
+        queued.restores.insert(entity, dump);
+        Ok(())
+    }
+
+    /// Define that the partition table of this device will be rewritten in another format.
+    ///
+    /// Existing partitions are preserved, and none of their sectors are moved.
+    pub fn convert_table(
+        &mut self,
+        entity: DeviceEntity,
+        table: PartitionTable,
+    ) -> Result<(), Error> {
+        self.assert_not_creating_table_on(entity);
+
+        let current = self.components.devices.tables.get(entity).cloned();
+        if current.ok_or(Error::TableNotFound)? == table {
+            return Ok(());
+        }
+
+        {
+            let devices = &self.components.devices;
+            let device = &devices.devices[entity];
+
+            let partitions = devices
+                .children
+                .get(entity)
+                .into_iter()
+                .flatten()
+                .filter(|&&child| !self.entities.devices[child].contains(EntityFlags::REMOVE))
+                .filter_map(|&child| {
+                    devices.partitions.get(child).map(|partition| TablePartition {
+                        number:     partition.number,
+                        start:      partition.offset,
+                        end:        partition.offset + devices.devices[child].sectors - 1,
+                        name:       partition.partlabel.clone(),
+                        uuid:       partition.partuuid.clone().unwrap_or_default(),
+                        variant:    partition.mbr_variant,
+                        kind:       partition.kind,
+                        attributes: partition.attributes,
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            plan_conversion(&partitions, table, device.sectors, device.logical_sector_size)
+                .map_err(Error::Convert)?;
+        }
+
+        self.components.queued_changes.conversions.insert(entity, table);
+        self.flags |= ManagerFlags::CREATE;
+
+        Ok(())
+    }
+
+    /// Define that the backup header of a GPT table will be moved to the end of its device.
+    ///
+    /// This allows the sectors gained by growing a disk or loopback file to be partitioned. The
+    /// new size of the device is read from the kernel when the change is applied, and
+    /// partitions may be created in the new sectors after that.
+    pub fn relocate_backup_header(&mut self, entity: DeviceEntity) -> Result<(), Error> {
+        match self.components.devices.tables.get(entity) {
+            Some(PartitionTable::Guid) => (),
+            Some(&table) => return Err(Error::BackupUnsupported(table)),
+            None => return Err(Error::TableNotFound),
+        }
+
+        self.components.queued_changes.backup_relocations.insert(entity, ());
+        self.flags |= ManagerFlags::CREATE;
+
+        Ok(())
+    }
+
+    /// Requests the number of a partition which is queued to be created on a partition table.
+    ///
+    /// Partitions without a requested number take the first number that is unused. On MBR
+    /// tables, primary partitions may be numbered 1 to 4, and logical partitions must be given
+    /// the number that follows the last logical partition, counting those which were already
+    /// given a number. On GPT tables, the number may not exceed the number of entries in the
+    /// table, which is `GPT_ENTRIES` for tables that are queued to be written.
+    pub fn request_number(&mut self, entity: DeviceEntity, number: u32) -> Result<(), Error> {
+        let queued = &self.components.queued_changes;
+        let parent = *queued.parents.get(entity).ok_or(Error::NotQueued)?;
+        let partition = &queued.partitions[entity];
+
+        let devices = &self.components.devices;
+        let existing = devices
+            .children
+            .get(parent)
+            .into_iter()
+            .flatten()
+            .filter(|&&child| !self.entities.devices[child].contains(EntityFlags::REMOVE))
+            .filter_map(|&child| devices.partitions.get(child));
+
+        let requested = queued
+            .parents
+            .iter()
+            .filter(|&(child, &cparent)| cparent == parent && child != entity)
+            .map(|(child, _)| &queued.partitions[child])
+            .filter(|partition| partition.number != 0);
+
+        let siblings = existing.chain(requested).collect::<Vec<_>>();
+
+        let valid = match self.table_of(parent) {
+            Some(PartitionTable::Mbr) => match partition.mbr_variant {
+                // Logical partitions are numbered by their position in the chain of EBRs.
+                PartitionType::Logical => {
+                    let logicals = siblings
+                        .iter()
+                        .filter(|sibling| sibling.mbr_variant == PartitionType::Logical)
+                        .count();
+                    number == 5 + logicals as u32
+                }
+                _ => number >= 1 && number <= 4,
+            },
+            Some(PartitionTable::Guid) => {
+                // The health of the old table does not apply to a table which is to be written.
+                let written =
+                    !queued.tables.contains_key(parent) && !queued.conversions.contains_key(parent);
+                let entries = devices
+                    .table_health
+                    .get(parent)
+                    .filter(|_| written)
+                    .map_or(GPT_ENTRIES, |health| health.entries);
+
+                number >= 1 && number <= entries
+            }
+            None => return Err(Error::TableNotFound),
+        };
+
+        if !valid || siblings.iter().any(|sibling| sibling.number == number) {
+            return Err(Error::NumberInvalid(number));
+        }
+
+        self.components.queued_changes.partitions[entity].number = number;
+        Ok(())
+    }
+
+    /// Define that the partitions of a GPT table will be renumbered in the order of their first
+    /// sector, once all other changes to the table are applied.
+    ///
+    /// Returns the partitions whose device paths will change, which may need to be updated in
+    /// places such as `/etc/fstab`.
+    ///
+    /// The returned renumbering is computed when this is called, from the partitions which are
+    /// queued at that time. Partitions which are queued to be removed or created afterwards are
+    /// not reflected in it, though they are still sorted when changes are applied, so this
+    /// should be called after every other change to the table has been queued. Partitions which
+    /// are queued to be created are never listed, as they have no device path to change.
+    pub fn sort_partitions(&mut self, entity: DeviceEntity) -> Result<Vec<Renumbered>, Error> {
+        match self.table_of(entity) {
+            Some(PartitionTable::Guid) => (),
+            Some(table) => return Err(Error::SortUnsupported(table)),
+            None => return Err(Error::TableNotFound),
+        }
+
+        let devices = &self.components.devices;
+        let renumbered = match devices.devices.get(entity) {
+            Some(parent) => self
+                .allocations(entity)
+                .iter()
+                .enumerate()
+                .filter_map(|(id, allocation)| {
+                    let partition = devices.partitions.get(allocation.entity)?;
+                    let number = id as u32 + 1;
+                    if partition.number == number {
+                        return None;
+                    }
+
+                    Some(Renumbered {
+                        entity: allocation.entity,
+                        from:   devices.devices[allocation.entity].path.clone(),
+                        to:     crate::systems::partition_device(parent, number).1,
+                    })
+                })
+                .collect(),
+            // Partitions on a table which has yet to be created have no paths to change.
+            None => Vec::new(),
+        };
+
+        self.components.queued_changes.sorts.insert(entity, ());
+        self.flags |= ManagerFlags::CREATE;
+
+        Ok(renumbered)
+    }
+
     /// Define that a new volume group is to be created
     pub fn volume_group_create(
         &mut self,
@@ -367,7 +723,7 @@ impl DiskManager {
 
     fn assert_not_creating_table_on(&self, device: DeviceEntity) {
         debug_assert!(
-            self.components.queued_changes.tables.contains_key(device),
+            !self.components.queued_changes.tables.contains_key(device),
             "attempted to create a file system on a device that is marked for creation of a \
              partition table"
         );
@@ -375,7 +731,7 @@ impl DiskManager {
 
     fn assert_not_creating_partition_on(&self, device: DeviceEntity) {
         debug_assert!(
-            self.components.queued_changes.partitions.contains_key(device),
+            !self.components.queued_changes.partitions.contains_key(device),
             "attempted to create a partition on a device that was marked to be formatted"
         );
     }
@@ -390,68 +746,108 @@ impl DiskManager {
         start: Sector,
         end: Sector,
     ) -> Result<(u64, u64), Error> {
-        let entities = &self.entities.devices;
-
-        fn validate(
-            partdev: &Device,
-            partition: &Partition,
-            offset: u64,
-            end: u64,
-        ) -> Result<(), Error> {
+        fn validate(allocation: &Allocation, offset: u64, end: u64) -> Result<(), Error> {
             // The end of the new partition is before the start of the current.
-            let before = || end < partition.offset;
+            let before = || end <= allocation.offset;
 
             // The start of the new partition is after the end of the current.
-            let after = || offset > partition.offset + partdev.sectors;
+            let after = || offset >= allocation.end();
+
+            // Logical partitions are created within the extended partition.
+            let within_extended = || {
+                allocation.variant == PartitionType::Extended
+                    && offset > allocation.offset
+                    && end <= allocation.end()
+            };
 
-            if before() || after() {
+            if before() || after() || within_extended() {
                 Ok(())
             } else {
                 Err(Error::PartitionOverlap)
             }
         }
 
-        // For all children of the device, guarantee that there is no overlap.
-        match self.components.devices.children.get(parent) {
-            Some(children) => {
-                let offset = device.get_sector(start);
-                let end = device.get_sector(end);
+        // Only devices which support children may be partitioned.
+        if !self.components.devices.children.contains_key(parent) {
+            return Err(Error::NotPartitionable);
+        }
+
+        // Free percentages are measured within the free region that the partition starts in.
+        let free = self
+            .layout(parent)
+            .into_iter()
+            .filter(|segment| segment.kind == SegmentKind::Free)
+            .collect::<Vec<_>>();
 
-                // The start sector must be less than end sector.
-                if offset >= end {
-                    return Err(Error::InputsInverted);
-                }
+        let first = free.first().map_or(0..0, |segment| segment.start..segment.end());
+        let offset = device.get_sector_within(start, first).map_err(Error::Sector)?;
+        let region = free
+            .iter()
+            .find(|segment| segment.start <= offset && offset < segment.end())
+            .map_or(offset..offset, |segment| segment.start..segment.end());
+        let end = device.get_sector_within(end, region).map_err(Error::Sector)?;
 
-                // The end sector must also exist on the device.
-                if device.sectors < end {
-                    return Err(Error::ExceedsDevice);
-                }
+        let (offset, end) = self.align_sectors(parent, device, offset, end);
 
-                // Check if there is any overlap, ignoring children marked for removal.
-                for &child in children {
-                    let child_flags = entities[child];
-                    if !child_flags.contains(EntityFlags::REMOVE) {
-                        let partdev = &self.components.devices.devices[child];
-                        let partition = &self.components.devices.partitions[child];
-                        validate(partdev, partition, offset, end)?;
-                    }
-                }
+        // The start sector must be less than end sector.
+        if offset >= end {
+            return Err(Error::InputsInverted);
+        }
 
-                // Check if there is an overlap with other partitions queued to be created.
-                for (child, &cparent) in self.components.queued_changes.parents.iter() {
-                    if cparent == parent {
-                        let partdev = &self.components.queued_changes.devices[child];
-                        let partition = &self.components.queued_changes.partitions[child];
-                        validate(partdev, partition, offset, end)?;
-                    }
-                }
+        // The end sector must also exist on the device.
+        if device.sectors < end {
+            return Err(Error::ExceedsDevice);
+        }
+
+        // Check for overlap with existing and queued partitions, ignoring those being removed.
+        for allocation in self.allocations(parent) {
+            validate(&allocation, offset, end)?;
+        }
+
+        Ok((offset, end - offset))
+    }
 
-                Ok((offset, end - offset))
+    /// Snaps the sectors of a new partition to the optimal alignment of its parent device,
+    /// if the device needs it, and the alignment policy allows it.
+    pub(crate) fn align_sectors(
+        &self,
+        parent: DeviceEntity,
+        device: &Device,
+        offset: u64,
+        end: u64,
+    ) -> (u64, u64) {
+        if self.alignment == AlignmentPolicy::Off {
+            return (offset, end);
+        }
+
+        match self.components.devices.topologies.get(parent) {
+            Some(topology) if topology.needs_alignment(device) => {
+                (topology.align_up(device, offset), topology.align_down(device, end))
             }
-            None => Err(Error::NotPartitionable),
+            _ => (offset, end),
         }
     }
 
+    /// The partition table on a device, or the table that is queued to be created on it.
+    pub(crate) fn table_of(&self, entity: DeviceEntity) -> Option<PartitionTable> {
+        let queued = &self.components.queued_changes;
+        queued
+            .tables
+            .get(entity)
+            .or_else(|| queued.conversions.get(entity))
+            .or_else(|| self.components.devices.tables.get(entity))
+            .cloned()
+    }
+
+    /// Checks if the sectors lie within an existing or queued extended partition of the parent.
+    fn within_extended(&self, parent: DeviceEntity, offset: u64, end: u64) -> bool {
+        self.allocations(parent).iter().any(|allocation| {
+            allocation.variant == PartitionType::Extended
+                && offset > allocation.offset
+                && end <= allocation.end()
+        })
+    }
+
     fn can_create_on_vg(
         &self,
         entity: VgEntity,
@@ -459,7 +855,8 @@ impl DiskManager {
         length: Sector,
     ) -> Result<u64, Error> {
         let queued = &self.components.queued_changes;
-        let length = parent.get_sector(length);
+        let length =
+            parent.get_sector_within(length, 0..parent.sectors_free()).map_err(Error::Sector)?;
 
         // Other LVs may be queued for addition, so we will also consider their lengths.
         let adding: u64 = queued
@@ -479,23 +876,54 @@ impl DiskManager {
         }
     }
 
+    /// Rejects file systems which cannot be created with the given options, or size in bytes.
+    fn validate_format(&self, what: &PartitionCreate, size: u64) -> Result<(), Error> {
+        let fs = match what {
+            // LUKS devices are created with `PartitionCreate::Luks`, which holds their parameters.
+            PartitionCreate::Plain(FileSystem::Luks, _) => {
+                return Err(Error::FormatUnsupported(FileSystem::Luks))
+            }
+            PartitionCreate::Plain(fs, options) => {
+                options.validate(*fs).map_err(|why| Error::FormatOptions(*fs, why))?;
+                *fs
+            }
+            PartitionCreate::Luks(_) => FileSystem::Luks,
+            PartitionCreate::Extended => return Ok(()),
+        };
+
+        if !fs.capabilities().creatable {
+            return Err(Error::FormatUnsupported(fs));
+        }
+
+        match fs.validate_size(size) {
+            Err(PartitionSizeError::TooSmall(_, min)) => return Err(Error::TooSmall(fs, min)),
+            Err(PartitionSizeError::TooLarge(_, max)) => return Err(Error::TooLarge(fs, max)),
+            Ok(()) => (),
+        }
+
+        self.tool_available(fs, tools::Operation::Create).map_err(|why| Error::Unavailable(fs, why))
+    }
+
     fn create_partition<F: FnOnce(&mut Self) -> Box<Path>>(
         &mut self,
         what: PartitionCreate,
         entity: DeviceEntity,
         label: Option<Box<str>>,
+        kind: Option<PartitionKind>,
         offset: u64,
         sectors: u64,
         logical_sector_size: u64,
         physical_sector_size: u64,
+        mbr_variant: PartitionType,
         lvm_path: F,
     ) {
         // Followed by the partition component.
-        let mut partition = Partition { partlabel: label, offset, ..Default::default() };
+        let mut partition =
+            Partition { partlabel: label, kind, offset, mbr_variant, ..Default::default() };
 
         // Are you are a LUKS device, or a plain-old-filesystem?
         match what {
-            PartitionCreate::Plain(filesystem) => {
+            PartitionCreate::Plain(filesystem, options) => {
                 // Mark the device as a future PV if it is LVM.
                 if let FileSystem::Lvm = filesystem {
                     let path: Box<Path> = lvm_path(self);
@@ -509,6 +937,7 @@ impl DiskManager {
                 }
 
                 partition.filesystem = Some(filesystem);
+                self.components.queued_changes.formats.insert(entity, (filesystem, options));
             }
             PartitionCreate::Luks(luks) => {
                 // Specify that the partition is a LUKS device, and create a new device entity
@@ -518,6 +947,8 @@ impl DiskManager {
                 let child = self.entities.devices.insert(flags);
                 self.components.queued_changes.luks.insert(entity, (child, luks));
             }
+            // Extended partitions only contain logical partitions.
+            PartitionCreate::Extended => (),
         }
 
         let queued = &mut self.components.queued_changes;
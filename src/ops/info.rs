@@ -1,5 +1,6 @@
 /// ! Methods for fetching information in the world.
 use crate::*;
+use disk_ops::table::Gpt;
 
 impl DiskManager {
     // If the device is a loopback, this will kdisplay the backing file.
@@ -36,6 +37,29 @@ impl DiskManager {
         self.components.devices.devices.iter()
     }
 
+    /// Reads the partition table of a device, as it currently exists on the disk, into a dump.
+    ///
+    /// Returns `None` if the device does not have a partition table.
+    pub fn dump_table(&self, entity: DeviceEntity) -> Option<Result<TableDump, PartitionError>> {
+        let table = *self.components.devices.tables.get(entity)?;
+        Some(disk_ops::table::dump(self.device(entity).path(), table))
+    }
+
+    /// The number of sectors that a device has grown by, beyond the end of its GPT table.
+    ///
+    /// Returns `None` if the device does not have a GPT table.
+    pub fn table_growth(&self, entity: DeviceEntity) -> Option<Result<u64, PartitionError>> {
+        match self.components.devices.tables.get(entity) {
+            Some(PartitionTable::Guid) => Some(Gpt::growth(self.device(entity).path())),
+            _ => None,
+        }
+    }
+
+    /// EFI system partitions, as identified by their partition type.
+    pub fn esp_partitions<'a>(&'a self) -> impl Iterator<Item = DeviceEntity> + 'a {
+        self.partitions_of_kind(PartitionKind::EfiSystem)
+    }
+
     /// If the device is a disk, information about that disk can be retrieved here.
     pub fn disk(&self, entity: DeviceEntity) -> Option<&Disk> {
         self.components.devices.disks.get(entity)
@@ -51,6 +75,31 @@ impl DiskManager {
         self.components.devices.disks.iter()
     }
 
+    /// Partitions which contain the second stage of GRUB for BIOS boots on GPT disks.
+    pub fn is_bios_boot(&self, entity: DeviceEntity) -> bool {
+        self.is_kind(entity, PartitionKind::BiosBoot)
+    }
+
+    /// Partitions which are EFI system partitions.
+    pub fn is_esp(&self, entity: DeviceEntity) -> bool {
+        self.is_kind(entity, PartitionKind::EfiSystem)
+    }
+
+    /// Checks if a partition was recorded with the given partition type.
+    pub fn is_kind(&self, entity: DeviceEntity, kind: PartitionKind) -> bool {
+        self.partition_kind(entity) == Some(kind)
+    }
+
+    /// Partitions which are Microsoft reserved partitions.
+    pub fn is_msr(&self, entity: DeviceEntity) -> bool {
+        self.is_kind(entity, PartitionKind::MicrosoftReserved)
+    }
+
+    /// Partitions which contain the Windows recovery environment.
+    pub fn is_recovery(&self, entity: DeviceEntity) -> bool {
+        self.is_kind(entity, PartitionKind::WindowsRecovery)
+    }
+
     /// For LV devices which are associated with a VG.
     pub fn lv(&self, entity: DeviceEntity) -> Option<&(LvmLv, VgEntity)> {
         self.components.devices.lvs.get(entity)
@@ -76,6 +125,35 @@ impl DiskManager {
         })
     }
 
+    /// If the device is an extended partition, this will return its logical partitions.
+    pub fn logical_partitions<'a>(
+        &'a self,
+        entity: DeviceEntity,
+    ) -> impl Iterator<Item = DeviceEntity> + 'a {
+        let devices = &self.components.devices;
+
+        let extended = devices
+            .partitions
+            .get(entity)
+            .filter(|partition| partition.mbr_variant == PartitionType::Extended)
+            .map(|partition| {
+                (partition.offset, partition.offset + devices.devices[entity].sectors)
+            });
+
+        self.parents(entity)
+            .filter_map(move |parent| devices.children.get(parent))
+            .flatten()
+            .cloned()
+            .filter(move |&child| match (extended, devices.partitions.get(child)) {
+                (Some((start, end)), Some(partition)) => {
+                    partition.mbr_variant == PartitionType::Logical
+                        && partition.offset > start
+                        && partition.offset < end
+                }
+                _ => false,
+            })
+    }
+
     pub fn lvm_volume_group(&self, name: &str) -> Option<(VgEntity, &LvmVg)> {
         self.components.vgs.volume_groups.iter().find(|(_, vg)| vg.name.as_ref() == name)
     }
@@ -116,11 +194,29 @@ impl DiskManager {
         self.components.devices.partitions.get(entity)
     }
 
+    /// The programs and kernel support which were found on the host, when disks were scanned.
+    pub fn tool_support(&self) -> Option<&tools::SupportMatrix> { self.tools.as_ref() }
+
+    /// The partition type of a partition, if it is a well-known type.
+    pub fn partition_kind(&self, entity: DeviceEntity) -> Option<PartitionKind> {
+        self.partition(entity).and_then(|partition| partition.kind)
+    }
+
     /// Some device entities are partitions.
     pub fn partitions<'a>(&'a self) -> impl Iterator<Item = (DeviceEntity, &'a Partition)> + 'a {
         self.components.devices.partitions.iter()
     }
 
+    /// Partitions which were recorded with the given partition type.
+    pub fn partitions_of_kind<'a>(
+        &'a self,
+        kind: PartitionKind,
+    ) -> impl Iterator<Item = DeviceEntity> + 'a {
+        self.partitions()
+            .filter(move |(_, partition)| partition.kind == Some(kind))
+            .map(|(entity, _)| entity)
+    }
+
     /// For PVs which may be associated with a VG.
     pub fn pv<'b>(&'b self, entity: DeviceEntity) -> Option<(&'b LvmPv, Option<&'b LvmVg>)> {
         self.components.devices.pvs.get(entity).map(|(pv, vg_entity)| {
@@ -129,22 +225,28 @@ impl DiskManager {
         })
     }
 
-    /// Checks if the given sector is allocated in the partition table of the device.
+    /// Checks if the given sector is allocated to a partition of the device, once queued
+    /// changes are applied.
     ///
     /// # Notes
     ///
     /// If the device does not support children, `false` is returned.
     pub fn sector_overlaps(&self, entity: DeviceEntity, sector: u64) -> bool {
-        let devices = &self.components.devices.devices;
-        let partitions = &self.components.devices.partitions;
+        self.allocations(entity)
+            .iter()
+            .any(|allocation| sector >= allocation.offset && sector < allocation.end())
+    }
 
-        self.components.devices.children.get(entity).map_or(false, |children| {
-            children.iter().any(|&child| {
-                let device = &devices[child];
-                let partition = &partitions[child];
+    /// The integrity of the GPT table of a device, as it was when the device was scanned.
+    pub fn table_health(&self, entity: DeviceEntity) -> Option<&GptHealth> {
+        self.components.devices.table_health.get(entity)
+    }
 
-                sector >= partition.offset && sector <= device.sectors
-            })
-        })
+    /// The I/O topology of a whole device, if it was probed.
+    pub fn topology(&self, entity: DeviceEntity) -> Option<&Topology> {
+        self.components.devices.topologies.get(entity)
     }
+
+    /// UUIDs which were found on more than one device, when devices were last scanned.
+    pub fn uuid_collisions(&self) -> &[UuidCollision] { &self.components.devices.uuid_collisions }
 }
@@ -0,0 +1,269 @@
+/// ! Methods for describing how the space of a device is laid out.
+use crate::*;
+use disk_ops::table::usable_sectors;
+
+/// What a segment of a device is occupied by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SegmentKind {
+    /// Unallocated space, where a partition or logical volume may be created.
+    Free,
+    /// Space that is reserved for partition table metadata and alignment.
+    Reserved,
+    /// A partition or logical volume, which may be queued to be created.
+    Used(DeviceEntity),
+}
+
+/// A contiguous range of sectors on a device, or of extents on a volume group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Segment {
+    pub kind:   SegmentKind,
+    /// The first sector, or extent, of the segment.
+    pub start:  u64,
+    /// The number of sectors, or extents, in the segment.
+    pub length: u64,
+}
+
+impl Segment {
+    /// The sector, or extent, after the last one in the segment.
+    pub fn end(&self) -> u64 { self.start + self.length }
+}
+
+/// A partition on a device, with the sectors that it will have once changes are applied.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Allocation {
+    pub entity:  DeviceEntity,
+    pub offset:  u64,
+    pub sectors: u64,
+    pub variant: PartitionType,
+}
+
+impl Allocation {
+    pub fn end(&self) -> u64 { self.offset + self.sectors }
+}
+
+impl DiskManager {
+    /// The ordered segments of a device, as they will be once queued changes are applied.
+    ///
+    /// On a device with a partition table, partitions are listed with the unallocated gaps
+    /// between them, and the sectors at either end of the device are reserved for the headers
+    /// of the table. Logical partitions are found in the layout of their extended partition.
+    ///
+    /// Devices without a partition table consist of a single segment.
+    pub fn layout(&self, entity: DeviceEntity) -> Vec<Segment> {
+        let device = self.device_or_queued(entity);
+        let table = self.table_of(entity);
+
+        let extended = self
+            .components
+            .queued_changes
+            .partitions
+            .get(entity)
+            .or_else(|| self.components.devices.partitions.get(entity))
+            .filter(|partition| partition.mbr_variant == PartitionType::Extended)
+            .map(|partition| partition.offset);
+
+        let (start, end, head, tail, allocations) = if let Some(offset) = extended {
+            // Each logical partition is preceded by an extended boot record.
+            let end = offset + device.sectors;
+            let logical = self
+                .parents(entity)
+                .flat_map(|parent| self.allocations(parent))
+                .filter(|allocation| {
+                    allocation.variant == PartitionType::Logical
+                        && allocation.offset > offset
+                        && allocation.end() <= end
+                })
+                .collect::<Vec<_>>();
+
+            (offset, end, offset + 1, end, logical)
+        } else if let Some(table) = table {
+            let primary = self
+                .allocations(entity)
+                .into_iter()
+                .filter(|allocation| allocation.variant != PartitionType::Logical)
+                .collect::<Vec<_>>();
+
+            // Free space begins and ends on the 1 MiB boundaries within the sectors that the
+            // table leaves usable. Devices too small to hold its headers have no free space.
+            let align = (1024 * 1024 / device.logical_sector_size).max(1);
+            let (first, last) = usable_sectors(table, device.sectors, device.logical_sector_size);
+            let head = ((first + align - 1) / align * align).min(device.sectors);
+            let tail = ((last + 1) / align * align).max(head);
+            (0, device.sectors, head, tail, primary)
+        } else {
+            let has_fs = self.partition(entity).map_or(false, |p| p.filesystem.is_some())
+                || self.components.queued_changes.partitions.contains_key(entity);
+
+            let kind = if has_fs { SegmentKind::Used(entity) } else { SegmentKind::Free };
+            return vec![Segment { kind, start: 0, length: device.sectors }];
+        };
+
+        let mut segments = Vec::new();
+        let gap = |segments: &mut Vec<Segment>, from: u64, to: u64| {
+            let ranges = [
+                (SegmentKind::Reserved, from, to.min(head)),
+                (SegmentKind::Free, from.max(head), to.min(tail)),
+                (SegmentKind::Reserved, from.max(tail), to),
+            ];
+
+            for &(kind, start, end) in &ranges {
+                if start < end {
+                    segments.push(Segment { kind, start, length: end - start });
+                }
+            }
+        };
+
+        let mut cursor = start;
+        for allocation in allocations {
+            if allocation.offset > cursor {
+                gap(&mut segments, cursor, allocation.offset);
+            }
+
+            segments.push(Segment {
+                kind:   SegmentKind::Used(allocation.entity),
+                start:  allocation.offset,
+                length: allocation.sectors,
+            });
+
+            cursor = cursor.max(allocation.end());
+        }
+
+        gap(&mut segments, cursor, end);
+        segments
+    }
+
+    /// Unallocated regions of a device where partitions may be created, once queued changes
+    /// are applied.
+    ///
+    /// Regions are shrunk to the optimal alignment of the device, if the alignment policy
+    /// allows it.
+    pub fn free_regions(&self, entity: DeviceEntity) -> Vec<Segment> {
+        let device = self.device_or_queued(entity);
+        let parent = self.parents(entity).next().unwrap_or(entity);
+
+        self.layout(entity)
+            .into_iter()
+            .filter(|segment| segment.kind == SegmentKind::Free)
+            .filter_map(|segment| {
+                let (start, end) = self.align_sectors(parent, device, segment.start, segment.end());
+                if start < end {
+                    Some(Segment { kind: SegmentKind::Free, start, length: end - start })
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// The segments of a volume group, in extents, as they will be once queued changes are
+    /// applied.
+    ///
+    /// Logical volumes are not required to be contiguous, so each is given as a single segment
+    /// of its total extents, in sequence, followed by the free extents of the group.
+    pub fn volume_group_layout(&self, entity: VgEntity) -> Vec<Segment> {
+        let queued = &self.components.queued_changes;
+        let vg = match queued
+            .volume_groups
+            .get(entity)
+            .or_else(|| self.components.vgs.volume_groups.get(entity))
+        {
+            Some(vg) => vg,
+            None => return Vec::new(),
+        };
+
+        let existing = self
+            .components
+            .devices
+            .lvs
+            .iter()
+            .filter(|&(lv, &(_, parent))| {
+                parent == entity && !self.entities.devices[lv].contains(EntityFlags::REMOVE)
+            })
+            .map(|(lv, _)| (lv, &self.components.devices.devices[lv]));
+
+        let adding = queued
+            .lvs
+            .iter()
+            .filter(|&(_, &(_, parent))| parent == entity)
+            .map(|(lv, _)| (lv, &queued.devices[lv]));
+
+        let mut segments = Vec::new();
+        let mut cursor = 0;
+        for (lv, device) in existing.chain(adding) {
+            let bytes = device.sectors * device.logical_sector_size;
+            let length = (bytes + vg.extent_size - 1) / vg.extent_size;
+            segments.push(Segment { kind: SegmentKind::Used(lv), start: cursor, length });
+            cursor += length;
+        }
+
+        if cursor < vg.extents {
+            segments.push(Segment {
+                kind:   SegmentKind::Free,
+                start:  cursor,
+                length: vg.extents - cursor,
+            });
+        }
+
+        segments
+    }
+
+    /// Unallocated extents of a volume group, once queued changes are applied.
+    pub fn volume_group_free_regions(&self, entity: VgEntity) -> Vec<Segment> {
+        self.volume_group_layout(entity)
+            .into_iter()
+            .filter(|segment| segment.kind == SegmentKind::Free)
+            .collect()
+    }
+
+    /// Partitions of a device, sorted by their offset, with queued changes applied.
+    ///
+    /// Partitions marked for removal are excluded, and those queued to be created, resized or
+    /// moved are given with their new sectors.
+    pub(crate) fn allocations(&self, parent: DeviceEntity) -> Vec<Allocation> {
+        let devices = &self.components.devices;
+        let queued = &self.components.queued_changes;
+
+        let existing = devices
+            .children
+            .get(parent)
+            .into_iter()
+            .flatten()
+            .filter(|&&child| !self.entities.devices[child].contains(EntityFlags::REMOVE))
+            .filter_map(|&child| {
+                let partition = devices.partitions.get(child)?;
+                let sectors = devices.devices[child].sectors;
+                let (offset, sectors) = match (queued.resize.get(child), queued.moves.get(child)) {
+                    (Some(&resized), _) => resized,
+                    (None, Some(&moved)) => (moved, sectors),
+                    (None, None) => (partition.offset, sectors),
+                };
+
+                Some(Allocation { entity: child, offset, sectors, variant: partition.mbr_variant })
+            });
+
+        let adding =
+            queued.parents.iter().filter(|&(_, &cparent)| cparent == parent).map(|(child, _)| {
+                let partition = &queued.partitions[child];
+                Allocation {
+                    entity:  child,
+                    offset:  partition.offset,
+                    sectors: queued.devices[child].sectors,
+                    variant: partition.mbr_variant,
+                }
+            });
+
+        let mut allocations = existing.chain(adding).collect::<Vec<_>>();
+        allocations.sort_by_key(|allocation| allocation.offset);
+        allocations
+    }
+
+    /// A device, or the device which is queued to be created.
+    fn device_or_queued(&self, entity: DeviceEntity) -> &Device {
+        self.components
+            .queued_changes
+            .devices
+            .get(entity)
+            .or_else(|| self.components.devices.devices.get(entity))
+            .expect("invalid device entity; report this as a bug")
+    }
+}
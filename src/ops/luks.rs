@@ -3,12 +3,12 @@
 // TODO: Use the cryptsetup bindings instead of the cryptsetup binary.
 
 use crate::{DeviceEntity, DiskManager};
+use disk_ops::command::{run_with_input, CommandError};
 use disk_types::LuksPassphrase;
 use secstr::SecStr;
 use std::{
-    io::{self, Write},
     path::Path,
-    process::{Command, ExitStatus, Stdio},
+    process::{Command, Stdio},
 };
 
 impl DiskManager {
@@ -24,18 +24,6 @@ impl DiskManager {
 #[error(display = "failed to execute cryptsetup command")]
 pub struct Error(#[error(cause)] CommandError);
 
-#[derive(Debug, Error)]
-pub enum CommandError {
-    #[error(display = "command exited with failure status: {}", 0)]
-    ExitStatus(ExitStatus),
-    #[error(display = "failed to write to the stdin of the child process")]
-    StdinWrite(#[error(cause)] io::Error),
-    #[error(display = "failed to spawn child process")]
-    Spawn(#[error(cause)] io::Error),
-    #[error(display = "failed to wait on child process")]
-    Wait(#[error(cause)] io::Error),
-}
-
 #[derive(Debug)]
 pub struct LuksParams {
     pub key_size:    u16,
@@ -69,29 +57,8 @@ pub fn deactivate(device: &Path) -> Result<(), Error> {
 }
 
 fn exec(cmd: &mut Command, passphrase: Option<&LuksPassphrase>) -> Result<(), CommandError> {
-    let mut child = cmd
-        .stdin(if passphrase.is_some() { Stdio::piped() } else { Stdio::null() })
-        .stdout(Stdio::null())
-        .spawn()
-        .map_err(CommandError::Spawn)?;
-
-    if let Some(passphrase) = passphrase {
-        let appended = append_newline(passphrase.as_ref());
-        child
-            .stdin
-            .as_mut()
-            .expect("stdin not obtained")
-            .write_all(appended.unsecure())
-            .map_err(CommandError::StdinWrite)?;
-    }
-
-    let status = child.wait().map_err(CommandError::Wait)?;
-
-    if status.success() {
-        Ok(())
-    } else {
-        Err(CommandError::ExitStatus(status))
-    }
+    let appended = passphrase.map(|passphrase| append_newline(passphrase.as_ref()));
+    run_with_input(cmd.stdout(Stdio::null()), appended.as_ref().map(SecStr::unsecure))
 }
 
 fn append_newline(input: &SecStr) -> SecStr {
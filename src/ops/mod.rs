@@ -1,18 +1,9 @@
+pub mod check;
 pub mod create;
 pub mod info;
+pub mod layout;
 pub mod luks;
 pub mod modify;
+pub mod resize;
 
-use std::{io, process::ExitStatus};
-
-#[derive(Debug, Error)]
-pub enum CommandError {
-    #[error(display = "command exited with failure status: {}", 0)]
-    ExitStatis(ExitStatus),
-    #[error(display = "failed to write to the stdin of the child process")]
-    StdinWrite(#[error(cause)] io::Error),
-    #[error(display = "failed to spawn child process")]
-    Spawn(#[error(cause)] io::Error),
-    #[error(display = "failed to wait on child process")]
-    Wait(#[error(cause)] io::Error),
-}
+pub use disk_ops::command::CommandError;
@@ -1,11 +1,151 @@
 /// ! Miscellanious methods for modifying entities in the world.
 use crate::*;
 
+/// An error that may occur when adding modification operations to the queue.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Error)]
+pub enum Error {
+    #[error(display = "{} file systems cannot be labeled", _0)]
+    LabelUnsupported(FileSystem),
+    #[error(display = "{} file system labels may not be longer than {}", _0, _1)]
+    LabelTooLong(FileSystem, usize),
+    #[error(display = "device does not have a file system")]
+    NoFileSystem,
+    #[error(display = "device is not a partition on a partition table")]
+    NotPartition,
+    #[error(display = "{:?} partition tables do not support partition labels", _0)]
+    PartLabelUnsupported(PartitionTable),
+    #[error(display = "{:?} partition tables derive PARTUUIDs from the disk signature", _0)]
+    PartUuidUnsupported(PartitionTable),
+    #[error(display = "a seeded PARTUUID would be the same as that of the partition's clone")]
+    PartUuidSeeded,
+    #[error(display = "cannot modify {} file systems on this host: {}", _0, _1)]
+    Unavailable(FileSystem, tools::Unavailable),
+    #[error(display = "the UUID of {} file systems cannot be changed", _0)]
+    UuidUnsupported(FileSystem),
+}
+
 impl DiskManager {
-    /// Sets the label of a partition.
-    pub fn label<S: Into<Box<str>>>(&mut self, entity: DeviceEntity, label: S) {
+    /// Sets the label of a partition in its GPT partition table.
+    ///
+    /// Partitions on MBR tables have no labels, and are rejected.
+    pub fn label<S: Into<Box<str>>>(
+        &mut self,
+        entity: DeviceEntity,
+        label: S,
+    ) -> Result<(), Error> {
+        let table = self.table_of_partition(entity)?;
+        if table == PartitionTable::Mbr {
+            return Err(Error::PartLabelUnsupported(table));
+        }
+
         self.components.queued_changes.labels.insert(entity, label.into());
         self.flags |= ManagerFlags::LABEL;
+
+        Ok(())
+    }
+
+    /// Sets the label of the file system on a device, which may be any device with a file system.
+    ///
+    /// If the file system is queued to be created, the label is created along with it.
+    pub fn fs_label<S: Into<Box<str>>>(
+        &mut self,
+        entity: DeviceEntity,
+        label: S,
+    ) -> Result<(), Error> {
+        let label = label.into();
+        let fs = self.filesystem_of(entity)?;
+
+        let max = fs.capabilities().max_label_len;
+        if max == 0 {
+            return Err(Error::LabelUnsupported(fs));
+        } else if fs.label_len(&label) > max {
+            return Err(Error::LabelTooLong(fs, max));
+        }
+
+        // File systems which are to be created are labeled by their mkfs tool.
+        if !self.components.queued_changes.formats.contains_key(entity) {
+            self.tool_available(fs, tools::Operation::Label)
+                .map_err(|why| Error::Unavailable(fs, why))?;
+        }
+
+        let queued = &mut self.components.queued_changes;
+        match queued.formats.get_mut(entity) {
+            Some((_, options)) => options.label = Some(label),
+            None => {
+                queued.fs_labels.insert(entity, label);
+                self.flags |= ManagerFlags::LABEL;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Gives the file system on a device a new, random UUID, such as after a disk was cloned.
+    ///
+    /// File systems which are queued to be created will have a new UUID regardless.
+    pub fn regenerate_uuid(&mut self, entity: DeviceEntity) -> Result<(), Error> {
+        let fs = self.filesystem_of(entity)?;
+        if !fs.capabilities().uuid_settable {
+            return Err(Error::UuidUnsupported(fs));
+        }
+
+        if self.components.queued_changes.formats.contains_key(entity) {
+            return Ok(());
+        }
+
+        self.tool_available(fs, tools::Operation::Uuid)
+            .map_err(|why| Error::Unavailable(fs, why))?;
+        self.components.queued_changes.fs_uuids.insert(entity, ());
+        self.flags |= ManagerFlags::UUIDS;
+
+        Ok(())
+    }
+
+    /// Gives a partition on a GPT table a new PARTUUID, which is chosen by the `uuid` policy.
+    ///
+    /// `UuidPolicy::Seeded` is rejected, as it derives the PARTUUID from the type and position of
+    /// the partition, which a clone of the partition shares.
+    pub fn regenerate_partuuid(
+        &mut self,
+        entity: DeviceEntity,
+        uuid: UuidPolicy,
+    ) -> Result<(), Error> {
+        if let UuidPolicy::Seeded(_) = uuid {
+            return Err(Error::PartUuidSeeded);
+        }
+
+        let devices = &self.components.devices;
+        let table = self
+            .parents(entity)
+            .find_map(|parent| devices.tables.get(parent))
+            .ok_or(Error::NotPartition)?;
+
+        if *table == PartitionTable::Mbr {
+            return Err(Error::PartUuidUnsupported(*table));
+        }
+
+        let partition = devices.partitions.get(entity).ok_or(Error::NotPartition)?;
+        let kind =
+            partition.kind.unwrap_or_else(|| PartitionKind::from_filesystem(partition.filesystem));
+        let partuuid = uuid.partition_uuid(kind, partition.offset);
+
+        self.components.queued_changes.partuuids.insert(entity, partuuid);
+        self.flags |= ManagerFlags::UUIDS;
+
+        Ok(())
+    }
+
+    /// Sets the attribute bits of a partition.
+    pub fn set_attributes(
+        &mut self,
+        entity: DeviceEntity,
+        attributes: PartitionAttributes,
+    ) -> Result<(), Error> {
+        self.table_of_partition(entity)?;
+        self.components.queued_changes.attributes.insert(entity, attributes);
+        self.flags |= ManagerFlags::ATTRIBUTES;
+
+        Ok(())
     }
 
     /// Marks the entity for removal, along with all of its children, and their children.
@@ -24,6 +164,36 @@ impl DiskManager {
         }
 
         recurse(&mut self.entities.devices, &self.components.devices.children, entity);
+
+        // Logical partitions are removed along with their extended partition.
+        for logical in self.logical_partitions(entity).collect::<Vec<_>>() {
+            self.entities.devices[logical] |= EntityFlags::REMOVE;
+        }
+
         self.flags |= ManagerFlags::REMOVE;
     }
+
+    /// The table of a partition, or of a partition which is queued to be created.
+    fn table_of_partition(&self, entity: DeviceEntity) -> Result<PartitionTable, Error> {
+        let queued_parent = self.components.queued_changes.parents.get(entity).cloned();
+        queued_parent
+            .into_iter()
+            .chain(self.parents(entity))
+            .find_map(|parent| self.table_of(parent))
+            .ok_or(Error::NotPartition)
+    }
+
+    /// The file system which a device has, or will have once queued changes are applied.
+    fn filesystem_of(&self, entity: DeviceEntity) -> Result<FileSystem, Error> {
+        match self.components.queued_changes.formats.get(entity) {
+            Some(&(fs, _)) => Ok(fs),
+            None => self
+                .components
+                .devices
+                .partitions
+                .get(entity)
+                .and_then(|partition| partition.filesystem)
+                .ok_or(Error::NoFileSystem),
+        }
+    }
 }
@@ -0,0 +1,180 @@
+/// ! Methods for resizing and moving partitions in the world.
+use crate::*;
+
+/// An error that may occur when adding resize or move operations to the queue.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Error)]
+pub enum Error {
+    #[error(display = "the partition would exceed the size of the parent device")]
+    ExceedsDevice,
+    #[error(display = "{} file systems cannot be grown", _0)]
+    GrowUnsupported(FileSystem),
+    #[error(display = "the end sector lies before the start sector")]
+    InputsInverted,
+    #[error(display = "a partition cannot be moved and resized at the same time")]
+    MoveAndResize,
+    #[error(display = "device is not a partition on a partition table")]
+    NotPartition,
+    #[error(display = "the partition would overlap an existing partition")]
+    PartitionOverlap,
+    #[error(display = "the sector could not be calculated: {:?}", _0)]
+    Sector(SectorError),
+    #[error(display = "{} file systems cannot be shrunk", _0)]
+    ShrinkUnsupported(FileSystem),
+    #[error(display = "the start of a partition with a file system cannot be moved")]
+    StartMoved,
+    #[error(display = "{} file systems may not be larger than {} bytes", _0, _1)]
+    TooLarge(FileSystem, u64),
+    #[error(display = "{} file systems may not be smaller than {} bytes", _0, _1)]
+    TooSmall(FileSystem, u64),
+    #[error(display = "cannot resize {} file systems on this host: {}", _0, _1)]
+    Unavailable(FileSystem, tools::Unavailable),
+    #[error(display = "{:?} partitions cannot be resized or moved", _0)]
+    VariantUnsupported(PartitionType),
+}
+
+impl DiskManager {
+    /// Define that a partition will be moved to start at the given sector.
+    ///
+    /// The data of the partition is copied to its new location, after which its partition
+    /// table entry is rewritten with the same PARTUUID.
+    pub fn move_partition(&mut self, entity: DeviceEntity, start: Sector) -> Result<(), Error> {
+        if self.components.queued_changes.resize.contains_key(entity) {
+            return Err(Error::MoveAndResize);
+        }
+
+        let parent = self.resizable_parent(entity)?;
+        let parent_device = &self.components.devices.devices[parent];
+        let sectors = self.components.devices.devices[entity].sectors;
+        let start = parent_device.get_sector(start).map_err(Error::Sector)?;
+        let (offset, _) = self.align_sectors(parent, parent_device, start, start + sectors);
+
+        self.validate_sectors(entity, parent, offset, offset + sectors)?;
+
+        self.components.queued_changes.moves.insert(entity, offset);
+        self.flags |= ManagerFlags::RESIZE;
+
+        Ok(())
+    }
+
+    /// Sets a callback which receives the progress of partition moves, as they are applied.
+    ///
+    /// The callback is given the partition being moved, the number of sectors which have been
+    /// copied so far, and the total number of sectors to copy.
+    pub fn on_move_progress<F>(&mut self, func: F)
+    where
+        F: FnMut(DeviceEntity, u64, u64) + Send + 'static,
+    {
+        self.systems.resize.progress = Some(Box::new(func));
+    }
+
+    /// Define that a partition will be resized to the given sectors.
+    ///
+    /// The file system of the partition is resized along with it, so the start of a partition
+    /// may only be moved when it does not have a file system.
+    ///
+    /// Before they are resized, ext file systems are checked in the `ForceRepair` mode, and
+    /// other file systems are checked in the `Repair` mode before they are shrunk. Only errors
+    /// which are safe to repair without asking are repaired, and the resize fails if any others
+    /// remain. Progress is given to the `on_check_progress` callback.
+    pub fn resize(
+        &mut self,
+        entity: DeviceEntity,
+        start: Sector,
+        end: Sector,
+    ) -> Result<(), Error> {
+        if self.components.queued_changes.moves.contains_key(entity) {
+            return Err(Error::MoveAndResize);
+        }
+
+        let parent = self.resizable_parent(entity)?;
+        let parent_device = &self.components.devices.devices[parent];
+        let (offset, end) = self.align_sectors(
+            parent,
+            parent_device,
+            parent_device.get_sector(start).map_err(Error::Sector)?,
+            parent_device.get_sector(end).map_err(Error::Sector)?,
+        );
+
+        self.validate_sectors(entity, parent, offset, end)?;
+
+        let devices = &self.components.devices;
+        let partition = &devices.partitions[entity];
+        let sectors = end - offset;
+        if let Some(fs) = partition.filesystem {
+            if offset != partition.offset {
+                return Err(Error::StartMoved);
+            }
+
+            match fs.validate_size(sectors * parent_device.logical_sector_size) {
+                Err(PartitionSizeError::TooSmall(_, min)) => return Err(Error::TooSmall(fs, min)),
+                Err(PartitionSizeError::TooLarge(_, max)) => return Err(Error::TooLarge(fs, max)),
+                Ok(()) => (),
+            }
+
+            let current = devices.devices[entity].sectors;
+            if sectors < current && !fs.supports_shrinking() {
+                return Err(Error::ShrinkUnsupported(fs));
+            } else if sectors > current && !fs.supports_growing() {
+                return Err(Error::GrowUnsupported(fs));
+            }
+
+            // File systems are checked by the resize system before they are shrunk.
+            let unavailable = |why| Error::Unavailable(fs, why);
+            self.tool_available(fs, tools::Operation::Resize).map_err(unavailable)?;
+            if sectors < current {
+                self.tool_available(fs, tools::Operation::Check).map_err(unavailable)?;
+            }
+        }
+
+        self.components.queued_changes.resize.insert(entity, (offset, sectors));
+        self.flags |= ManagerFlags::RESIZE;
+
+        Ok(())
+    }
+
+    /// The device containing the partition table of a partition which may be resized or moved.
+    fn resizable_parent(&self, entity: DeviceEntity) -> Result<DeviceEntity, Error> {
+        let parent = self
+            .parents(entity)
+            .find(|&parent| self.components.devices.tables.contains_key(parent))
+            .ok_or(Error::NotPartition)?;
+
+        let partition =
+            self.components.devices.partitions.get(entity).ok_or(Error::NotPartition)?;
+        if partition.mbr_variant != PartitionType::Primary {
+            return Err(Error::VariantUnsupported(partition.mbr_variant));
+        }
+
+        Ok(parent)
+    }
+
+    /// Validates that a partition may occupy the given sectors of its parent.
+    fn validate_sectors(
+        &self,
+        entity: DeviceEntity,
+        parent: DeviceEntity,
+        offset: u64,
+        end: u64,
+    ) -> Result<(), Error> {
+        // The start sector must be less than end sector.
+        if offset >= end {
+            return Err(Error::InputsInverted);
+        }
+
+        // The end sector must also exist on the device.
+        if self.components.devices.devices[parent].sectors < end {
+            return Err(Error::ExceedsDevice);
+        }
+
+        // Neighbours are compared by the sectors which they will have once changes are applied.
+        let overlaps = self.allocations(parent).iter().any(|allocation| {
+            allocation.entity != entity && offset < allocation.end() && end > allocation.offset
+        });
+
+        if overlaps {
+            return Err(Error::PartitionOverlap);
+        }
+
+        Ok(())
+    }
+}
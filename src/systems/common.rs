@@ -1,6 +1,6 @@
-use disk_ops::table::{Gpt, PartitionError, Partitioner};
+use disk_ops::table::{Gpt, Mbr, PartitionError, Partitioner};
 use disk_types::*;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 pub fn open_partitioner<E>(
     table: PartitionTable,
@@ -12,6 +12,7 @@ pub fn open_partitioner<E>(
 ) -> Result<(), E> {
     // Temporary variables for storing could-be table values.
     let mut gpt: Gpt;
+    let mut mbr: Mbr;
 
     // Fetch a generic partitioner depending on the table kind.
     let partitioner: Result<&mut dyn Partitioner, PartitionError> = match table {
@@ -22,8 +23,22 @@ pub fn open_partitioner<E>(
             }
             Err(why) => Err(why),
         },
-        PartitionTable::Mbr => unimplemented!("no mbr support"),
+        PartitionTable::Mbr => match Mbr::open(path) {
+            Ok(msdos) => {
+                mbr = msdos;
+                Ok(&mut mbr)
+            }
+            Err(why) => Err(why),
+        },
     };
 
     partitioner_func(partitioner, table)
 }
+
+/// The name and path of a partition on a parent device, as the kernel will name it.
+pub fn partition_device(parent: &Device, number: u32) -> (Box<str>, Box<Path>) {
+    let numbered = parent.name.chars().last().map_or(false, char::is_numeric);
+    let name = format!("{}{}{}", parent.name, if numbered { "p" } else { "" }, number);
+    let path = PathBuf::from(["/dev/", &name].concat());
+    (name.into(), path.into())
+}
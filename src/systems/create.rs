@@ -3,9 +3,13 @@
 //! All operations qhich queue a device to be created will be enacted here. Supported operations in
 //! this system are as below, and executed in this order:
 //!
-//! 1. Creating new partition tables on physical devices
-//! 2. Creating new partitions on partition tables
-//! 3. Creating new LUKS devices by encryptiong partitions
+//! 1. Creating new partition tables on physical devices, or restoring them from a dump
+//! 2. Converting existing partition tables into another format
+//! 3. Moving the backup headers of GPT tables to the end of their grown devices
+//! 4. Creating new partitions on partition tables
+//! 5. Creating new LUKS devices by encryptiong partitions
+//! 6. Creating new LUKS devices on existing devices, and releasing devices queued for formatting
+//! 7. Renumbering the partitions of GPT tables in the order of their first sector
 //!
 //! It is important to note that newly-created LUKS partitions will expose a device map as a child
 //! device, which will be equal in size to the size of the partition, minus the LUKS header. This
@@ -13,14 +17,13 @@
 
 use super::*;
 use crate::*;
-use disk_ops::table::{Gpt, Partitioner};
+use disk_ops::table::{convert_str_to_array, Gpt, Mbr, NewPartition, Partitioner, TablePartition};
 use disk_types::*;
 
 use std::path::PathBuf;
 
 // TODO:
 // - Handle parents whom have not been created yet.
-// - Mbr partition tables
 // - LUKS and LVM devices
 
 #[derive(Debug, Error)]
@@ -31,10 +34,18 @@ pub enum Error {
     LuksCreate(Box<Path>, #[error(cause)] ops::luks::Error),
     #[error(display = "attempted to create a device whose parent did not exist")]
     Parentless,
+    #[error(display = "failed to convert partition table on {:?} to {:?}", _1, _0)]
+    TableConvert(PartitionTable, Box<Path>, #[error(cause)] ConvertError),
     #[error(display = "failed to add new partition to {:?} partition table on {:?}", _0, _1)]
     TableAdd(PartitionTable, Box<Path>, #[error(cause)] PartitionError),
     #[error(display = "failed to create {:?} partition table on {:?}", _0, _1)]
     TableCreate(PartitionTable, Box<Path>, #[error(cause)] PartitionError),
+    #[error(display = "failed to move the backup header of the partition table on {:?}", _0)]
+    TableRelocate(Box<Path>, #[error(cause)] PartitionError),
+    #[error(display = "failed to restore partition table on {:?}", _0)]
+    TableRestore(Box<Path>, #[error(cause)] PartitionError),
+    #[error(display = "failed to sort the partitions of the partition table on {:?}", _0)]
+    TableSort(Box<Path>, #[error(cause)] PartitionError),
     #[error(display = "failed to read {:?} partition table from {:?}", _0, _1)]
     TableRead(PartitionTable, Box<Path>, #[error(cause)] PartitionError),
     #[error(display = "failed to write changes to {:?} partition table on {:?}", _0, _1)]
@@ -115,13 +126,13 @@ impl CreationSystem {
             ref mut devices,
             ref mut partitions,
             ref mut luks,
+            ref mut table_health,
             ref mut tables,
             ..
         } = &mut components.devices;
 
         // TODO: Create volume groups and their logical volumes.
 
-        // 
         // - Disks with the create flag will be wiped and formatted
         // - Queued partitions will be added to partition tables.
         // - Queued partitions of LVM VGs will be created on the VG as a LV
@@ -129,6 +140,25 @@ impl CreationSystem {
 
         // First, the creation of new partition tables.
         for (parent_entity, new_table) in queued_changes.tables.drain() {
+            // Restored tables are written with the partitions of their dump.
+            if let Some(dump) = queued_changes.restores.remove(parent_entity) {
+                let parent_device = devices[parent_entity].clone();
+                let path = parent_device.path();
+
+                let restored = disk_ops::table::restore(path, &dump)
+                    .map_err(|why| Error::TableRestore(path.into(), why))?;
+
+                let restored = restored
+                    .iter()
+                    .map(|new| insert_partition(entities, devices, partitions, &parent_device, new))
+                    .collect();
+
+                tables.insert(parent_entity, new_table);
+                children.insert(parent_entity, restored);
+                entities[parent_entity] -= EntityFlags::CREATE;
+                continue;
+            }
+
             let parent_device = &devices[parent_entity];
             let parent_flags = &mut entities[parent_entity];
             let path = parent_device.path();
@@ -136,22 +166,116 @@ impl CreationSystem {
             {
                 let sector_size = parent_device.logical_sector_size();
 
+                let guid = queued_changes.disk_guids.remove(parent_entity);
+
                 match new_table {
                     PartitionTable::Guid => {
-                        disk_ops::table::Gpt::create(path, sector_size)
-                            .map_err(|why| Error::TableCreate(new_table, path.into(), why))?
-                            .write()
+                        let mut gpt = Gpt::create(path, sector_size)
+                            .map_err(|why| Error::TableCreate(new_table, path.into(), why))?;
+
+                        if let Some(guid) = guid {
+                            gpt.set_disk_guid(guid);
+                        }
+
+                        gpt.write()
+                            .map_err(|why| Error::TableWrite(new_table, path.into(), why))?;
+                    }
+                    PartitionTable::Mbr => {
+                        let mut mbr = Mbr::create(path, sector_size)
+                            .map_err(|why| Error::TableCreate(new_table, path.into(), why))?;
+
+                        if let Some(guid) = guid {
+                            let mut signature = [0; 4];
+                            signature.copy_from_slice(&guid[..4]);
+                            mbr.set_signature(u32::from_le_bytes(signature));
+                        }
+
+                        mbr.write()
                             .map_err(|why| Error::TableWrite(new_table, path.into(), why))?;
                     }
-                    PartitionTable::Mbr => unimplemented!("mbr tables are not supported"),
                 }
 
                 tables.insert(parent_entity, new_table);
+                if !children.contains_key(parent_entity) {
+                    children.insert(parent_entity, Vec::new());
+                }
                 *parent_flags -= EntityFlags::CREATE;
             }
         }
 
-        // Second, check if any children need to be created on available partition tables.
+        // Second, the conversion of existing partition tables into another format.
+        for (parent_entity, new_table) in queued_changes.conversions.drain() {
+            let parent_device = devices[parent_entity].clone();
+            let path = parent_device.path();
+
+            let converted = disk_ops::table::convert(path, tables[parent_entity], new_table)
+                .map_err(|why| Error::TableConvert(new_table, path.into(), why))?;
+
+            tables.insert(parent_entity, new_table);
+
+            // Partitions keep their sectors, but may be renumbered. Extended partitions are
+            // dropped when converting to GPT, and may be created when converting to MBR.
+            let mut retained = Vec::with_capacity(converted.len());
+            for child in children.remove(parent_entity).unwrap_or_default() {
+                let offset = partitions.get(child).map(|partition| partition.offset);
+                match converted.iter().find(|new| Some(new.start) == offset) {
+                    Some(new) => {
+                        let partition = &mut partitions[child];
+                        partition.number = new.number;
+                        partition.partuuid = Some(new.uuid.clone());
+                        partition.mbr_variant = new.variant;
+
+                        let (name, path) = super::partition_device(&parent_device, new.number);
+                        let device = &mut devices[child];
+                        device.name = name;
+                        device.path = path;
+
+                        retained.push(child);
+                    }
+                    None => {
+                        entities.remove(child);
+                        devices.remove(child);
+                        partitions.remove(child);
+                    }
+                }
+            }
+
+            for new in &converted {
+                if retained.iter().any(|&child| partitions[child].offset == new.start) {
+                    continue;
+                }
+
+                retained.push(insert_partition(entities, devices, partitions, &parent_device, new));
+            }
+
+            children.insert(parent_entity, retained);
+        }
+
+        // Third, the relocation of GPT backup headers to the end of their grown devices.
+        for (parent_entity, ()) in queued_changes.backup_relocations.drain() {
+            let parent_device = &mut devices[parent_entity];
+            let path = parent_device.path.clone();
+            let sector_size = parent_device.logical_sector_size;
+
+            let sectors = disk_ops::table::kernel_sectors(&path, sector_size).map_err(|why| {
+                Error::TableRelocate(path.clone(), PartitionError::DeviceRead(why))
+            })?;
+
+            let mut gpt = Gpt::open(&path)
+                .map_err(|why| Error::TableRead(PartitionTable::Guid, path.clone(), why))?;
+
+            gpt.relocate_backup(sectors).map_err(|why| Error::TableRelocate(path.clone(), why))?;
+
+            gpt.write()
+                .map_err(|why| Error::TableWrite(PartitionTable::Guid, path.clone(), why))?;
+
+            parent_device.sectors = sectors;
+            if let Some(health) = table_health.get_mut(parent_entity) {
+                health.backup_misplaced = false;
+            }
+        }
+
+        // Fourth, check if any children need to be created on available partition tables.
         for (parent_entity, &table) in tables.iter() {
             let parent_flags = &mut entities[parent_entity];
             if !parent_flags.contains(EntityFlags::CREATE_CHILDREN) {
@@ -159,7 +283,7 @@ impl CreationSystem {
             }
 
             *parent_flags -= EntityFlags::CREATE_CHILDREN;
-            let parent_device = &devices[parent_entity];
+            let parent_device = devices[parent_entity].clone();
             let path = parent_device.path();
             let mut new_children = Vec::new();
 
@@ -169,8 +293,22 @@ impl CreationSystem {
                     partitioner.map_err(|why| Error::TableRead(table, path.into(), why))?;
 
                 // Add partitions to the in-memory partition table.
-                let queued_children =
-                    QueuedChanges::pop_children_of(&mut queued_changes.parents, parent_entity);
+                let mut queued_children =
+                    QueuedChanges::pop_children_of(&mut queued_changes.parents, parent_entity)
+                        .collect::<Vec<_>>();
+
+                // Extended partitions must exist before their logical partitions are added, and
+                // partitions which requested a number are added before others can take it. Logical
+                // partitions are numbered as they are added, so those are added in number order.
+                queued_children.sort_by_key(|&child| {
+                    let partition = &queued_changes.partitions[child];
+                    (
+                        partition.mbr_variant == PartitionType::Logical,
+                        partition.number == 0,
+                        partition.number,
+                        partition.offset,
+                    )
+                });
 
                 for child in queued_children {
                     let child_device = queued_changes
@@ -182,17 +320,47 @@ impl CreationSystem {
                         .remove(child)
                         .expect("partition is being created without a partition component");
 
-                    let start = partition.offset;
-                    let end = child_device.sectors + start;
-                    let name = partition.partlabel.as_ref().map(AsRef::as_ref);
+                    let kind = partition
+                        .kind
+                        .unwrap_or_else(|| PartitionKind::from_filesystem(partition.filesystem));
+
+                    // GPT partitions have their PARTUUID chosen when they are queued.
+                    let uuid = partition
+                        .partuuid
+                        .as_ref()
+                        .and_then(|uuid| convert_str_to_array(uuid).ok());
+
+                    let new_partition = NewPartition {
+                        start:      partition.offset,
+                        end:        partition.offset + child_device.sectors - 1,
+                        name:       partition.partlabel.as_ref().map(AsRef::as_ref),
+                        variant:    partition.mbr_variant,
+                        kind,
+                        attributes: partition.attributes,
+                        uuid,
+                        number:     Some(partition.number).filter(|&number| number != 0),
+                    };
+
                     partition.number = partitioner
-                        .add(start, end, name)
+                        .add(new_partition)
                         .map_err(|why| Error::TableAdd(table, path.into(), why))?;
+                    partition.kind = Some(kind);
 
+                    let (name, path) = super::partition_device(&parent_device, partition.number);
+                    devices.insert(child, Device { name, path, ..child_device });
                     partitions.insert(child, partition);
                     new_children.push(child);
                 }
 
+                // MBR tables only assign a PARTUUID once the partition has a number.
+                let written = partitioner.partitions();
+                for &child in &new_children {
+                    let partition = &mut partitions[child];
+                    if let Some(new) = written.iter().find(|new| new.number == partition.number) {
+                        partition.partuuid = Some(new.uuid.clone());
+                    }
+                }
+
                 // Write changes to disk
                 partitioner.write().map_err(|why| Error::TableWrite(table, path.into(), why))
             })?;
@@ -201,32 +369,133 @@ impl CreationSystem {
             for &child in &new_children {
                 entities[child] -= EntityFlags::CREATE;
 
-                // Take the file system of the newly-created partition, and queue it
-                // to be applied in the format system.
+                // File systems were queued along with their partitions, to be applied in the
+                // modification system. LUKS devices are created here, as they may have children.
                 let device = &devices[child];
 
-                match partitions[child].filesystem {
-                    Some(FileSystem::Luks) => {
-                        let (entity, params) = queued_changes.luks.remove(child).expect(
-                            "entities marked for creation with a Luks FS are expected to have \
-                             LUKS parameters to use when creating the LUKS device",
-                        );
+                if let Some(FileSystem::Luks) = partitions[child].filesystem {
+                    let (entity, params) = queued_changes.luks.remove(child).expect(
+                        "entities marked for creation with a Luks FS are expected to have LUKS \
+                         parameters to use when creating the LUKS device",
+                    );
 
-                        let result = crate::ops::luks::format(device.path.as_ref(), &params);
-                        result.map_err(|why| Error::LuksCreate(device.path.clone(), why))?;
+                    let result = crate::ops::luks::format(device.path.as_ref(), &params);
+                    result.map_err(|why| Error::LuksCreate(device.path.clone(), why))?;
 
-                        luks.insert(child, params.passphrase);
-                        entities[entity] -= EntityFlags::CREATE;
-                        self.newly_created_luks_devices.push((child, entity, params.target_name));
-                    }
-                    Some(fs) => {
-                        queued_changes.formats.insert(child, fs);
-                    }
-                    None => (),
+                    luks.insert(child, params.passphrase);
+                    entities[entity] -= EntityFlags::CREATE;
+                    self.newly_created_luks_devices.push((child, entity, params.target_name));
                 }
             }
+
+            self.new_children.entry(parent_entity).or_insert_with(Vec::new).extend(new_children);
+        }
+
+        // Fifth, devices which are formatted directly, rather than having partitions created on
+        // them. Their file systems are created by the modification system, which skips devices
+        // that are still marked for creation.
+        let formatted_directly = entities
+            .iter()
+            .filter(|&(entity, flags)| {
+                flags.contains(EntityFlags::CREATE)
+                    && devices.contains_key(entity)
+                    && queued_changes.partitions.contains_key(entity)
+                    && !queued_changes.parents.contains_key(entity)
+            })
+            .map(|(entity, _)| entity)
+            .collect::<Vec<_>>();
+
+        for entity in formatted_directly {
+            queued_changes.devices.remove(entity);
+            let queued = queued_changes.partitions.remove(entity);
+            entities[entity] -= EntityFlags::CREATE;
+
+            if let Some((child, params)) = queued_changes.luks.remove(entity) {
+                let device = &devices[entity];
+
+                let result = crate::ops::luks::format(device.path.as_ref(), &params);
+                result.map_err(|why| Error::LuksCreate(device.path.clone(), why))?;
+
+                if let Some(partition) = partitions.get_mut(entity) {
+                    partition.filesystem = queued.and_then(|queued| queued.filesystem);
+                }
+
+                luks.insert(entity, params.passphrase);
+                entities[child] -= EntityFlags::CREATE;
+                self.newly_created_luks_devices.push((entity, child, params.target_name));
+            }
+        }
+
+        // Sixth, renumber the partitions of tables which are to be sorted.
+        for (parent_entity, ()) in queued_changes.sorts.drain() {
+            let parent_device = devices[parent_entity].clone();
+            let path = parent_device.path();
+
+            let mut gpt = Gpt::open(path)
+                .map_err(|why| Error::TableRead(PartitionTable::Guid, path.into(), why))?;
+
+            let renumbered = gpt.sort().map_err(|why| Error::TableSort(path.into(), why))?;
+
+            gpt.write().map_err(|why| Error::TableWrite(PartitionTable::Guid, path.into(), why))?;
+
+            let created = self.new_children.get(&parent_entity);
+            for &child in children.get(parent_entity).into_iter().chain(created).flatten() {
+                let partition = match partitions.get_mut(child) {
+                    Some(partition) => partition,
+                    None => continue,
+                };
+
+                let new = match renumbered.iter().find(|&&(old, _)| old == partition.number) {
+                    Some(&(_, new)) => new,
+                    None => continue,
+                };
+
+                let (name, path) = super::partition_device(&parent_device, new);
+                partition.number = new;
+                devices[child].name = name;
+                devices[child].path = path;
+            }
         }
 
         Ok(())
     }
 }
+
+/// Adds a partition which was written to the partition table of the parent device to the world.
+fn insert_partition(
+    entities: &mut HopSlotMap<DeviceEntity, EntityFlags>,
+    devices: &mut SecondaryMap<DeviceEntity, Device>,
+    partitions: &mut SparseSecondaryMap<DeviceEntity, Partition>,
+    parent_device: &Device,
+    new: &TablePartition,
+) -> DeviceEntity {
+    let (name, path) = super::partition_device(parent_device, new.number);
+    let child = entities.insert(EntityFlags::empty());
+
+    devices.insert(
+        child,
+        Device {
+            name,
+            path,
+            sectors:              new.end - new.start + 1,
+            logical_sector_size:  parent_device.logical_sector_size,
+            physical_sector_size: parent_device.physical_sector_size,
+        },
+    );
+
+    partitions.insert(
+        child,
+        Partition {
+            offset:      new.start,
+            number:      new.number,
+            partuuid:    Some(new.uuid.clone()),
+            partlabel:   new.name.clone(),
+            mbr_variant: new.variant,
+            kind:        new.kind,
+            attributes:  new.attributes,
+            ..Default::default()
+        },
+    );
+
+    child
+}
@@ -87,7 +87,14 @@ pub(crate) fn run(
         systems.creation.run(entities, components, cancel)?
     }
 
-    if flags.contains(ManagerFlags::FORMAT | ManagerFlags::LABEL) {
+    // Newly-created partitions have their file systems formatted by the modification system.
+    let modify = ManagerFlags::CREATE
+        | ManagerFlags::FORMAT
+        | ManagerFlags::LABEL
+        | ManagerFlags::ATTRIBUTES
+        | ManagerFlags::UUIDS;
+
+    if flags.intersects(modify) {
         cancellation_check!(cancel);
         systems.modification.run(entities, components, cancel)?;
     }
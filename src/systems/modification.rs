@@ -1,24 +1,43 @@
 use super::*;
 use crate::*;
+use disk_ops::table::convert_array_to_str;
 use std::collections::HashMap;
 
 #[derive(Debug, Error)]
 pub enum Error {
+    #[error(display = "failed to write attributes")]
+    AttributesWrite(#[error(cause)] PartitionError),
+    #[error(display = "failed to label {} file system on {:?}", _1, _0)]
+    FsLabel(Box<Path>, FileSystem, #[error(cause)] io::Error),
+    #[error(display = "failed to assign a new UUID to {} file system on {:?}", _1, _0)]
+    FsUuid(Box<Path>, FileSystem, #[error(cause)] io::Error),
     #[error(display = "failed to write label")]
     LabelWrite(#[error(cause)] PartitionError),
     #[error(display = "failed to format {:?} with {}", _0, _1)]
     Mkfs(Box<Path>, FileSystem, #[error(cause)] io::Error),
+    #[error(display = "failed to write PARTUUID")]
+    PartUuidWrite(#[error(cause)] PartitionError),
     #[error(display = "failed to read {:?} partition table from {:?}", _0, _1)]
     TableRead(PartitionTable, Box<Path>, #[error(cause)] PartitionError),
+    #[error(display = "failed to write changes to {:?} partition table on {:?}", _0, _1)]
+    TableWrite(PartitionTable, Box<Path>, #[error(cause)] PartitionError),
 }
 
 #[derive(Debug)]
 pub struct ModificationSystem {
-    changed: HashMap<DeviceEntity, Box<str>>,
+    changed:            HashMap<DeviceEntity, Box<str>>,
+    changed_attributes: HashMap<DeviceEntity, PartitionAttributes>,
+    changed_partuuids:  HashMap<DeviceEntity, [u8; 16]>,
 }
 
 impl Default for ModificationSystem {
-    fn default() -> Self { Self { changed: HashMap::with_capacity(8) } }
+    fn default() -> Self {
+        Self {
+            changed:            HashMap::with_capacity(8),
+            changed_attributes: HashMap::with_capacity(8),
+            changed_partuuids:  HashMap::new(),
+        }
+    }
 }
 
 impl System for ModificationSystem {
@@ -31,6 +50,7 @@ impl System for ModificationSystem {
         cancel: &AtomicBool,
     ) -> Result<(), Self::Err> {
         self.changed.clear();
+        self.changed_attributes.clear();
 
         let entities = &mut entities.devices;
         let queued_changes = &mut components.queued_changes;
@@ -45,18 +65,65 @@ impl System for ModificationSystem {
 
         // TODO: Extend volume groups that already exist.
 
-        for entity in entities.keys() {
-            if let Some(fs) = queued_changes.formats.remove(entity) {
+        // Devices which have yet to be created are not formatted.
+        for (entity, flags) in entities.iter() {
+            if flags.contains(EntityFlags::CREATE) {
+                continue;
+            }
+
+            if let Some((fs, options)) = queued_changes.formats.remove(entity) {
                 let device = &devices[entity];
 
-                disk_ops::partition::format(device.path.as_ref(), fs)
+                disk_ops::partition::format(device.path.as_ref(), fs, &options)
                     .map_err(|why| Error::Mkfs(device.path.clone(), fs, why))?;
 
-                partitions[entity].filesystem = Some(fs);
+                if let Some(partition) = partitions.get_mut(entity) {
+                    partition.filesystem = Some(fs);
+                    partition.label = options.label;
+                }
             }
         }
 
+        // Then label file systems which already exist.
+        for (entity, label) in queued_changes.fs_labels.drain() {
+            let (device, partition) = match (devices.get(entity), partitions.get_mut(entity)) {
+                (Some(device), Some(partition)) => (device, partition),
+                // The device was removed after it was labeled.
+                _ => continue,
+            };
+
+            let fs = partition.filesystem.expect("labeled device without a file system");
+
+            disk_ops::partition::label(device.path.as_ref(), fs, &label)
+                .map_err(|why| Error::FsLabel(device.path.clone(), fs, why))?;
+
+            partition.label = Some(label);
+        }
+
+        // And give file systems new UUIDs.
+        for (entity, ()) in queued_changes.fs_uuids.drain() {
+            let (device, partition) = match (devices.get(entity), partitions.get_mut(entity)) {
+                (Some(device), Some(partition)) => (device, partition),
+                _ => continue,
+            };
+
+            let fs = partition.filesystem.expect("device without a file system given a UUID");
+
+            partition.uuid = disk_ops::partition::regenerate_uuid(device.path.as_ref(), fs)
+                .map_err(|why| Error::FsUuid(device.path.clone(), fs, why))?;
+        }
+
         for (parent_entity, children) in children.iter() {
+            let queued = children.iter().any(|&child| {
+                queued_changes.labels.contains_key(child)
+                    || queued_changes.attributes.contains_key(child)
+                    || queued_changes.partuuids.contains_key(child)
+            });
+
+            if !queued {
+                continue;
+            }
+
             let parent_device = &devices[parent_entity];
             if let Some(table) = tables.get(parent_entity) {
                 let path = parent_device.path();
@@ -66,7 +133,7 @@ impl System for ModificationSystem {
                     let partitioner =
                         partitioner.map_err(|why| Error::TableRead(table, path.into(), why))?;
 
-                    // Locate the children who have new labels queued.
+                    // Locate the children who have new labels, attributes, or PARTUUIDs queued.
                     for &child in children {
                         if let Some(new_label) = queued_changes.labels.remove(child) {
                             let partition = &partitions[child];
@@ -75,17 +142,39 @@ impl System for ModificationSystem {
                                 .map_err(Error::LabelWrite)?;
                             self.changed.insert(child, new_label);
                         }
+
+                        if let Some(attributes) = queued_changes.attributes.remove(child) {
+                            let partition = &partitions[child];
+                            partitioner
+                                .set_attributes(partition.offset + 1, attributes)
+                                .map_err(Error::AttributesWrite)?;
+                            self.changed_attributes.insert(child, attributes);
+                        }
+
+                        if let Some(partuuid) = queued_changes.partuuids.remove(child) {
+                            let partition = &partitions[child];
+                            partitioner
+                                .set_partuuid(partition.offset + 1, partuuid)
+                                .map_err(Error::PartUuidWrite)?;
+                            self.changed_partuuids.insert(child, partuuid);
+                        }
                     }
 
-                    Ok(())
+                    partitioner.write().map_err(|why| Error::TableWrite(table, path.into(), why))
                 })?;
 
-                // Apply the new labels to the in-memory representation.
+                // Apply the new labels, attributes, and PARTUUIDs to the in-memory representation.
                 for (entity, new_label) in self.changed.drain() {
                     partitions[entity].partlabel = Some(new_label);
                 }
-            } else {
-                unimplemented!("unsupported device type")
+
+                for (entity, attributes) in self.changed_attributes.drain() {
+                    partitions[entity].attributes = attributes;
+                }
+
+                for (entity, partuuid) in self.changed_partuuids.drain() {
+                    partitions[entity].partuuid = Some(convert_array_to_str(&partuuid).into());
+                }
             }
         }
 
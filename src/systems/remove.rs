@@ -36,6 +36,7 @@ impl System for RemoveSystem {
             ref mut devices,
             ref mut disks,
             ref mut partitions,
+            ref mut table_health,
             ref mut tables,
             ..
         } = &mut components.devices;
@@ -92,6 +93,7 @@ impl System for RemoveSystem {
 
             wipe(&device.path).map_err(|why| Error::Wipefs(device.path.clone(), why))?;
             partitions.remove(entity);
+            table_health.remove(entity);
             free_children(entities, children, entity);
 
             let flags = &mut entities[entity];
@@ -102,10 +104,15 @@ impl System for RemoveSystem {
         }
 
         // Free all partitions from their parent devices.
-        for (disk_entity, children_to_free) in partitions_to_free {
+        for (disk_entity, mut children_to_free) in partitions_to_free {
+            // Logical partitions must be removed before their extended partition.
+            children_to_free
+                .sort_by_key(|&child| partitions[child].mbr_variant != PartitionType::Logical);
+
             let disk_device = &devices[disk_entity];
             let table = tables[disk_entity];
             let path = disk_device.path();
+            let mut remaining = Vec::new();
             super::open_partitioner(table, path, |partitioner, table| {
                 let partitioner =
                     partitioner.map_err(|why| Error::TableRead(table, path.into(), why))?;
@@ -117,7 +124,9 @@ impl System for RemoveSystem {
                     })?;
                 }
 
-                partitioner.write().map_err(|why| Error::TableWrite(table, path.into(), why))
+                partitioner.write().map_err(|why| Error::TableWrite(table, path.into(), why))?;
+                remaining = partitioner.partitions();
+                Ok(())
             })?;
 
             // On success, free all children from the world.
@@ -125,6 +134,32 @@ impl System for RemoveSystem {
                 entities.remove(child);
                 free_children(entities, children, child);
             }
+
+            // Logical partitions which followed a removed logical partition are renumbered.
+            let disk_device = devices[disk_entity].clone();
+            let siblings = children.get_mut(disk_entity).map(|siblings| {
+                siblings.retain(|&child| entities.contains_key(child));
+                siblings.as_slice()
+            });
+
+            for &child in siblings.into_iter().flatten() {
+                let partition = match partitions.get_mut(child) {
+                    Some(partition) => partition,
+                    None => continue,
+                };
+
+                let new = match remaining.iter().find(|new| new.start == partition.offset) {
+                    Some(new) if new.number != partition.number => new,
+                    _ => continue,
+                };
+
+                // The PARTUUIDs of MBR partitions are derived from their numbers.
+                let (name, path) = super::partition_device(&disk_device, new.number);
+                partition.number = new.number;
+                partition.partuuid = Some(new.uuid.clone());
+                devices[child].name = name;
+                devices[child].path = path;
+            }
         }
 
         Ok(())
@@ -1,14 +1,67 @@
+//! # Device Resize System
+//!
+//! Partitions which have been queued to be resized will have their partition table entries,
+//! and their file systems, resized here. File systems are shrunk before their partition is, and
+//! grown after their partition is. File systems are checked, and repaired, before they are
+//! shrunk, and ext file systems are also checked before they are grown, as resize2fs requires.
+//!
+//! All partitions being shrunk are resized before any partitions are moved, and partitions are
+//! moved before any partitions are grown, so that a partition may take the sectors that its
+//! neighbour has given up.
+//!
+//! Moved partitions have their data copied to their new location on the parent device, and
+//! their partition table entries are rewritten afterwards, keeping their PARTUUID.
+
 use super::*;
 use crate::*;
+use disk_ops::relocate::{self, MoveError};
+use std::fmt;
 
 #[derive(Debug, Error)]
 pub enum Error {
-    #[error(display = "placeholder")]
-    Placeholder,
+    #[error(display = "partition resize system was cancelled")]
+    Cancelled,
+    #[error(display = "failed to check {} file system on {:?}", _1, _0)]
+    FsCheck(Box<Path>, FileSystem, #[error(cause)] io::Error),
+    #[error(display = "{} file system on {:?} has errors which were not repaired", _1, _0)]
+    FsErrors(Box<Path>, FileSystem),
+    #[error(display = "failed to resize {} file system on {:?}", _1, _0)]
+    FsResize(Box<Path>, FileSystem, #[error(cause)] io::Error),
+    #[error(display = "failed to move partition data on {:?}", _0)]
+    Move(Box<Path>, #[error(cause)] MoveError),
+    #[error(display = "attempted to resize a partition whose parent did not exist")]
+    Parentless,
+    #[error(display = "failed to read {:?} partition table from {:?}", _0, _1)]
+    TableRead(PartitionTable, Box<Path>, #[error(cause)] PartitionError),
+    #[error(display = "failed to resize partition on {:?} partition table on {:?}", _0, _1)]
+    TableResize(PartitionTable, Box<Path>, #[error(cause)] PartitionError),
+    #[error(display = "failed to write changes to {:?} partition table on {:?}", _0, _1)]
+    TableWrite(PartitionTable, Box<Path>, #[error(cause)] PartitionError),
+}
+
+/// Receives the partition being moved, the sectors copied so far, and the total to copy.
+pub type MoveProgress = Box<dyn FnMut(DeviceEntity, u64, u64) + Send>;
+
+/// Receives the partition being checked, the steps completed so far, and the total steps.
+pub type CheckProgress = Box<dyn FnMut(DeviceEntity, u64, u64) + Send>;
+
+#[derive(Default)]
+pub struct ResizeSystem {
+    /// Reports the progress of partitions being moved.
+    pub progress: Option<MoveProgress>,
+
+    /// Reports the progress of file systems being checked.
+    pub check_progress: Option<CheckProgress>,
 }
 
-#[derive(Debug, Default)]
-pub struct ResizeSystem;
+impl fmt::Debug for ResizeSystem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ResizeSystem")
+            .field("progress", &self.progress.is_some())
+            .field("check_progress", &self.check_progress.is_some())
+            .finish()
+    }
+}
 
 impl System for ResizeSystem {
     type Err = Error;
@@ -19,25 +72,207 @@ impl System for ResizeSystem {
         components: &mut DiskComponents,
         cancel: &AtomicBool,
     ) -> Result<(), Self::Err> {
-        let entities = &mut entities.devices;
-        let &mut DeviceComponents {
-            ref mut children,
-            ref mut devices,
-            ref mut disks,
-            ref mut device_maps,
-            ref mut loopbacks,
-            ref mut luks,
-            ref mut partitions,
-            ref mut pvs,
-            ref mut lvs,
-            ..
-        } = &mut components.devices;
+        let queued_changes = &mut components.queued_changes;
+        let devices = &mut components.devices;
 
         // TODO: Resize volumes on volume groups
-        // TODO: Resize volumes on physical disks
         // TODO: Resize LVM PVs and their LVM VGs
         // TODO: Resize LUKS devices and their associated device maps
 
-        unimplemented!()
+        let (grows, shrinks): (Vec<_>, Vec<_>) = queued_changes
+            .resize
+            .drain()
+            .filter(|&(entity, _)| entities.devices.contains_key(entity))
+            .partition(|&(entity, (_, sectors))| sectors > devices.devices[entity].sectors);
+
+        let mut moves = queued_changes
+            .moves
+            .drain()
+            .filter(|&(entity, _)| entities.devices.contains_key(entity))
+            .collect::<Vec<_>>();
+
+        // Partitions moving right are moved from the rightmost first, followed by partitions
+        // moving left from the leftmost first, so that none are moved onto a neighbour that has
+        // yet to move out of the way.
+        moves.sort_by_key(|&(entity, offset)| {
+            let current = devices.partitions[entity].offset;
+            if offset > current {
+                (false, u64::max_value() - current)
+            } else {
+                (true, current)
+            }
+        });
+
+        for (entity, (offset, sectors)) in shrinks {
+            if cancel.load(Ordering::SeqCst) {
+                return Err(Error::Cancelled);
+            }
+
+            self.resize(devices, entity, offset, sectors)?;
+        }
+
+        for (entity, offset) in moves {
+            if cancel.load(Ordering::SeqCst) {
+                return Err(Error::Cancelled);
+            }
+
+            self.move_partition(devices, entity, offset, cancel)?;
+        }
+
+        for (entity, (offset, sectors)) in grows {
+            if cancel.load(Ordering::SeqCst) {
+                return Err(Error::Cancelled);
+            }
+
+            self.resize(devices, entity, offset, sectors)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ResizeSystem {
+    /// Copies the data of a partition to its new offset, and then moves its table entry.
+    fn move_partition(
+        &mut self,
+        components: &mut DeviceComponents,
+        entity: DeviceEntity,
+        offset: u64,
+        cancel: &AtomicBool,
+    ) -> Result<(), Error> {
+        let parent = parent_of(components, entity)?;
+        let parent_path = &components.devices[parent].path;
+        let device = &components.devices[entity];
+        let sectors = device.sectors;
+
+        // The partition's own device node only spans its old sectors, so data is copied
+        // through the parent device.
+        let progress = &mut self.progress;
+        relocate::move_sectors(
+            parent_path,
+            components.partitions[entity].offset,
+            offset,
+            sectors,
+            device.logical_sector_size,
+            cancel,
+            |copied, total| {
+                if let Some(ref mut progress) = progress {
+                    progress(entity, copied, total);
+                }
+            },
+        )
+        .map_err(|why| match why {
+            MoveError::Cancelled => Error::Cancelled,
+            why => Error::Move(parent_path.clone(), why),
+        })?;
+
+        rewrite_entry(components, parent, entity, offset, sectors)
     }
+
+    /// Resizes a partition, and the file system on it.
+    fn resize(
+        &mut self,
+        components: &mut DeviceComponents,
+        entity: DeviceEntity,
+        offset: u64,
+        sectors: u64,
+    ) -> Result<(), Error> {
+        let parent = parent_of(components, entity)?;
+        let device = &components.devices[entity];
+        let shrinking = sectors < device.sectors;
+        let size = sectors * device.logical_sector_size;
+        let filesystem = components.partitions[entity].filesystem;
+
+        if let Some(fs) = filesystem {
+            let ext = match fs {
+                FileSystem::Ext2 | FileSystem::Ext3 | FileSystem::Ext4 => true,
+                _ => false,
+            };
+
+            if ext {
+                self.check(device, entity, fs, CheckMode::ForceRepair)?;
+            } else if shrinking && fs.check_tool().is_some() {
+                self.check(device, entity, fs, CheckMode::Repair)?;
+            }
+        }
+
+        let resize_fs = |device: &Device| match filesystem {
+            Some(fs) => disk_ops::partition::resize(device.path(), fs, size)
+                .map_err(|why| Error::FsResize(device.path.clone(), fs, why)),
+            None => Ok(()),
+        };
+
+        if shrinking {
+            resize_fs(device)?;
+        }
+
+        rewrite_entry(components, parent, entity, offset, sectors)?;
+
+        if !shrinking {
+            resize_fs(&components.devices[entity])?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks a file system before it is resized, which fails if errors remain afterwards.
+    fn check(
+        &mut self,
+        device: &Device,
+        entity: DeviceEntity,
+        fs: FileSystem,
+        mode: CheckMode,
+    ) -> Result<(), Error> {
+        let progress = &mut self.check_progress;
+        let verdict = disk_ops::partition::check(device.path(), fs, mode, |done, total| {
+            if let Some(ref mut progress) = progress {
+                progress(entity, done, total);
+            }
+        })
+        .map_err(|why| Error::FsCheck(device.path.clone(), fs, why))?;
+
+        if verdict == CheckVerdict::ErrorsRemaining {
+            return Err(Error::FsErrors(device.path.clone(), fs));
+        }
+
+        Ok(())
+    }
+}
+
+/// Rewrites the partition table entry of a partition to cover the given sectors.
+fn rewrite_entry(
+    components: &mut DeviceComponents,
+    parent: DeviceEntity,
+    entity: DeviceEntity,
+    offset: u64,
+    sectors: u64,
+) -> Result<(), Error> {
+    let table = components.tables[parent];
+    let parent_path = &components.devices[parent].path;
+    let sector = components.partitions[entity].offset + 1;
+
+    super::open_partitioner(table, parent_path, |partitioner, table| {
+        let partitioner =
+            partitioner.map_err(|why| Error::TableRead(table, parent_path.clone(), why))?;
+
+        partitioner
+            .resize(sector, offset, offset + sectors - 1)
+            .map_err(|why| Error::TableResize(table, parent_path.clone(), why))?;
+
+        partitioner.write().map_err(|why| Error::TableWrite(table, parent_path.clone(), why))
+    })?;
+
+    components.partitions[entity].offset = offset;
+    components.devices[entity].sectors = sectors;
+
+    Ok(())
+}
+
+fn parent_of(components: &DeviceComponents, entity: DeviceEntity) -> Result<DeviceEntity, Error> {
+    components
+        .children
+        .iter()
+        .find(|(_, children)| children.contains(&entity))
+        .map(|(parent, _)| parent)
+        .ok_or(Error::Parentless)
 }
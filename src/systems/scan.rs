@@ -4,8 +4,9 @@ pub use self::linux::*;
 #[cfg(target_os = "linux")]
 mod linux {
     use crate::{Error as DiskError, *};
+    use disk_ops::table::Gpt;
     use disk_types::*;
-    use std::fs::read_link;
+    use std::{collections::BTreeMap, fs::read_link};
 
     pub fn scan(
         entities: &mut DiskEntities,
@@ -29,6 +30,15 @@ mod linux {
                 },
             );
 
+            components.devices.topologies.insert(
+                whole_entity,
+                Topology {
+                    alignment_offset: info.alignment,
+                    minimum_io_size:  info.minimum_io_size,
+                    optimal_io_size:  info.optimal_io_size,
+                },
+            );
+
             match info.variant {
                 DeviceVariant::Loopback(backing_file) => {
                     components.devices.loopbacks.insert(whole_entity, backing_file);
@@ -43,6 +53,18 @@ mod linux {
 
             if let Some(table) = info.table {
                 components.devices.tables.insert(whole_entity, table);
+
+                if table == PartitionTable::Guid {
+                    match Gpt::verify(
+                        &components.devices.devices[whole_entity].path,
+                        info.logical_sector_size,
+                    ) {
+                        Ok(health) => {
+                            components.devices.table_health.insert(whole_entity, health);
+                        }
+                        Err(why) => eprintln!("failed to verify GPT table: {}", why),
+                    }
+                }
             }
 
             if let Some(fstype) = info.fstype {
@@ -56,6 +78,10 @@ mod linux {
                         partlabel:   None,
                         mbr_variant: PartitionType::Primary,
                         uuid:        info.uuid,
+                        uuid_sub:    info.uuid_sub,
+                        label:       info.label,
+                        kind:        None,
+                        attributes:  PartitionAttributes::empty(),
                     },
                 );
             }
@@ -84,8 +110,12 @@ mod linux {
                         filesystem:  partition.fstype.and_then(|fstype| fstype.parse().ok()),
                         partuuid:    partition.partuuid,
                         partlabel:   partition.partlabel,
-                        mbr_variant: PartitionType::Primary,
+                        mbr_variant: partition.variant,
                         uuid:        partition.uuid,
+                        uuid_sub:    partition.uuid_sub,
+                        label:       partition.label,
+                        kind:        partition.kind,
+                        attributes:  partition.attributes,
                     },
                 );
             }
@@ -110,9 +140,67 @@ mod linux {
             }
         }
 
+        components.devices.uuid_collisions = uuid_collisions(entities, &components.devices);
+
         Ok(())
     }
 
+    /// Finds the UUIDs and PARTUUIDs which are shared by more than one device.
+    ///
+    /// The devices of a multi-device file system share its UUID, but each has a UUID_SUB of its
+    /// own. Their UUIDs only collide when their UUID_SUBs do too, as for a cloned device.
+    pub(crate) fn uuid_collisions(
+        entities: &DiskEntities,
+        components: &DeviceComponents,
+    ) -> Vec<UuidCollision> {
+        type Members = Vec<(DeviceEntity, Option<String>)>;
+        let mut found: BTreeMap<(UuidKind, Box<str>), Members> = BTreeMap::new();
+        let mut insert = |kind, uuid: &str, sub: Option<&str>, entity| {
+            let sub = sub.map(str::to_lowercase);
+            found.entry((kind, uuid.to_lowercase().into())).or_default().push((entity, sub));
+        };
+
+        for entity in entities.devices.keys() {
+            let partition = match components.partitions.get(entity) {
+                Some(partition) => partition,
+                None => continue,
+            };
+
+            // Hybrid ISO images share a UUID with the partition that they contain.
+            match partition.uuid {
+                Some(_) if partition.filesystem == Some(FileSystem::Iso9660) => (),
+                Some(ref uuid) => {
+                    let sub = partition.uuid_sub.as_ref().map(AsRef::as_ref);
+                    insert(UuidKind::FileSystem, uuid, sub, entity);
+                }
+                None => (),
+            }
+
+            if let Some(ref partuuid) = partition.partuuid {
+                insert(UuidKind::Partition, partuuid, None, entity);
+            }
+        }
+
+        found
+            .into_iter()
+            .filter_map(|((kind, uuid), members)| {
+                let entities = members
+                    .iter()
+                    .filter(|&(entity, sub)| {
+                        members.iter().any(|(other, other_sub)| other != entity && other_sub == sub)
+                    })
+                    .map(|&(entity, _)| entity)
+                    .collect::<Vec<_>>();
+
+                if entities.is_empty() {
+                    None
+                } else {
+                    Some(UuidCollision { kind, uuid, entities })
+                }
+            })
+            .collect()
+    }
+
     fn associate_children(components: &mut DiskComponents) {
         let &mut DeviceComponents { ref devices, ref mut children, .. } = &mut components.devices;
 
@@ -16,6 +16,26 @@ fn setup<F: FnOnce(DiskManager, DeviceEntity)>(func: F) {
     func(manager, entity)
 }
 
+/// A device which exists only in memory.
+fn device(sectors: u64, sector_size: u64) -> Device {
+    Device {
+        name: Box::from("sda"),
+        path: Box::from(Path::new("/dev/sda")),
+        sectors,
+        logical_sector_size: sector_size,
+        physical_sector_size: sector_size,
+    }
+}
+
+/// Adds a 512 MiB partition which exists only in memory, which operations may be queued on.
+fn insert_partition(manager: &mut DiskManager, partition: Partition) -> DeviceEntity {
+    let entity = manager.entities.devices.insert(EntityFlags::empty());
+    let devices = &mut manager.components.devices;
+    devices.devices.insert(entity, device(1024 * 1024, 512));
+    devices.partitions.insert(entity, partition);
+    entity
+}
+
 fn apply(manager: &mut DiskManager) {
     manager.apply(&Arc::new(AtomicBool::new(false))).unwrap();
 
@@ -29,7 +49,12 @@ fn apply(manager: &mut DiskManager) {
 #[test]
 fn create_fs_on_loopback() {
     setup(|mut manager, entity| {
-        manager.create_on(entity, ops::create::PartitionCreate::Plain(FileSystem::Ext4)).unwrap();
+        manager
+            .create_on(
+                entity,
+                ops::create::PartitionCreate::Plain(FileSystem::Ext4, FormatOptions::default()),
+            )
+            .unwrap();
     });
 }
 
@@ -37,7 +62,7 @@ fn create_fs_on_loopback() {
 fn create_partition_table() {
     setup(|mut manager, entity| {
         // Create a GUID partition table on the loopback device.
-        manager.create_table(entity, PartitionTable::Guid).unwrap();
+        manager.create_table(entity, PartitionTable::Guid, UuidPolicy::Random).unwrap();
 
         // Create the EFI partition.
         let entity_efi = manager
@@ -46,7 +71,9 @@ fn create_partition_table() {
                 Sector::Start,
                 Sector::Megabyte(100),
                 Box::from("EFI"),
-                ops::create::PartitionCreate::Plain(FileSystem::Vfat),
+                Some(PartitionKind::EfiSystem),
+                UuidPolicy::Random,
+                ops::create::PartitionCreate::Plain(FileSystem::Vfat, FormatOptions::default()),
             )
             .unwrap();
 
@@ -57,7 +84,9 @@ fn create_partition_table() {
                 Sector::Megabyte(100),
                 Sector::MegabyteFromEnd(1000),
                 Box::from("Root"),
-                ops::create::PartitionCreate::Plain(FileSystem::Ext4),
+                None,
+                UuidPolicy::Random,
+                ops::create::PartitionCreate::Plain(FileSystem::Ext4, FormatOptions::default()),
             )
             .unwrap();
 
@@ -67,7 +96,9 @@ fn create_partition_table() {
                 Sector::MegabyteFromEnd(1000),
                 Sector::End,
                 Box::from("Swap"),
-                ops::create::PartitionCreate::Plain(FileSystem::Swap),
+                None,
+                UuidPolicy::Random,
+                ops::create::PartitionCreate::Plain(FileSystem::Swap, FormatOptions::default()),
             )
             .unwrap();
 
@@ -84,11 +115,13 @@ fn create_partition_table() {
                 eprintln!("EFI sector length: {}", device.sectors);
                 assert_eq!(device.sectors, 100 * 1024 * 1024 / 512, "EFI sector length is off");
                 assert_eq!(partition.filesystem, Some(FileSystem::Vfat));
+                assert_eq!(partition.kind, Some(PartitionKind::EfiSystem));
             } else if child == entity_root {
                 assert_eq!(partition.filesystem, Some(FileSystem::Ext4));
             } else if child == entity_swap {
                 assert_eq!(device.sectors, 1000 * 1024 * 1024 / 512, "Swap sector length is off");
                 assert_eq!(partition.filesystem, Some(FileSystem::Swap));
+                assert_eq!(partition.kind, Some(PartitionKind::LinuxSwap));
             } else {
                 panic!("device has an entity which the test did not create");
             }
@@ -98,6 +131,1663 @@ fn create_partition_table() {
     });
 }
 
+#[test]
+fn create_mbr_partition_table() {
+    setup(|mut manager, entity| {
+        manager.create_table(entity, PartitionTable::Mbr, UuidPolicy::Random).unwrap();
+
+        let entity_boot = manager
+            .create_as_child_of(
+                entity,
+                Sector::Start,
+                Sector::Megabyte(512),
+                Box::from(""),
+                None,
+                UuidPolicy::Random,
+                ops::create::PartitionCreate::Plain(FileSystem::Ext4, FormatOptions::default()),
+            )
+            .unwrap();
+
+        let entity_extended = manager
+            .create_as_child_of(
+                entity,
+                Sector::Megabyte(512),
+                Sector::End,
+                Box::from(""),
+                None,
+                UuidPolicy::Random,
+                ops::create::PartitionCreate::Extended,
+            )
+            .unwrap();
+
+        // Partitions created within the extended partition become logical partitions.
+        let entity_root = manager
+            .create_as_child_of(
+                entity,
+                Sector::Megabyte(513),
+                Sector::MegabyteFromEnd(1000),
+                Box::from(""),
+                None,
+                UuidPolicy::Random,
+                ops::create::PartitionCreate::Plain(FileSystem::Ext4, FormatOptions::default()),
+            )
+            .unwrap();
+
+        let entity_swap = manager
+            .create_as_child_of(
+                entity,
+                Sector::MegabyteFromEnd(999),
+                Sector::End,
+                Box::from(""),
+                None,
+                UuidPolicy::Random,
+                ops::create::PartitionCreate::Plain(FileSystem::Swap, FormatOptions::default()),
+            )
+            .unwrap();
+
+        apply(&mut manager);
+
+        let children = manager.children(entity).expect("did not create children");
+        for &child in children {
+            let partition = &manager.components.devices.partitions[child];
+
+            if child == entity_boot {
+                assert_eq!(partition.mbr_variant, PartitionType::Primary);
+                assert_eq!(partition.number, 1);
+            } else if child == entity_extended {
+                assert_eq!(partition.mbr_variant, PartitionType::Extended);
+                assert_eq!(partition.number, 2);
+            } else if child == entity_root {
+                assert_eq!(partition.mbr_variant, PartitionType::Logical);
+                assert_eq!(partition.number, 5);
+            } else if child == entity_swap {
+                assert_eq!(partition.mbr_variant, PartitionType::Logical);
+                assert_eq!(partition.number, 6);
+            } else {
+                panic!("device has an entity which the test did not create");
+            }
+        }
+
+        // Logical partitions which follow a removed logical partition take its number.
+        manager.remove(entity_root);
+        apply(&mut manager);
+
+        assert_eq!(manager.children(entity).unwrap().len(), 3);
+        let swap = &manager.components.devices.partitions[entity_swap];
+        assert_eq!(swap.number, 5);
+        assert!(swap.partuuid.as_ref().map_or(false, |uuid| uuid.ends_with("-05")));
+
+        let path = manager.device(entity_swap).path.to_string_lossy().into_owned();
+        assert!(path.ends_with('5'), "{} was not renumbered", path);
+    });
+}
+
+#[test]
+fn mbr_partition_labels() {
+    let mut manager = DiskManager::default();
+    let entity = manager.entities.devices.insert(EntityFlags::SUPPORTS_TABLE);
+    let devices = &mut manager.components.devices;
+    devices.devices.insert(entity, device(1024 * 1024, 512));
+    devices.tables.insert(entity, PartitionTable::Mbr);
+    devices.children.insert(entity, Vec::new());
+
+    let mut create = |label: &str| {
+        manager.create_as_child_of(
+            entity,
+            Sector::Start,
+            Sector::End,
+            Box::from(label),
+            None,
+            UuidPolicy::Random,
+            ops::create::PartitionCreate::Plain(FileSystem::Ext4, FormatOptions::default()),
+        )
+    };
+
+    // MBR tables have nowhere to store a partition label, so it is rejected before queueing.
+    assert_eq!(create("Root"), Err(ops::create::Error::LabelUnsupported));
+    let child = create("").unwrap();
+
+    assert_eq!(
+        manager.label(child, "Root"),
+        Err(ops::modify::Error::PartLabelUnsupported(PartitionTable::Mbr))
+    );
+    assert!(manager.components.queued_changes.labels.get(child).is_none());
+}
+
+#[test]
+fn partition_table_conversion_plans() {
+    use disk_ops::table::{mbr_guid, parse_mbr_guid, plan_conversion, PlanError, TablePartition};
+
+    // Partitions of 2048 sectors, with a gap of 2048 sectors before each.
+    let partition = |number: u32, kind| TablePartition {
+        number,
+        start: u64::from(number) * 4096,
+        end: u64::from(number) * 4096 + 2047,
+        name: None,
+        uuid: format!("{:08x}-{:02x}", 0x1234_abcd, number).into(),
+        variant: PartitionType::Primary,
+        kind,
+        attributes: PartitionAttributes::empty(),
+    };
+
+    let layout = |numbers: &[u32]| {
+        numbers.iter().map(|&number| partition(number, Some(PartitionKind::LinuxData))).collect()
+    };
+
+    let plan = |partitions: &[TablePartition], table, sectors| {
+        plan_conversion(partitions, table, sectors, 512)
+            .map(|planned| planned.iter().map(|new| (new.variant, new.number)).collect::<Vec<_>>())
+    };
+
+    let small: Vec<TablePartition> = layout(&[1, 2, 3]);
+    let sectors = 1024 * 1024;
+
+    // MBR tables cannot address disks larger than 2 TiB.
+    let too_large = u64::from(u32::max_value()) + 1;
+    assert_eq!(
+        plan(&small, PartitionTable::Mbr, too_large),
+        Err(PlanError::DiskTooLarge(too_large))
+    );
+    assert!(plan(&small, PartitionTable::Guid, too_large).is_ok());
+
+    let unknown = vec![partition(1, None)];
+    assert_eq!(plan(&unknown, PartitionTable::Guid, sectors), Err(PlanError::KindUnknown(1)));
+
+    let msr = vec![partition(1, Some(PartitionKind::MicrosoftReserved))];
+    assert_eq!(
+        plan(&msr, PartitionTable::Mbr, sectors),
+        Err(PlanError::KindUnsupported(1, PartitionKind::MicrosoftReserved))
+    );
+
+    // The first partition begins within the primary GPT header and entries.
+    let mut early = layout(&[1]);
+    early[0].start = 2;
+    assert_eq!(plan(&early, PartitionTable::Guid, sectors), Err(PlanError::NoRoomForGpt));
+
+    // The last partition ends within the backup GPT header and entries.
+    let late = layout(&[255]);
+    assert_eq!(plan(&late, PartitionTable::Guid, 255 * 4096 + 2048), Err(PlanError::NoRoomForGpt));
+
+    // More than four partitions require logical partitions, each preceded by a free sector.
+    let mut adjacent: Vec<TablePartition> = layout(&[1, 2, 3, 4, 5]);
+    for partition in &mut adjacent {
+        partition.start = u64::from(partition.number) * 2048;
+        partition.end = partition.start + 2047;
+    }
+
+    assert_eq!(plan(&adjacent, PartitionTable::Mbr, sectors), Err(PlanError::NoRoomForEbr(4)));
+
+    // Primary partitions keep their numbers, and the extended partition takes a free number.
+    let spaced: Vec<TablePartition> = layout(&[1, 2, 3, 4, 5]);
+    assert_eq!(
+        plan(&spaced, PartitionTable::Mbr, sectors),
+        Ok(vec![
+            (PartitionType::Primary, Some(1)),
+            (PartitionType::Primary, Some(2)),
+            (PartitionType::Primary, Some(3)),
+            (PartitionType::Extended, Some(4)),
+            (PartitionType::Logical, None),
+            (PartitionType::Logical, None),
+        ])
+    );
+
+    // Numbers which MBR tables do not have room for are given the numbers not in use.
+    let gpt: Vec<TablePartition> = layout(&[2, 7, 3]);
+    assert_eq!(
+        plan(&gpt, PartitionTable::Mbr, sectors),
+        Ok(vec![
+            (PartitionType::Primary, Some(2)),
+            (PartitionType::Primary, Some(3)),
+            (PartitionType::Primary, Some(1)),
+        ])
+    );
+
+    assert_eq!(
+        plan(&gpt, PartitionTable::Guid, sectors),
+        Ok(vec![
+            (PartitionType::Primary, Some(2)),
+            (PartitionType::Primary, Some(3)),
+            (PartitionType::Primary, Some(7)),
+        ])
+    );
+
+    // MBR PARTUUIDs are carried through GPT, and back.
+    let guid = disk_ops::table::convert_array_to_str(&mbr_guid(0x1234_abcd, 2));
+    assert_eq!(guid, "1234ABCD-0002-0000-0000-000000000000");
+    assert_eq!(parse_mbr_guid(&guid), Some((0x1234_abcd, 2)));
+    assert_eq!(parse_mbr_guid("1234ABCD-0002-4000-8000-000000000000"), None);
+}
+
+#[test]
+fn kernel_partition_changes() {
+    use disk_ops::table::{kernel_changes, KernelChange, KernelPartition, TablePartition};
+
+    let partition = |number, start, end, variant| TablePartition {
+        number,
+        start,
+        end,
+        name: None,
+        uuid: "".into(),
+        variant,
+        kind: None,
+        attributes: PartitionAttributes::empty(),
+    };
+
+    let known = |number, start: u64, length: u64| KernelPartition {
+        number,
+        start: start * 512,
+        length: length * 512,
+    };
+
+    // 1 is unchanged, 2 is grown, 3 is shrunk, 4 is moved, 5 is removed, and 6 is added.
+    let kernel = vec![
+        known(1, 2048, 2048),
+        known(2, 4096, 2048),
+        known(3, 8192, 4096),
+        known(4, 16384, 2048),
+        known(5, 20480, 2048),
+    ];
+
+    let table = vec![
+        partition(1, 2048, 4095, PartitionType::Primary),
+        partition(2, 4096, 8191, PartitionType::Primary),
+        partition(3, 8192, 10239, PartitionType::Primary),
+        partition(4, 18432, 20479, PartitionType::Primary),
+        partition(6, 24576, 26623, PartitionType::Primary),
+    ];
+
+    assert_eq!(
+        kernel_changes(&kernel, &table, 512),
+        vec![
+            KernelChange::Remove(known(4, 16384, 2048)),
+            KernelChange::Remove(known(5, 20480, 2048)),
+            KernelChange::Resize(known(3, 8192, 2048)),
+            KernelChange::Resize(known(2, 4096, 4096)),
+            KernelChange::Add(known(4, 18432, 2048)),
+            KernelChange::Add(known(6, 24576, 2048)),
+        ]
+    );
+
+    // Extended partitions are presented by the kernel as 1 KiB, whatever their size.
+    let extended = vec![partition(2, 4096, 8191, PartitionType::Extended)];
+    let kernel = vec![KernelPartition { number: 2, start: 4096 * 4096, length: 1024 }];
+    assert_eq!(kernel_changes(&kernel, &extended, 4096), Vec::new());
+    assert_eq!(
+        kernel_changes(&[], &extended, 4096),
+        vec![KernelChange::Add(KernelPartition { number: 2, start: 4096 * 4096, length: 1024 })]
+    );
+}
+
+#[test]
+fn convert_partition_table() {
+    setup(|mut manager, entity| {
+        manager.create_table(entity, PartitionTable::Mbr, UuidPolicy::Random).unwrap();
+
+        let entity_boot = manager
+            .create_as_child_of(
+                entity,
+                Sector::Start,
+                Sector::Megabyte(512),
+                Box::from(""),
+                None,
+                UuidPolicy::Random,
+                ops::create::PartitionCreate::Plain(FileSystem::Vfat, FormatOptions::default()),
+            )
+            .unwrap();
+
+        let entity_root = manager
+            .create_as_child_of(
+                entity,
+                Sector::Megabyte(512),
+                Sector::End,
+                Box::from(""),
+                None,
+                UuidPolicy::Random,
+                ops::create::PartitionCreate::Plain(FileSystem::Ext4, FormatOptions::default()),
+            )
+            .unwrap();
+
+        apply(&mut manager);
+
+        let offsets = [entity_boot, entity_root]
+            .iter()
+            .map(|&child| manager.components.devices.partitions[child].offset)
+            .collect::<Vec<_>>();
+
+        manager.convert_table(entity, PartitionTable::Guid).unwrap();
+        apply(&mut manager);
+
+        assert_eq!(manager.components.devices.tables[entity], PartitionTable::Guid);
+
+        // Partitions must retain their sectors.
+        for (&child, &offset) in [entity_boot, entity_root].iter().zip(offsets.iter()) {
+            let partition = &manager.components.devices.partitions[child];
+            assert_eq!(partition.offset, offset);
+        }
+
+        assert_eq!(manager.components.devices.partitions[entity_boot].number, 1);
+        assert_eq!(manager.components.devices.partitions[entity_root].number, 2);
+    });
+}
+
+#[test]
+fn dump_and_restore_partition_table() {
+    setup(|mut manager, entity| {
+        manager.create_table(entity, PartitionTable::Guid, UuidPolicy::Random).unwrap();
+
+        for &(start, end, label) in &[
+            (Sector::Start, Sector::Megabyte(512), "Boot"),
+            (Sector::Megabyte(512), Sector::End, "Root"),
+        ] {
+            manager
+                .create_as_child_of(
+                    entity,
+                    start,
+                    end,
+                    Box::from(label),
+                    None,
+                    UuidPolicy::Random,
+                    ops::create::PartitionCreate::Plain(FileSystem::Ext4, FormatOptions::default()),
+                )
+                .unwrap();
+        }
+
+        apply(&mut manager);
+
+        let dump = manager.dump_table(entity).unwrap().unwrap();
+        assert_eq!(dump.partitions.len(), 2);
+
+        // The dump must survive a round trip through the sfdisk script format.
+        let dump = dump.to_string().parse::<TableDump>().unwrap();
+
+        // Dumps which do not fit the device are rejected before anything is queued.
+        let mut oversized = dump.clone();
+        oversized.partitions[1].size *= 2;
+        assert_eq!(
+            manager.restore_table(entity, oversized),
+            Err(ops::create::Error::Restore(disk_ops::table::RestoreError::OutOfBounds))
+        );
+
+        manager.create_table(entity, PartitionTable::Mbr, UuidPolicy::Random).unwrap();
+        apply(&mut manager);
+
+        manager.restore_table(entity, dump.clone()).unwrap();
+        apply(&mut manager);
+
+        assert_eq!(manager.components.devices.tables[entity], PartitionTable::Guid);
+
+        let children = manager.children(entity).unwrap();
+        assert_eq!(children.len(), 2);
+
+        for (&child, expected) in children.iter().zip(dump.partitions.iter()) {
+            let partition = &manager.components.devices.partitions[child];
+            assert_eq!(partition.offset, expected.start);
+            assert_eq!(partition.number, expected.number);
+            assert_eq!(partition.partuuid, expected.uuid);
+            assert_eq!(partition.partlabel, expected.name);
+        }
+    });
+}
+
+#[test]
+fn restore_table_checked() {
+    use disk_ops::table::RestoreError;
+    use ops::create::Error;
+
+    let mut manager = DiskManager::default();
+    let entity = manager.entities.devices.insert(EntityFlags::SUPPORTS_TABLE);
+    manager.components.devices.devices.insert(entity, device(1024 * 1024, 512));
+
+    let partition = |number, start, size, type_id: &str| DumpPartition {
+        number,
+        start,
+        size,
+        type_id: type_id.into(),
+        uuid: None,
+        name: None,
+        attributes: 0,
+    };
+
+    let dump = |partitions| TableDump {
+        table: PartitionTable::Mbr,
+        label_id: Some("0x1234abcd".into()),
+        device: None,
+        first_lba: None,
+        last_lba: None,
+        sector_size: Some(512),
+        partitions,
+    };
+
+    let cases = vec![
+        (dump(vec![partition(1, 2048, 1024 * 1024, "83")]), RestoreError::OutOfBounds),
+        (
+            dump(vec![partition(1, 2048, 4096, "83"), partition(2, 4096, 4096, "83")]),
+            RestoreError::Overlap,
+        ),
+        (dump(vec![partition(1, 2048, 4096, "linux")]), RestoreError::TypeInvalid),
+        (dump(vec![partition(0, 2048, 4096, "83")]), RestoreError::NumberInvalid(0)),
+        (dump(vec![partition(5, 2048, 4096, "83")]), RestoreError::ExtendedNotFound),
+        (
+            TableDump { label_id: Some("0xdisk".into()), ..dump(Vec::new()) },
+            RestoreError::UuidInvalid,
+        ),
+        (
+            TableDump { sector_size: Some(4096), ..dump(Vec::new()) },
+            RestoreError::SectorSizeMismatch(4096, 512),
+        ),
+    ];
+
+    // Dumps which cannot be restored are rejected before the device is marked to be wiped.
+    for (dump, error) in cases {
+        assert_eq!(manager.restore_table(entity, dump), Err(Error::Restore(error)));
+        assert!(!manager.entities.devices[entity].contains(EntityFlags::REMOVE));
+        assert!(manager.components.queued_changes.restores.is_empty());
+    }
+
+    let partitions = vec![partition(1, 2048, 4096, "ef"), partition(2, 6144, 4096, "83")];
+    manager.restore_table(entity, dump(partitions)).unwrap();
+    assert!(manager.entities.devices[entity].contains(EntityFlags::REMOVE));
+    assert!(manager.components.queued_changes.restores.contains_key(entity));
+}
+
+#[test]
+fn table_dump_round_trip() {
+    let partition = |number, start, size, type_id: &str| DumpPartition {
+        number,
+        start,
+        size,
+        type_id: type_id.into(),
+        uuid: None,
+        name: None,
+        attributes: 0,
+    };
+
+    let gpt = TableDump {
+        table:       PartitionTable::Guid,
+        label_id:    Some("2E7E5E3D-8A0E-4B6C-9E1B-5C1E8A7C6F10".into()),
+        device:      Some("/dev/nvme0n1".into()),
+        first_lba:   Some(34),
+        last_lba:    Some(1_048_542),
+        sector_size: Some(512),
+        partitions:  vec![
+            DumpPartition {
+                uuid: Some("A3C3E9F2-4B1D-4E8A-9C5F-0D6B7E8F9A01".into()),
+                name: Some(r#"EFI "System", \boot"#.into()),
+                attributes: 1 << 0 | 1 << 2 | 1 << 48 | 1 << 60,
+                ..partition(1, 2048, 1_048_576, PartitionKind::EfiSystem.guid())
+            },
+            partition(3, 1_050_624, 4096, PartitionKind::LinuxData.guid()),
+        ],
+    };
+
+    let text = gpt.to_string();
+    assert!(text.contains(concat!(
+        "/dev/nvme0n1p1 : start=        2048, size=     1048576, ",
+        "type=C12A7328-F81F-11D2-BA4B-00A0C93EC93B, ",
+        "uuid=A3C3E9F2-4B1D-4E8A-9C5F-0D6B7E8F9A01, ",
+        r#"name="EFI \"System\", \\boot", "#,
+        r#"attrs="RequiredPartition LegacyBIOSBootable GUID:48,60""#,
+        "\n",
+    )));
+
+    let parsed = text.parse::<TableDump>().unwrap();
+    assert_eq!(parsed, gpt);
+    assert_eq!(parsed.to_string(), text);
+
+    let mbr = TableDump {
+        table:       PartitionTable::Mbr,
+        label_id:    Some("0x1234abcd".into()),
+        device:      Some("/dev/sda".into()),
+        first_lba:   None,
+        last_lba:    None,
+        sector_size: Some(512),
+        partitions:  vec![
+            DumpPartition { attributes: 1 << 2, ..partition(1, 2048, 204_800, "ef") },
+            partition(2, 206_848, 409_600, "83"),
+            partition(5, 618_496, 2048, "82"),
+        ],
+    };
+
+    let text = concat!(
+        "label: dos\n",
+        "label-id: 0x1234abcd\n",
+        "device: /dev/sda\n",
+        "unit: sectors\n",
+        "sector-size: 512\n",
+        "\n",
+        "/dev/sda1 : start=        2048, size=      204800, type=ef, bootable\n",
+        "/dev/sda2 : start=      206848, size=      409600, type=83\n",
+        "/dev/sda5 : start=      618496, size=        2048, type=82\n",
+    );
+
+    assert_eq!(mbr.to_string(), text);
+    assert_eq!(text.parse::<TableDump>().unwrap(), mbr);
+
+    // Comments, blank lines, unknown fields and the legacy `Id` field are accepted.
+    let legacy = concat!(
+        "# partition table of /dev/sda\n",
+        "label: dos\n",
+        "label-id: 0x1234abcd\n",
+        "device: /dev/sda\n",
+        "unit: sectors\n",
+        "sector-size: 512\n",
+        "grain: 1M\n",
+        "\n",
+        "/dev/sda1 : start=2048, size=204800, Id=ef, bootable\n",
+        "/dev/sda2 : start=206848,size=409600,Id=83\n",
+        "/dev/sda5 : start= 618496, size= 2048, Id= 82\n",
+    );
+
+    assert_eq!(legacy.parse::<TableDump>().unwrap(), mbr);
+}
+
+#[test]
+fn table_dump_malformed() {
+    let error = |input: &str| input.parse::<TableDump>().unwrap_err().to_string();
+    let header = "label: gpt\nunit: sectors\n";
+    let partition = |fields: &str| error(&[header, "/dev/sda1 : ", fields, "\n"].concat());
+
+    assert_eq!(error(""), "the dump does not specify a partition table label");
+    assert_eq!(error("unit: sectors\n"), "the dump does not specify a partition table label");
+    assert_eq!(error("label: sun\n"), "line 1: `sun` is not a supported partition table label");
+    assert_eq!(error("label: gpt\nunit: cylinders\n"), "line 2: only sector units are supported");
+    assert_eq!(error("label: gpt\nlabel-id\n"), "line 2: invalid value for the `label-id` field");
+    assert_eq!(
+        error("label: gpt\nfirst-lba: 34s\n"),
+        "line 2: invalid value for the `first-lba` field"
+    );
+    assert_eq!(
+        error("label: gpt\nsector-size: -512\n"),
+        "line 2: invalid value for the `sector-size` field"
+    );
+
+    assert_eq!(
+        error(&[header, "/dev/sda : start=2048, size=2048, type=83\n"].concat()),
+        "line 3: the partition device does not end with a partition number"
+    );
+    assert_eq!(partition("size=2048, type=83"), "line 3: the `start` field is required");
+    assert_eq!(partition("start=2048, type=83"), "line 3: the `size` field is required");
+    assert_eq!(partition("start=2048, size=0, type=83"), "line 3: the `size` field is required");
+    assert_eq!(partition("start=2048, size=2048"), "line 3: the `type` field is required");
+    assert_eq!(
+        partition("start=0x800, size=2048, type=83"),
+        "line 3: invalid value for the `start` field"
+    );
+    assert_eq!(
+        partition("start=2048, size=-1, type=83"),
+        "line 3: invalid value for the `size` field"
+    );
+    assert_eq!(
+        partition("start=2048, size=18446744073709551615, type=83"),
+        "line 3: invalid value for the `size` field"
+    );
+    assert_eq!(
+        partition("start=2048, size=2048, type=83, attrs=\"Hidden\""),
+        "line 3: invalid value for the `attrs` field"
+    );
+    assert_eq!(
+        partition("start=2048, size=2048, type=83, attrs=\"GUID:47\""),
+        "line 3: invalid value for the `attrs` field"
+    );
+
+    // A comma within a quoted name does not end the field.
+    let dump = [header, "/dev/sda1 : start=2048, size=2048, type=83, name=\"a, b\"\n"].concat();
+    let dump = dump.parse::<TableDump>().unwrap();
+    assert_eq!(dump.partitions[0].name, Some("a, b".into()));
+
+    // Device paths may contain colons, as those in `/dev/disk/by-path` do.
+    let dump = concat!(
+        "label: gpt\n",
+        "device: /dev/disk/by-path/pci-0000:00:1f.2-ata-1\n",
+        "unit: sectors\n",
+        "\n",
+        "/dev/disk/by-path/pci-0000:00:1f.2-ata-1-part2 : start=2048, size=2048, type=83\n",
+    );
+
+    let dump = dump.parse::<TableDump>().unwrap();
+    assert_eq!(dump.device, Some("/dev/disk/by-path/pci-0000:00:1f.2-ata-1".into()));
+    assert_eq!((dump.partitions[0].number, dump.partitions[0].start), (2, 2048));
+}
+
+#[test]
+fn gpt_verify_and_repair() {
+    use disk_ops::table::Gpt;
+    use std::{
+        fs::OpenOptions,
+        io::{Seek, SeekFrom, Write},
+    };
+
+    setup(|mut manager, entity| {
+        manager.create_table(entity, PartitionTable::Guid, UuidPolicy::Random).unwrap();
+        apply(&mut manager);
+
+        let device = manager.device(entity);
+        let (path, sector_size) = (device.path(), device.logical_sector_size);
+        assert!(Gpt::verify(path, sector_size).unwrap().is_healthy());
+
+        // Erase the primary header, so that it must be rebuilt from the backup.
+        let mut file = OpenOptions::new().write(true).open(path).unwrap();
+        file.seek(SeekFrom::Start(sector_size)).unwrap();
+        file.write_all(&vec![0; sector_size as usize]).unwrap();
+        file.sync_all().unwrap();
+
+        let health = Gpt::verify(path, sector_size).unwrap();
+        assert_eq!(health.primary, HeaderHealth::Missing);
+        assert_eq!(health.backup, HeaderHealth::Valid);
+
+        assert!(Gpt::repair(path, sector_size).unwrap().is_healthy());
+    });
+}
+
+#[test]
+fn gpt_relocate_backup_header() {
+    use disk_ops::table::Gpt;
+    use std::{fs::OpenOptions, process::Command};
+
+    setup(|mut manager, entity| {
+        manager.create_table(entity, PartitionTable::Guid, UuidPolicy::Random).unwrap();
+        apply(&mut manager);
+
+        let sectors = manager.device(entity).sectors;
+        assert_eq!(manager.table_growth(entity).unwrap().unwrap(), 0);
+
+        // Grow the backing file, and inform the loop driver of its new capacity.
+        let backing = manager.backing_file(entity).unwrap().to_path_buf();
+        let file = OpenOptions::new().write(true).open(&backing).unwrap();
+        file.set_len(file.metadata().unwrap().len() + 512 * 1024 * 1024).unwrap();
+        Command::new("losetup").arg("-c").arg(manager.device(entity).path()).status().unwrap();
+
+        let grown = 512 * 1024 * 1024 / manager.device(entity).logical_sector_size;
+        assert_eq!(manager.table_growth(entity).unwrap().unwrap(), grown);
+
+        manager.relocate_backup_header(entity).unwrap();
+        apply(&mut manager);
+
+        let device = manager.device(entity);
+        assert_eq!(device.sectors, sectors + grown);
+        assert_eq!(manager.table_growth(entity).unwrap().unwrap(), 0);
+
+        let health = Gpt::verify(device.path(), device.logical_sector_size).unwrap();
+        assert!(health.is_healthy());
+    });
+}
+
+#[test]
+fn partition_in_memory_image() {
+    use disk_ops::table::{Gpt, Mbr, NewPartition, Partitioner};
+    use std::io::Cursor;
+
+    let mut image = Cursor::new(vec![0u8; 64 * 1024 * 1024]);
+    let new = NewPartition { start: 2048, end: 4095, name: Some("image"), ..Default::default() };
+
+    // Nothing, not even the protective MBR, is written before the table is.
+    Gpt::create_from(&mut image, 512).unwrap().add(new).unwrap();
+    assert!(image.get_ref().iter().all(|&byte| byte == 0));
+
+    let mut gpt = Gpt::create_from(image, 512).unwrap();
+    assert_eq!(gpt.add(new).unwrap(), 1);
+    gpt.write().unwrap();
+
+    let gpt = Gpt::open_from(gpt.into_inner()).unwrap();
+    let partitions = gpt.partitions();
+    assert_eq!(partitions.len(), 1);
+    assert_eq!((partitions[0].start, partitions[0].end), (2048, 4095));
+    assert_eq!(partitions[0].name.as_ref().map(AsRef::as_ref), Some("image"));
+
+    let mut image = gpt.into_inner().into_inner();
+    let mut mbr = Mbr::create_from(Cursor::new(&mut image[..]), 512).unwrap();
+    assert_eq!(mbr.add(NewPartition { name: None, ..new }).unwrap(), 1);
+    mbr.write().unwrap();
+
+    let partitions = Mbr::open_from(Cursor::new(&mut image[..])).unwrap().partitions();
+    assert_eq!(partitions.len(), 1);
+    assert_eq!((partitions[0].start, partitions[0].end), (2048, 4095));
+}
+
+#[test]
+fn sector_units() {
+    assert_eq!("512Mi".parse::<Sector>(), Ok(Sector::Bytes(512 * 1024 * 1024)));
+    assert_eq!("-4G".parse::<Sector>(), Ok(Sector::BytesFromEnd(4_000_000_000)));
+    assert_eq!("4096B".parse::<Sector>(), Ok(Sector::Bytes(4096)));
+    assert_eq!("100M".parse::<Sector>(), Ok(Sector::Megabyte(100)));
+    assert_eq!("100%free".parse::<Sector>(), Ok(Sector::FreePercent(::std::u16::MAX)));
+    assert!("99999999999Ti".parse::<Sector>().is_err());
+
+    let disk = device(2 * 1024 * 1024, 512);
+    assert_eq!(disk.get_sector(Sector::size(1, ByteUnit::Gibibyte).unwrap()), Ok(2 * 1024 * 1024));
+    assert_eq!(disk.get_sector(Sector::FreePercent(::std::u16::MAX)), disk.get_sector(Sector::End));
+    assert_eq!(disk.get_sector_within(Sector::FreePercent(::std::u16::MAX / 2), 100..300), Ok(199));
+
+    // A device with no room for the headers of a table cannot have an end.
+    let tiny = device(4096, 512);
+    assert_eq!(tiny.get_sector(Sector::End), Err(SectorError::DeviceTooSmall));
+    assert_eq!(tiny.get_sector(Sector::MegabyteFromEnd(1)), Err(SectorError::DeviceTooSmall));
+    assert_eq!(disk.get_sector(Sector::UnitFromEnd(1 << 40)), Err(SectorError::OutOfRange));
+}
+
+#[test]
+fn sizes() {
+    let disk = device(2 * 1024 * 1024, 4096);
+
+    assert_eq!(disk.size(), Size::from_bytes(8 << 30));
+    assert_eq!(disk.size().to_string(), "8.0 GiB");
+    assert_eq!(Size::from_bytes(512).to_string(), "512 B");
+    assert_eq!(Size::from_bytes(13_314_398_618).to_string(), "12.4 GiB");
+
+    assert_eq!("12.4 GiB".parse::<Size>(), Ok(Size::from_bytes(13_314_398_617)));
+    assert_eq!("512Mi".parse::<Size>(), Ok(Size::from_bytes(512 << 20)));
+    assert_eq!("4GB".parse::<Size>(), Ok(Size::from_bytes(4_000_000_000)));
+    assert_eq!("100".parse::<Size>(), Ok(Size::from_bytes(100)));
+    assert!("1.2.3G".parse::<Size>().is_err());
+    assert!("99999999999Ti".parse::<Size>().is_err());
+
+    let size = Size::from_sectors(3, &disk).unwrap();
+    assert_eq!(size.bytes(), 3 * 4096);
+    assert_eq!(Size::from_bytes(size.bytes() + 1).to_sectors(&disk), 3);
+    assert_eq!(Size::from_sectors(::std::u64::MAX, &disk), None);
+}
+
+#[test]
+fn partition_kinds() {
+    use disk_types::Architecture::*;
+    use PartitionKind::*;
+
+    let guids = [
+        ("C12A7328-F81F-11D2-BA4B-00A0C93EC93B", EfiSystem),
+        ("21686148-6449-6E6F-744E-656564454649", BiosBoot),
+        ("0FC63DAF-8483-4772-8E79-3D69D8477DE4", LinuxData),
+        ("44479540-F297-41B2-9AF7-D131D5F0458A", LinuxRoot(X86)),
+        ("4F68BCE3-E8CD-4DB1-96E7-FBCAF984B709", LinuxRoot(X86_64)),
+        ("69DAD710-2CE4-4E3C-B16C-21A1D49ABED3", LinuxRoot(Arm)),
+        ("B921B045-1DF0-41C3-AF44-4C6F280D3FAE", LinuxRoot(Aarch64)),
+        ("C31C45E6-3F39-412E-80FB-4809C4980599", LinuxRoot(PowerPc64Le)),
+        ("72EC70A6-CF74-40E6-BD49-4BDA08E8F224", LinuxRoot(RiscV64)),
+        ("933AC7E1-2EB4-4F13-B844-0E14E2AEF915", LinuxHome),
+        ("3B8F8425-20E0-4F3B-907F-1A25A76F98E8", LinuxSrv),
+        ("4D21B016-B534-45C2-A9FB-5C16E091FD2D", LinuxVar),
+        ("7EC6F557-3BC5-4ACA-B293-16EF5DF639D1", LinuxTmp),
+        ("0657FD6D-A4AB-43C4-84E5-0933C84B4F4F", LinuxSwap),
+        ("E6D6D379-F507-44C2-A23C-238F2A3DF928", LinuxLvm),
+        ("CA7D7CCB-63ED-4C53-861C-1742536059CC", LinuxLuks),
+        ("A19D880F-05FC-4D3B-A006-743F0F84911E", LinuxRaid),
+        ("E3C9E316-0B5C-4DB8-817D-F92DF00215AE", MicrosoftReserved),
+        ("EBD0A0A2-B9E5-4433-87C0-68B6B72699C7", MicrosoftBasicData),
+        ("DE94BBA4-06D1-4D40-A16A-BFD50179D6AC", WindowsRecovery),
+        ("48465300-0000-11AA-AA11-00306543ECAC", AppleHfs),
+        ("7C3457EF-0000-11AA-AA11-00306543ECAC", AppleApfs),
+    ];
+
+    for &(guid, kind) in &guids {
+        assert_eq!(PartitionKind::from_guid(guid), Some(kind), "{}", guid);
+        assert_eq!(PartitionKind::from_guid(&guid.to_lowercase()), Some(kind), "{}", guid);
+        assert_eq!(kind.guid(), guid);
+    }
+
+    // Type GUIDs which are not known, or not GUIDs at all, have no kind.
+    assert_eq!(PartitionKind::from_guid("00000000-0000-0000-0000-000000000000"), None);
+    assert_eq!(PartitionKind::from_guid("6A898CC3-1DD2-11B2-99A6-080020736631"), None);
+    assert_eq!(PartitionKind::from_guid(""), None);
+
+    let ids = [
+        (0x07, MicrosoftBasicData),
+        (0x0B, MicrosoftBasicData),
+        (0x0C, MicrosoftBasicData),
+        (0x0E, MicrosoftBasicData),
+        (0x27, WindowsRecovery),
+        (0x82, LinuxSwap),
+        (0x83, LinuxData),
+        (0x8E, LinuxLvm),
+        (0xAF, AppleHfs),
+        (0xE8, LinuxLuks),
+        (0xEF, EfiSystem),
+        (0xFD, LinuxRaid),
+    ];
+
+    for id in 0..=255u8 {
+        let expected = ids.iter().find(|&&(known, _)| known == id).map(|&(_, kind)| kind);
+        assert_eq!(PartitionKind::from_mbr_id(id), expected, "{:#04x}", id);
+    }
+
+    // Shared system IDs resolve to the most generic kind, which maps back to the same ID.
+    for &(id, kind) in &ids {
+        let expected = if kind == MicrosoftBasicData { 0x07 } else { id };
+        assert_eq!(kind.mbr_id(), Some(expected));
+    }
+
+    for &kind in &[LinuxRoot(X86_64), LinuxHome, LinuxSrv, LinuxVar, LinuxTmp] {
+        assert_eq!(kind.mbr_id(), Some(0x83));
+    }
+
+    for &kind in &[BiosBoot, MicrosoftReserved, AppleApfs] {
+        assert_eq!(kind.mbr_id(), None);
+    }
+
+    assert_eq!(PartitionKind::default(), LinuxData);
+}
+
+#[test]
+fn partitions_attributes() {
+    setup(|mut manager, entity| {
+        manager.create_table(entity, PartitionTable::Guid, UuidPolicy::Random).unwrap();
+
+        let entity_data = manager
+            .create_as_child_of(
+                entity,
+                Sector::Start,
+                Sector::End,
+                Box::from("Data"),
+                None,
+                UuidPolicy::Random,
+                ops::create::PartitionCreate::Plain(FileSystem::Ext4, FormatOptions::default()),
+            )
+            .unwrap();
+
+        apply(&mut manager);
+
+        let attributes = PartitionAttributes::READ_ONLY | PartitionAttributes::NO_AUTOMOUNT;
+        manager.set_attributes(entity_data, attributes).unwrap();
+        apply(&mut manager);
+
+        let partition = &manager.components.devices.partitions[entity_data];
+        assert_eq!(partition.attributes, attributes);
+    });
+}
+
+#[test]
+fn uuid_digests() {
+    use disk_ops::table::{hmac_sha256, sha256};
+
+    let hex =
+        |digest: [u8; 32]| digest.iter().map(|byte| format!("{:02x}", byte)).collect::<String>();
+
+    // FIPS 180-4 examples, and the long message of the NIST test vectors.
+    assert_eq!(
+        hex(sha256(b"abc")),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+    assert_eq!(
+        hex(sha256(b"")),
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+    );
+    assert_eq!(
+        hex(sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")),
+        "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+    );
+    assert_eq!(
+        hex(sha256(&vec![b'a'; 1_000_000])),
+        "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"
+    );
+
+    // RFC 4231 test cases 1 to 4, 6 and 7. Test case 5 truncates its output.
+    let cases: &[(&[u8], &[u8], &str)] = &[
+        (
+            &[0x0b; 20],
+            b"Hi There",
+            "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7",
+        ),
+        (
+            b"Jefe",
+            b"what do ya want for nothing?",
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+        ),
+        (
+            &[0xaa; 20],
+            &[0xdd; 50],
+            "773ea91e36800e46854db8ebd09181a72959098b3ef8c122d9635514ced565fe",
+        ),
+        (
+            &[
+                1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23,
+                24, 25,
+            ],
+            &[0xcd; 50],
+            "82558a389a443c0ea4cc819899f2083a85f0faa3e578f8077a2e3ff46729665b",
+        ),
+        (
+            &[0xaa; 131],
+            b"Test Using Larger Than Block-Size Key - Hash Key First",
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+        ),
+        (
+            &[0xaa; 131],
+            b"This is a test using a larger than block-size key and a larger than block-size \
+              data. The key needs to be hashed before being used by the HMAC algorithm.",
+            "9b09ffa71b942fcb27635fbcd5b0e944bfdc63644f0713938a7f51535c3a35e2",
+        ),
+    ];
+
+    for &(key, message, expected) in cases {
+        assert_eq!(hex(hmac_sha256(key, message)), expected);
+    }
+}
+
+#[test]
+fn partitions_uuid_policy() {
+    use disk_ops::table::{convert_array_to_str, Gpt, Partitioner};
+
+    let policy = UuidPolicy::Seeded(*b"0123456789abcdef");
+    let disk_guid = "AAA377AD-AEDC-45A9-98A7-8F49E8D1412F";
+
+    assert_eq!(convert_array_to_str(&policy.disk_guid()), disk_guid);
+    assert_eq!(
+        convert_array_to_str(&policy.partition_uuid(PartitionKind::LinuxData, 2048)),
+        "414538C7-3118-4D07-896A-01E88B68CDEC"
+    );
+
+    setup(|mut manager, entity| {
+        manager.create_table(entity, PartitionTable::Guid, policy).unwrap();
+
+        let entity_data = manager
+            .create_as_child_of(
+                entity,
+                Sector::Start,
+                Sector::End,
+                Box::from("Data"),
+                None,
+                policy,
+                ops::create::PartitionCreate::Plain(FileSystem::Ext4, FormatOptions::default()),
+            )
+            .unwrap();
+
+        // The PARTUUID is known before the partition is written, and never changes.
+        let partuuid = manager.components.queued_changes.partitions[entity_data].partuuid.clone();
+        assert!(partuuid.is_some());
+
+        apply(&mut manager);
+
+        let partition = &manager.components.devices.partitions[entity_data];
+        assert_eq!(partition.partuuid, partuuid);
+
+        let gpt = Gpt::open(manager.device(entity).path()).unwrap();
+        assert_eq!(gpt.export().label_id.as_ref().map(AsRef::as_ref), Some(disk_guid));
+        assert_eq!(Some(gpt.partitions()[0].uuid.clone()), partuuid);
+    });
+}
+
+#[test]
+fn partitions_numbered_and_sorted() {
+    setup(|mut manager, entity| {
+        manager.create_table(entity, PartitionTable::Guid, UuidPolicy::Random).unwrap();
+
+        let sectors = [
+            (Sector::Start, Sector::Megabyte(512)),
+            (Sector::Megabyte(512), Sector::Megabyte(1024)),
+            (Sector::Megabyte(1024), Sector::End),
+        ];
+
+        let entities = sectors
+            .iter()
+            .map(|&(start, end)| {
+                manager
+                    .create_as_child_of(
+                        entity,
+                        start,
+                        end,
+                        Box::from(""),
+                        None,
+                        UuidPolicy::Random,
+                        ops::create::PartitionCreate::Plain(
+                            FileSystem::Ext4,
+                            FormatOptions::default(),
+                        ),
+                    )
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        manager.request_number(entities[0], 3).unwrap();
+        manager.request_number(entities[1], 1).unwrap();
+        assert!(manager.request_number(entities[2], 3).is_err());
+        apply(&mut manager);
+
+        let number =
+            |manager: &DiskManager, entity| manager.components.devices.partitions[entity].number;
+
+        let numbers = entities.iter().map(|&e| number(&manager, e)).collect::<Vec<_>>();
+        assert_eq!(numbers, vec![3, 1, 2]);
+
+        let renumbered = manager.sort_partitions(entity).unwrap();
+        assert_eq!(renumbered.len(), 3);
+        assert_eq!(renumbered[0].from, manager.device(entities[0]).path);
+        apply(&mut manager);
+
+        let numbers = entities.iter().map(|&e| number(&manager, e)).collect::<Vec<_>>();
+        assert_eq!(numbers, vec![1, 2, 3]);
+        assert_eq!(renumbered[0].to, manager.device(entities[0]).path);
+    });
+}
+
+#[test]
+fn partition_numbers_checked() {
+    use ops::create::Error;
+
+    let mut manager = DiskManager::default();
+    let mut insert = |table| {
+        let entity = manager.entities.devices.insert(EntityFlags::SUPPORTS_TABLE);
+        let devices = &mut manager.components.devices;
+        devices.devices.insert(entity, device(1024 * 1024, 512));
+        devices.tables.insert(entity, table);
+        devices.children.insert(entity, Vec::new());
+        entity
+    };
+
+    let (mbr, gpt) = (insert(PartitionTable::Mbr), insert(PartitionTable::Guid));
+
+    let create = |manager: &mut DiskManager, parent, start, end, what| {
+        manager
+            .create_as_child_of(parent, start, end, Box::from(""), None, UuidPolicy::Random, what)
+            .unwrap()
+    };
+
+    let ext4 = || ops::create::PartitionCreate::Plain(FileSystem::Ext4, FormatOptions::default());
+    let extended = ops::create::PartitionCreate::Extended;
+    let extended = create(&mut manager, mbr, Sector::Megabyte(1), Sector::End, extended);
+    let first = create(&mut manager, mbr, Sector::Megabyte(2), Sector::Megabyte(100), ext4());
+    let second = create(&mut manager, mbr, Sector::Megabyte(100), Sector::Megabyte(200), ext4());
+
+    // Logical partitions are numbered in the order that they are added to the EBR chain.
+    assert_eq!(manager.request_number(extended, 5), Err(Error::NumberInvalid(5)));
+    assert_eq!(manager.request_number(second, 6), Err(Error::NumberInvalid(6)));
+    manager.request_number(second, 5).unwrap();
+    assert_eq!(manager.request_number(first, 5), Err(Error::NumberInvalid(5)));
+    assert_eq!(manager.request_number(first, 7), Err(Error::NumberInvalid(7)));
+    manager.request_number(first, 6).unwrap();
+
+    // GPT numbers are limited by the number of entries in the table.
+    let partition = create(&mut manager, gpt, Sector::Start, Sector::End, ext4());
+    assert_eq!(manager.request_number(partition, 129), Err(Error::NumberInvalid(129)));
+    manager.request_number(partition, 128).unwrap();
+
+    let health = GptHealth {
+        primary:          HeaderHealth::Valid,
+        backup:           HeaderHealth::Valid,
+        backup_misplaced: false,
+        overlapping:      Vec::new(),
+        entries:          8,
+    };
+
+    manager.components.devices.table_health.insert(gpt, health);
+    assert_eq!(manager.request_number(partition, 9), Err(Error::NumberInvalid(9)));
+    manager.request_number(partition, 8).unwrap();
+}
+
+#[test]
+fn partitions_aligned() {
+    setup(|mut manager, entity| {
+        // Emulate a RAID array with a stripe width of 3 MiB.
+        let topology = Topology { optimal_io_size: 3 * 1024 * 1024, ..Default::default() };
+        manager.components.devices.topologies.insert(entity, topology);
+        manager.create_table(entity, PartitionTable::Guid, UuidPolicy::Random).unwrap();
+
+        let create = |manager: &mut DiskManager, start, end| {
+            let child = manager
+                .create_as_child_of(
+                    entity,
+                    Sector::Unit(start),
+                    Sector::Unit(end),
+                    Box::from(""),
+                    None,
+                    UuidPolicy::Random,
+                    ops::create::PartitionCreate::Plain(FileSystem::Ext4, FormatOptions::default()),
+                )
+                .unwrap();
+
+            let queued = &manager.components.queued_changes;
+            (queued.partitions[child].offset, queued.devices[child].sectors)
+        };
+
+        let grain = 3 * 1024 * 1024 / 512;
+        assert_eq!(create(&mut manager, 100, 5 * grain + 100), (grain, 4 * grain));
+
+        manager.set_alignment_policy(AlignmentPolicy::Off);
+        assert_eq!(create(&mut manager, 6 * grain + 1, 7 * grain), (6 * grain + 1, grain - 1));
+    });
+}
+
+#[test]
+fn topology_grain() {
+    let topology = |minimum_io_size, optimal_io_size| Topology {
+        minimum_io_size,
+        optimal_io_size,
+        ..Default::default()
+    };
+
+    const MIB: u64 = 1024 * 1024;
+    let cases = [
+        // Devices which do not report their I/O sizes are aligned to 1 MiB.
+        (topology(0, 0), MIB),
+        (topology(512, 0), MIB),
+        (topology(4096, 0), MIB),
+        // A RAID array with 64 KiB chunks and a 256 KiB stripe width.
+        (topology(64 * 1024, 256 * 1024), MIB),
+        // A RAID array with 64 KiB chunks and a 192 KiB stripe width.
+        (topology(64 * 1024, 192 * 1024), 192 * 1024),
+        // A RAID array which only reports a chunk size of 768 KiB.
+        (topology(768 * 1024, 0), 3 * MIB),
+        // An optimal I/O size which is not a multiple of the minimum.
+        (topology(256 * 1024, 3 * 64 * 1024), 3 * 256 * 1024),
+    ];
+
+    for &(topology, grain) in &cases {
+        assert_eq!(topology.grain(), grain, "{:?}", topology);
+    }
+
+    let device = device(1024 * 1024, 512);
+    assert!(!topology(512, 0).needs_alignment(&device));
+    assert!(topology(768 * 1024, 0).needs_alignment(&device));
+
+    // Partitions on a device with 768 KiB chunks are aligned to 3 MiB.
+    let topology = topology(768 * 1024, 0);
+    assert_eq!(topology.align_up(&device, 2048), 6144);
+    assert_eq!(topology.align_down(&device, 12_287), 6144);
+}
+
+#[test]
+fn partitions_layout() {
+    setup(|mut manager, entity| {
+        manager.create_table(entity, PartitionTable::Guid, UuidPolicy::Random).unwrap();
+
+        let create = |manager: &mut DiskManager, start, end| {
+            manager
+                .create_as_child_of(
+                    entity,
+                    start,
+                    end,
+                    Box::from(""),
+                    None,
+                    UuidPolicy::Random,
+                    ops::create::PartitionCreate::Plain(FileSystem::Ext4, FormatOptions::default()),
+                )
+                .unwrap()
+        };
+
+        let entity_boot = create(&mut manager, Sector::Start, Sector::Megabyte(512));
+        apply(&mut manager);
+
+        // Partitions which are queued to be created are included in the layout.
+        let entity_root = create(&mut manager, Sector::Megabyte(1024), Sector::Megabyte(1536));
+
+        let device = manager.device(entity);
+        let (start, end) =
+            (device.get_sector(Sector::Start).unwrap(), device.get_sector(Sector::End).unwrap());
+
+        // Free space begins and ends 1 MiB from either end of the device.
+        let (head, tail) = (2048, device.sectors - 2048);
+        let boot_end = device.get_sector(Sector::Megabyte(512)).unwrap();
+        let root = (
+            device.get_sector(Sector::Megabyte(1024)).unwrap(),
+            device.get_sector(Sector::Megabyte(1536)).unwrap(),
+        );
+
+        let kinds = |manager: &DiskManager| {
+            manager
+                .layout(entity)
+                .iter()
+                .map(|segment| (segment.kind, segment.start, segment.end()))
+                .collect::<Vec<_>>()
+        };
+
+        use ops::layout::SegmentKind::*;
+        assert_eq!(
+            kinds(&manager),
+            vec![
+                (Reserved, 0, head),
+                (Free, head, start),
+                (Used(entity_boot), start, boot_end),
+                (Free, boot_end, root.0),
+                (Used(entity_root), root.0, root.1),
+                (Free, root.1, tail),
+                (Reserved, tail, device.sectors),
+            ]
+        );
+
+        // Partitions which are queued to be removed are not.
+        manager.remove(entity_boot);
+        assert_eq!(kinds(&manager)[1], (Free, head, root.0));
+
+        let free = manager.free_regions(entity);
+        assert_eq!(free.len(), 2);
+        assert_eq!((free[1].start, free[1].end()), (root.1, tail));
+        assert!(end < tail);
+        assert!(!manager.sector_overlaps(entity, start));
+        assert!(manager.sector_overlaps(entity, root.0));
+    });
+}
+
+#[test]
+fn table_layout() {
+    use ops::layout::SegmentKind::*;
+
+    let cases = [
+        (PartitionTable::Guid, 1024 * 1024, 512, 2048, 1024 * 1024 - 2048),
+        (PartitionTable::Mbr, 1024 * 1024, 512, 2048, 1024 * 1024),
+        (PartitionTable::Guid, 128 * 1024, 4096, 256, 128 * 1024 - 256),
+        (PartitionTable::Guid, 1000, 512, 1000, 1000),
+    ];
+
+    for &(table, sectors, sector_size, head, tail) in &cases {
+        let mut manager = DiskManager::default();
+        let entity = manager.entities.devices.insert(EntityFlags::empty());
+        let devices = &mut manager.components.devices;
+        devices.devices.insert(entity, device(sectors, sector_size));
+        devices.tables.insert(entity, table);
+
+        let layout = manager
+            .layout(entity)
+            .iter()
+            .map(|segment| (segment.kind, segment.start, segment.end()))
+            .collect::<Vec<_>>();
+
+        // Empty ranges are left out of the layout.
+        let expected = [(Reserved, 0, head), (Free, head, tail), (Reserved, tail, sectors)];
+        let expected =
+            expected.iter().cloned().filter(|&(_, start, end)| start < end).collect::<Vec<_>>();
+
+        assert_eq!(layout, expected, "{:?} on {} sectors of {} bytes", table, sectors, sector_size);
+    }
+}
+
+#[test]
+fn fs_capabilities() {
+    let xfs = FileSystem::Xfs.capabilities();
+    assert!(xfs.shrink.is_empty());
+    assert_eq!(xfs.grow, ResizeSupport::ONLINE);
+    assert_eq!(FileSystem::Vfat.capabilities().max_label_len, 11);
+    assert!(!FileSystem::Squashfs.capabilities().creatable);
+
+    setup(|mut manager, entity| {
+        manager.create_table(entity, PartitionTable::Guid, UuidPolicy::Random).unwrap();
+
+        let mut create = |end, fs| {
+            manager.create_as_child_of(
+                entity,
+                Sector::Start,
+                end,
+                Box::from(""),
+                None,
+                UuidPolicy::Random,
+                ops::create::PartitionCreate::Plain(fs, FormatOptions::default()),
+            )
+        };
+
+        assert_eq!(
+            create(Sector::Megabyte(100), FileSystem::Xfs),
+            Err(ops::create::Error::TooSmall(FileSystem::Xfs, 300 * 1024 * 1024))
+        );
+
+        assert_eq!(
+            create(Sector::End, FileSystem::Squashfs),
+            Err(ops::create::Error::FormatUnsupported(FileSystem::Squashfs))
+        );
+
+        // LUKS devices cannot be created without their parameters.
+        assert_eq!(
+            create(Sector::End, FileSystem::Luks),
+            Err(ops::create::Error::FormatUnsupported(FileSystem::Luks))
+        );
+    });
+}
+
+#[test]
+fn format_options() {
+    let options = FormatOptions {
+        label: Some(Box::from("ROOT")),
+        block_size: Some(4096),
+        inode_ratio: Some(16384),
+        ..Default::default()
+    };
+
+    assert_eq!(options.validate(FileSystem::Ext4), Ok(()));
+    assert_eq!(
+        options.validate(FileSystem::Vfat),
+        Err(FormatOptionsError::Unsupported(FileSystem::Vfat, "block size"))
+    );
+
+    let options = FormatOptions { inode_ratio: Some(1024), ..options };
+    assert_eq!(
+        options.validate(FileSystem::Ext4),
+        Err(FormatOptionsError::InodeRatioInvalid(1024))
+    );
+
+    let options =
+        FormatOptions { label: Some(Box::from("A LONG FAT LABEL")), ..Default::default() };
+    assert_eq!(options.validate(FileSystem::Vfat), Err(FormatOptionsError::LabelTooLong(16, 11)));
+
+    let options = FormatOptions { data_profile: Some(BtrfsProfile::Dup), ..Default::default() };
+    assert_eq!(options.validate(FileSystem::Btrfs), Ok(()));
+    assert!(options.validate(FileSystem::Xfs).is_err());
+}
+
+#[test]
+fn format_existing_device() {
+    use crate::systems::{create::CreationSystem, System};
+
+    let mut manager = DiskManager::default();
+    let entity = insert_partition(
+        &mut manager,
+        Partition { filesystem: Some(FileSystem::Vfat), ..Default::default() },
+    );
+
+    let options = FormatOptions { label: Some(Box::from("ROOT")), ..Default::default() };
+    manager
+        .create_on(entity, ops::create::PartitionCreate::Plain(FileSystem::Ext4, options))
+        .unwrap();
+    assert!(manager.entities.devices[entity].contains(EntityFlags::CREATE));
+
+    CreationSystem::default()
+        .run(&mut manager.entities, &mut manager.components, &AtomicBool::new(false))
+        .unwrap();
+
+    // The device already exists, so the modification system must not skip its format.
+    assert!(!manager.entities.devices[entity].contains(EntityFlags::CREATE));
+    let queued = &manager.components.queued_changes;
+    assert_eq!(queued.formats.get(entity).map(|&(fs, _)| fs), Some(FileSystem::Ext4));
+    assert!(queued.partitions.get(entity).is_none());
+}
+
+#[test]
+fn fs_labels() {
+    let mut manager = DiskManager::default();
+    let mut insert =
+        |filesystem| insert_partition(&mut manager, Partition { filesystem, ..Default::default() });
+
+    let (vfat, lvm, raw) =
+        (insert(Some(FileSystem::Vfat)), insert(Some(FileSystem::Lvm)), insert(None));
+
+    assert_eq!(
+        manager.fs_label(vfat, "A LONG FAT LABEL"),
+        Err(ops::modify::Error::LabelTooLong(FileSystem::Vfat, 11))
+    );
+    assert_eq!(
+        manager.fs_label(lvm, "PV"),
+        Err(ops::modify::Error::LabelUnsupported(FileSystem::Lvm))
+    );
+    assert_eq!(manager.fs_label(raw, "DATA"), Err(ops::modify::Error::NoFileSystem));
+
+    // Partition labels and attributes are stored in a partition table, which these lack.
+    assert_eq!(manager.label(vfat, "EFI"), Err(ops::modify::Error::NotPartition));
+    assert_eq!(
+        manager.set_attributes(vfat, PartitionAttributes::HIDDEN),
+        Err(ops::modify::Error::NotPartition)
+    );
+
+    // The file system label is kept apart from the partition label.
+    manager.fs_label(vfat, "EFI").unwrap();
+    let queued = &manager.components.queued_changes;
+    assert_eq!(queued.fs_labels.get(vfat).map(AsRef::as_ref), Some("EFI"));
+    assert!(queued.labels.get(vfat).is_none());
+
+    // A label queued before a format is given to the new file system, which must support it.
+    let format = |fs| ops::create::PartitionCreate::Plain(fs, FormatOptions::default());
+    manager.fs_label(vfat, "EFI SYSTEM").unwrap();
+    assert_eq!(
+        manager.create_on(vfat, format(FileSystem::Lvm)),
+        Err(ops::create::Error::FormatOptions(
+            FileSystem::Lvm,
+            FormatOptionsError::Unsupported(FileSystem::Lvm, "label")
+        ))
+    );
+
+    manager.create_on(vfat, format(FileSystem::Ext4)).unwrap();
+    let queued = &manager.components.queued_changes;
+    assert!(queued.fs_labels.get(vfat).is_none());
+    assert_eq!(
+        queued.formats.get(vfat).and_then(|(_, options)| options.label.as_ref()).map(AsRef::as_ref),
+        Some("EFI SYSTEM")
+    );
+}
+
+#[test]
+fn uuids() {
+    let mut manager = DiskManager::default();
+    let mut insert = |filesystem, uuid: &str, uuid_sub: Option<&str>, partuuid: &str| {
+        let partition = Partition {
+            filesystem,
+            uuid: Some(uuid.into()),
+            uuid_sub: uuid_sub.map(Box::from),
+            partuuid: Some(partuuid.into()),
+            ..Default::default()
+        };
+
+        insert_partition(&mut manager, partition)
+    };
+
+    let ext4 = insert(Some(FileSystem::Ext4), "2a5d-ef", None, "1111");
+    let clone = insert(Some(FileSystem::Ext4), "2A5D-EF", None, "2222");
+    let btrfs = insert(Some(FileSystem::Btrfs), "b7", Some("d1"), "3333");
+    let _raid = insert(Some(FileSystem::Btrfs), "b7", Some("d2"), "4444");
+    let btrfs_clone = insert(Some(FileSystem::Btrfs), "b7", Some("D1"), "3333");
+    let f2fs = insert(Some(FileSystem::F2fs), "f2", None, "5555");
+    let raw = insert(None, "", None, "6666");
+
+    // Devices of a multi-device btrfs file system share a UUID, but not their UUID_SUBs.
+    let collisions =
+        crate::systems::scan::uuid_collisions(&manager.entities, &manager.components.devices);
+    assert_eq!(
+        collisions,
+        vec![
+            UuidCollision {
+                kind:     UuidKind::FileSystem,
+                uuid:     Box::from("2a5d-ef"),
+                entities: vec![ext4, clone],
+            },
+            UuidCollision {
+                kind:     UuidKind::FileSystem,
+                uuid:     Box::from("b7"),
+                entities: vec![btrfs, btrfs_clone],
+            },
+            UuidCollision {
+                kind:     UuidKind::Partition,
+                uuid:     Box::from("3333"),
+                entities: vec![btrfs, btrfs_clone],
+            },
+        ]
+    );
+
+    assert_eq!(
+        manager.regenerate_uuid(f2fs),
+        Err(ops::modify::Error::UuidUnsupported(FileSystem::F2fs))
+    );
+    assert_eq!(manager.regenerate_uuid(raw), Err(ops::modify::Error::NoFileSystem));
+    assert_eq!(
+        manager.regenerate_partuuid(clone, UuidPolicy::Random),
+        Err(ops::modify::Error::NotPartition)
+    );
+    assert_eq!(
+        manager.regenerate_partuuid(btrfs_clone, UuidPolicy::Seeded([0; 16])),
+        Err(ops::modify::Error::PartUuidSeeded)
+    );
+
+    manager.regenerate_uuid(clone).unwrap();
+    assert!(manager.components.queued_changes.fs_uuids.contains_key(clone));
+    assert!(manager.flags.contains(ManagerFlags::UUIDS));
+}
+
+#[test]
+fn fs_check() {
+    let mut manager = DiskManager::default();
+    let mut insert =
+        |filesystem| insert_partition(&mut manager, Partition { filesystem, ..Default::default() });
+
+    let (exfat, raw) = (insert(Some(FileSystem::Exfat)), insert(None));
+
+    assert_eq!(FileSystem::Ext4.check_tool(), Some("e2fsck"));
+    assert_eq!(FileSystem::Vfat.check_tool(), Some("fsck.fat"));
+    assert_eq!(FileSystem::Swap.check_tool(), None);
+
+    match manager.check(exfat, CheckMode::DryRun) {
+        Err(ops::check::Error::CheckUnsupported(FileSystem::Exfat)) => (),
+        result => panic!("exfat should not be checkable: {:?}", result),
+    }
+
+    match manager.check(raw, CheckMode::Force) {
+        Err(ops::check::Error::NoFileSystem) => (),
+        result => panic!("device without a file system was checked: {:?}", result),
+    }
+
+    let result = disk_ops::partition::check(
+        Path::new("/dev/null"),
+        FileSystem::Swap,
+        CheckMode::Repair,
+        |_, _| (),
+    );
+    assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn fs_check_outcomes() {
+    use disk_ops::partition::{check_progress, check_verdict};
+
+    let progress = &[
+        (FileSystem::Ext4, "1 50 100 /dev/sda1", Some((500, 5000))),
+        (FileSystem::Ext4, "5 100 100 /dev/sda1", Some((5000, 5000))),
+        (FileSystem::Ext4, "6 1 100 /dev/sda1", None),
+        (FileSystem::Ext4, "1 1 0 /dev/sda1", None),
+        (FileSystem::Ext4, "/dev/sda1: clean, 11/65536 files", None),
+        (FileSystem::Btrfs, "[1/7] checking root items", Some((0, 7))),
+        (FileSystem::Btrfs, "  [7/7] checking quota groups", Some((6, 7))),
+        (FileSystem::Btrfs, "[8/7] checking quota groups", None),
+        (FileSystem::Btrfs, "Opening filesystem to check...", None),
+        (FileSystem::Xfs, "Phase 1 - find and verify superblock...", Some((0, 7))),
+        (FileSystem::Xfs, "        - agno = 0", None),
+        (FileSystem::Xfs, "Phase 8 - unknown", None),
+        (FileSystem::Vfat, "1 50 100", None),
+    ];
+
+    for &(fs, line, expected) in progress {
+        assert_eq!(check_progress(fs, line), expected, "{}: {:?}", fs, line);
+    }
+
+    let verdicts = &[
+        (FileSystem::Ext4, CheckMode::Repair, 0, Some(CheckVerdict::Clean)),
+        (FileSystem::Ext4, CheckMode::Repair, 1, Some(CheckVerdict::Repaired)),
+        (FileSystem::Ext4, CheckMode::Force, 3, Some(CheckVerdict::Repaired)),
+        (FileSystem::Ext4, CheckMode::ForceRepair, 1, Some(CheckVerdict::Repaired)),
+        (FileSystem::Ext4, CheckMode::ForceRepair, 4, Some(CheckVerdict::ErrorsRemaining)),
+        (FileSystem::Ext4, CheckMode::DryRun, 4, Some(CheckVerdict::ErrorsRemaining)),
+        (FileSystem::Ext4, CheckMode::Repair, 8, None),
+        (FileSystem::F2fs, CheckMode::Repair, 1, Some(CheckVerdict::Repaired)),
+        (FileSystem::Vfat, CheckMode::Repair, 1, Some(CheckVerdict::Repaired)),
+        (FileSystem::Vfat, CheckMode::DryRun, 1, Some(CheckVerdict::ErrorsRemaining)),
+        (FileSystem::Vfat, CheckMode::Repair, 2, None),
+        (FileSystem::Xfs, CheckMode::DryRun, 0, Some(CheckVerdict::Clean)),
+        (FileSystem::Xfs, CheckMode::Repair, 0, Some(CheckVerdict::CleanOrRepaired)),
+        (FileSystem::Xfs, CheckMode::DryRun, 1, Some(CheckVerdict::ErrorsRemaining)),
+        (FileSystem::Xfs, CheckMode::Repair, 2, Some(CheckVerdict::ErrorsRemaining)),
+        (FileSystem::Ntfs, CheckMode::DryRun, 0, Some(CheckVerdict::Clean)),
+        (FileSystem::Ntfs, CheckMode::Force, 0, Some(CheckVerdict::CleanOrRepaired)),
+        (FileSystem::Ntfs, CheckMode::Repair, 1, Some(CheckVerdict::ErrorsRemaining)),
+        // btrfs is only repaired in the `ForceUnsafe` mode.
+        (FileSystem::Btrfs, CheckMode::Force, 0, Some(CheckVerdict::Clean)),
+        (FileSystem::Btrfs, CheckMode::ForceUnsafe, 0, Some(CheckVerdict::CleanOrRepaired)),
+        (FileSystem::Btrfs, CheckMode::Force, 1, Some(CheckVerdict::ErrorsRemaining)),
+    ];
+
+    for &(fs, mode, code, expected) in verdicts {
+        assert_eq!(check_verdict(fs, mode, code), expected, "{} {:?} {}", fs, mode, code);
+    }
+}
+
+#[test]
+fn commands() {
+    use disk_ops::command::{run, run_lines, run_with_input, CommandError};
+    use std::process::Command;
+
+    run(&mut Command::new("true")).unwrap();
+
+    match run(Command::new("sh").args(&["-c", "echo no space left >&2; exit 3"])) {
+        Err(CommandError::ExitStatus(_, status, stderr)) => {
+            assert_eq!(status.code(), Some(3));
+            assert_eq!(&*stderr, "no space left");
+        }
+        result => panic!("failed command was not reported: {:?}", result),
+    }
+
+    match run(&mut Command::new("mkfs.nonexistent")) {
+        Err(CommandError::NotFound(ref program)) => assert_eq!(&**program, "mkfs.nonexistent"),
+        result => panic!("missing program was not reported: {:?}", result),
+    }
+
+    let error = std::io::Error::from(CommandError::NotFound("mkfs.nonexistent".into()));
+    assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+
+    run_with_input(Command::new("grep").arg("-q").arg("secret"), Some(b"secret\n")).unwrap();
+
+    let mut lines = Vec::new();
+    let status = run_lines(Command::new("sh").args(&["-c", "echo 1; echo 2 >&2"]), |line| {
+        lines.push(line.to_owned())
+    })
+    .unwrap();
+    lines.sort();
+    assert!(status.success());
+    assert_eq!(lines, vec!["1", "2"]);
+}
+
+#[test]
+fn tool_support() {
+    use disk_ops::tools::{Operation, SupportMatrix, ToolSupport, Unavailable};
+
+    let tools = SupportMatrix::new(
+        vec!["mkfs.ext4", "e2fsck", "resize2fs", "e2label", "btrfs", "mount", "umount"],
+        vec![FileSystem::Ext4],
+    );
+
+    assert_eq!(
+        tools.support(FileSystem::Ext4),
+        ToolSupport {
+            create: true,
+            resize: true,
+            label:  true,
+            uuid:   false,
+            check:  true,
+            kernel: true,
+        }
+    );
+
+    // Swap is never mounted, and cannot be resized or checked by `disk_ops`.
+    assert_eq!(tools.support(FileSystem::Swap), ToolSupport::default());
+    assert_eq!(tools.rows().count(), 15);
+
+    assert_eq!(
+        tools.available(FileSystem::Ext4, Operation::Uuid),
+        Err(Unavailable::Tool("tune2fs"))
+    );
+    assert_eq!(
+        tools.available(FileSystem::Btrfs, Operation::Resize),
+        Err(Unavailable::Kernel(FileSystem::Btrfs))
+    );
+
+    let mut manager = DiskManager::default();
+    let entity = insert_partition(
+        &mut manager,
+        Partition { filesystem: Some(FileSystem::Ext4), ..Default::default() },
+    );
+
+    // Without a support matrix, operations are not checked for their tools.
+    manager.regenerate_uuid(entity).unwrap();
+    manager.components.queued_changes.clear();
+
+    manager.set_tool_support(Some(tools));
+    assert_eq!(
+        manager.regenerate_uuid(entity),
+        Err(ops::modify::Error::Unavailable(FileSystem::Ext4, Unavailable::Tool("tune2fs")))
+    );
+    assert_eq!(
+        manager.create_on(
+            entity,
+            ops::create::PartitionCreate::Plain(FileSystem::Xfs, FormatOptions::default())
+        ),
+        Err(ops::create::Error::Unavailable(FileSystem::Xfs, Unavailable::Tool("mkfs.xfs")))
+    );
+    manager.fs_label(entity, "DATA").unwrap();
+    manager
+        .create_on(
+            entity,
+            ops::create::PartitionCreate::Plain(FileSystem::Ext4, FormatOptions::default()),
+        )
+        .unwrap();
+}
+
 #[test]
 fn partitions_add() {}
 
@@ -108,10 +1798,95 @@ fn partitions_remove() {}
 fn partitions_add_and_remove() {}
 
 #[test]
-fn partitions_resize() {}
+fn partitions_resize() {
+    setup(|mut manager, entity| {
+        manager.create_table(entity, PartitionTable::Guid, UuidPolicy::Random).unwrap();
+
+        let entity_root = manager
+            .create_as_child_of(
+                entity,
+                Sector::Start,
+                Sector::Megabyte(512),
+                Box::from("Root"),
+                None,
+                UuidPolicy::Random,
+                ops::create::PartitionCreate::Plain(FileSystem::Ext4, FormatOptions::default()),
+            )
+            .unwrap();
+
+        apply(&mut manager);
+
+        let offset = manager.components.devices.partitions[entity_root].offset;
+
+        // Grow the file system, and then shrink it back to a smaller size.
+        for &end in &[Sector::Megabyte(1024), Sector::Megabyte(256)] {
+            manager.resize(entity_root, Sector::Unit(offset), end).unwrap();
+            apply(&mut manager);
+
+            let device = &manager.components.devices.devices[entity_root];
+            assert_eq!(offset + device.sectors, manager.device(entity).get_sector(end).unwrap());
+        }
+
+        // The start of a partition with a file system cannot be moved.
+        assert_eq!(
+            manager.resize(entity_root, Sector::Unit(offset + 2048), Sector::Megabyte(256)),
+            Err(ops::resize::Error::StartMoved)
+        );
+    });
+}
 
 #[test]
-fn partitions_move() {}
+fn partitions_move() {
+    setup(|mut manager, entity| {
+        manager.create_table(entity, PartitionTable::Guid, UuidPolicy::Random).unwrap();
+
+        let entity_data = manager
+            .create_as_child_of(
+                entity,
+                Sector::Megabyte(512),
+                Sector::Megabyte(1024),
+                Box::from("Data"),
+                None,
+                UuidPolicy::Random,
+                ops::create::PartitionCreate::Plain(FileSystem::Ext4, FormatOptions::default()),
+            )
+            .unwrap();
+
+        apply(&mut manager);
+
+        let partuuid = manager.components.devices.partitions[entity_data].partuuid.clone();
+        let sectors = manager.components.devices.devices[entity_data].sectors;
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        manager.on_move_progress(move |moved, copied, total| {
+            let _ = sender.send((moved, copied, total));
+        });
+
+        // Move left, and then back to the right, overlapping the old sectors both times.
+        for &start in &[Sector::Megabyte(256), Sector::Megabyte(512)] {
+            manager.move_partition(entity_data, start).unwrap();
+            apply(&mut manager);
+
+            let offset = manager.components.devices.partitions[entity_data].offset;
+            assert_eq!(offset, manager.device(entity).get_sector(start).unwrap());
+            assert_eq!(manager.components.devices.devices[entity_data].sectors, sectors);
+
+            let last = receiver.try_iter().last().expect("no progress was reported");
+            assert_eq!(last, (entity_data, sectors, sectors));
+        }
+
+        // The partition must keep its PARTUUID in the partition table.
+        let dump = manager.dump_table(entity).unwrap().unwrap();
+        assert_eq!(dump.partitions[0].uuid, partuuid);
+
+        // A partition cannot be resized while it is queued to be moved.
+        manager.move_partition(entity_data, Sector::Megabyte(256)).unwrap();
+        assert_eq!(
+            manager.resize(entity_data, Sector::Megabyte(256), Sector::Megabyte(1024)),
+            Err(ops::resize::Error::MoveAndResize)
+        );
+    });
+}
 
 #[test]
 fn fs_on_luks() {}